@@ -0,0 +1,170 @@
+/*
+Criterion benchmarks of each engine on synthetic audio, sweeping num_threads.
+Models are read from environment variables, engines without one are skipped:
+
+SHERPA_BENCH_WHISPER=sherpa-onnx-whisper-tiny \
+SHERPA_BENCH_VAD=silero_vad.onnx \
+SHERPA_BENCH_SPEAKER=nemo_en_speakerverification_speakernet.onnx \
+SHERPA_BENCH_TTS=vits-piper-en_US-amy-low \
+cargo bench --bench engines
+
+Throughput is in samples per second, divide by 16000 for the inverse real-time factor.
+See `examples/bench.rs` for latency percentiles and peak memory.
+*/
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sherpa_rs::{
+    bench::synthetic_audio,
+    speaker_id::{EmbeddingExtractor, ExtractorConfig},
+    transcribe::whisper::WhisperRecognizer,
+    vad::{Vad, VadConfig},
+};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const SAMPLE_RATE: i32 = 16000;
+const AUDIO_SECONDS: f32 = 10.0;
+const THREADS: [i32; 3] = [1, 2, 4];
+
+fn model(var: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os(var)?);
+    if !path.exists() {
+        panic!("{} points to missing {}", var, path.display());
+    }
+    Some(path)
+}
+
+/// The first file in `dir` ending with `suffix`, preferring full precision models
+fn find(dir: &Path, suffix: &str) -> PathBuf {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(suffix))
+        .collect();
+    files.sort_by_key(|path| path.to_string_lossy().contains("int8"));
+    files
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| panic!("no *{} in {}", suffix, dir.display()))
+}
+
+fn whisper(c: &mut Criterion) {
+    let Some(dir) = model("SHERPA_BENCH_WHISPER") else {
+        return;
+    };
+    let samples = synthetic_audio(AUDIO_SECONDS, SAMPLE_RATE);
+    let mut group = c.benchmark_group("whisper");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    group.sample_size(10);
+    for num_threads in THREADS {
+        let recognizer = WhisperRecognizer::builder(
+            find(&dir, "encoder.onnx"),
+            find(&dir, "decoder.onnx"),
+            find(&dir, "tokens.txt"),
+        )
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("transcribe", num_threads),
+            &samples,
+            |b, samples| b.iter(|| recognizer.transcribe(SAMPLE_RATE, samples.clone()).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn vad(c: &mut Criterion) {
+    let Some(path) = model("SHERPA_BENCH_VAD") else {
+        return;
+    };
+    let samples = synthetic_audio(AUDIO_SECONDS, SAMPLE_RATE);
+    let mut group = c.benchmark_group("vad");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    for num_threads in THREADS {
+        let config = VadConfig::builder(&path)
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut vad = Vad::new_from_config(config, AUDIO_SECONDS + 1.0).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("accept_waveform", num_threads),
+            &samples,
+            |b, samples| {
+                b.iter(|| {
+                    for window in samples.chunks_exact(512) {
+                        vad.accept_waveform(window.to_vec());
+                    }
+                    vad.clear();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn speaker_embedding(c: &mut Criterion) {
+    let Some(path) = model("SHERPA_BENCH_SPEAKER") else {
+        return;
+    };
+    let samples = synthetic_audio(AUDIO_SECONDS, SAMPLE_RATE);
+    let mut group = c.benchmark_group("speaker_embedding");
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    for num_threads in THREADS {
+        let config = ExtractorConfig::builder(&path)
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut extractor = EmbeddingExtractor::new_from_config(config).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("compute_speaker_embedding", num_threads),
+            &samples,
+            |b, samples| {
+                b.iter(|| {
+                    extractor
+                        .compute_speaker_embedding(SAMPLE_RATE, samples.clone())
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+#[cfg(feature = "tts")]
+fn tts(c: &mut Criterion) {
+    use sherpa_rs::tts::{OfflineTts, OfflineTtsConfig, TtsVitsModelConfig};
+
+    let Some(dir) = model("SHERPA_BENCH_TTS") else {
+        return;
+    };
+    let text = "The quick brown fox jumps over the lazy dog. It was a sunny morning.";
+    let mut group = c.benchmark_group("tts");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(20));
+    for num_threads in THREADS {
+        let model = TtsVitsModelConfig::builder(find(&dir, ".onnx"), dir.join("tokens.txt"))
+            .data_dir(dir.join("espeak-ng-data"));
+        let config = OfflineTtsConfig::builder(model)
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut tts = OfflineTts::new(config).unwrap();
+        let generated = tts.generate(text.into(), 0, 1.0).unwrap();
+        group.throughput(Throughput::Elements(generated.samples.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("generate", num_threads),
+            text,
+            |b, text| b.iter(|| tts.generate(text.into(), 0, 1.0).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "tts"))]
+fn tts(_: &mut Criterion) {}
+
+criterion_group!(benches, whisper, vad, speaker_embedding, tts);
+criterion_main!(benches);
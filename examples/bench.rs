@@ -0,0 +1,56 @@
+/*
+wget https://github.com/k2-fsa/sherpa-onnx/releases/download/asr-models/sherpa-onnx-whisper-tiny.tar.bz2
+tar xvf sherpa-onnx-whisper-tiny.tar.bz2
+wget https://github.com/snakers4/silero-vad/raw/master/files/silero_vad.onnx
+wget https://github.com/k2-fsa/sherpa-onnx/releases/download/speaker-recongition-models/nemo_en_speakerverification_speakernet.onnx
+wget https://github.com/k2-fsa/sherpa-onnx/releases/download/tts-models/vits-piper-en_US-amy-low.tar.bz2
+tar xvf vits-piper-en_US-amy-low.tar.bz2
+cargo run --release --example bench
+*/
+
+use eyre::Result;
+use sherpa_rs::{
+    bench::{self, BenchOptions, BenchResult},
+    speaker_id::ExtractorConfig,
+    transcribe::whisper::WhisperRecognizer,
+    tts::{OfflineTtsConfig, TtsVitsModelConfig},
+    vad::VadConfig,
+};
+
+fn main() -> Result<()> {
+    let threads = [1, 2, 4];
+    let options = BenchOptions::default();
+
+    let whisper = WhisperRecognizer::builder(
+        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-tokens.txt",
+    );
+    let vad = VadConfig::builder("silero_vad.onnx");
+    let extractor = ExtractorConfig::builder("nemo_en_speakerverification_speakernet.onnx");
+    let tts = OfflineTtsConfig::builder(
+        TtsVitsModelConfig::builder(
+            "vits-piper-en_US-amy-low/en_US-amy-low.onnx",
+            "vits-piper-en_US-amy-low/tokens.txt",
+        )
+        .data_dir("vits-piper-en_US-amy-low/espeak-ng-data"),
+    );
+
+    let mut results = bench::bench_whisper(&whisper, &threads, 10.0, &options)?;
+    results.extend(bench::bench_vad(&vad, &threads, 60.0, &options)?);
+    results.extend(bench::bench_extractor(
+        &extractor, &threads, 10.0, &options,
+    )?);
+    results.extend(bench::bench_tts(
+        &tts,
+        &threads,
+        "The quick brown fox jumps over the lazy dog.",
+        &options,
+    )?);
+
+    println!("{}", BenchResult::HEADER);
+    for result in results {
+        println!("{}", result);
+    }
+    Ok(())
+}
@@ -33,7 +33,9 @@ fn get_speaker_name(
             *speaker_counter += 1;
         }
     } else {
-        let matches = embedding_manager.get_best_matches(embedding, 0.2, *speaker_counter);
+        let matches = embedding_manager
+            .get_best_matches(embedding, 0.2, *speaker_counter)
+            .unwrap();
         if let Some(name_match) = matches.first().map(|m| m.name.clone()) {
             name = name_match;
         }
@@ -50,8 +52,7 @@ fn process_speech_segment(
     speaker_counter: &mut i32,
     max_speakers: i32,
 ) -> Result<()> {
-    while !vad.is_empty() {
-        let segment = vad.front();
+    while let Some(segment) = vad.front() {
         let start_sec = (segment.start as f32) / sample_rate as f32;
         let duration_sec = (segment.samples.len() as f32) / sample_rate as f32;
 
@@ -98,31 +99,23 @@ fn main() -> Result<()> {
         samples.push(0.0);
     }
 
-    let extractor_config = speaker_id::ExtractorConfig::new(
-        "nemo_en_speakerverification_speakernet.onnx".into(),
-        None,
-        None,
-        false,
-    );
+    let extractor_config =
+        speaker_id::ExtractorConfig::builder("nemo_en_speakerverification_speakernet.onnx")
+            .build()?;
     let mut extractor = speaker_id::EmbeddingExtractor::new_from_config(extractor_config).unwrap();
     let mut embedding_manager =
         embedding_manager::EmbeddingManager::new(extractor.embedding_size.try_into().unwrap()); // Assuming dimension 512 for embeddings
 
     let mut speaker_counter = 1;
 
-    let vad_model = "silero_vad.onnx".into();
     let window_size: usize = 512;
-    let config = VadConfig::new(
-        vad_model,
-        0.5,
-        0.5,
-        0.5,
-        sample_rate,
-        window_size.try_into().unwrap(),
-        None,
-        None,
-        Some(false),
-    );
+    let config = VadConfig::builder("silero_vad.onnx")
+        .min_silence_duration(0.5)
+        .min_speech_duration(0.5)
+        .threshold(0.5)
+        .sample_rate(sample_rate)
+        .window_size(window_size.try_into().unwrap())
+        .build()?;
 
     let mut vad = Vad::new_from_config(config, 60.0 * 10.0).unwrap();
     let mut index = 0;
@@ -41,42 +41,31 @@ fn main() -> Result<()> {
         samples.push(0.0);
     }
 
-    let extractor_config = speaker_id::ExtractorConfig::new(
-        "nemo_en_speakerverification_speakernet.onnx".into(),
-        None,
-        None,
-        false,
-    );
+    let extractor_config =
+        speaker_id::ExtractorConfig::builder("nemo_en_speakerverification_speakernet.onnx")
+            .build()?;
     let mut extractor = speaker_id::EmbeddingExtractor::new_from_config(extractor_config).unwrap();
     let mut embedding_manager =
         embedding_manager::EmbeddingManager::new(extractor.embedding_size.try_into().unwrap()); // Assuming dimension 512 for embeddings
 
-    let mut recognizer = WhisperRecognizer::new(
-        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx".into(),
-        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx".into(),
-        "sherpa-onnx-whisper-tiny/tiny-tokens.txt".into(),
-        "en".into(),
-        Some(false),
-        None,
-        None,
-        None,
-    );
+    let recognizer = WhisperRecognizer::builder(
+        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-tokens.txt",
+    )
+    .language("en")
+    .build()?;
 
     let mut speaker_counter = 0;
 
-    let vad_model = "silero_vad.onnx".into();
     let window_size: usize = 512;
-    let config = VadConfig::new(
-        vad_model,
-        0.4,
-        0.4,
-        0.5,
-        sample_rate,
-        window_size.try_into().unwrap(),
-        None,
-        None,
-        Some(false),
-    );
+    let config = VadConfig::builder("silero_vad.onnx")
+        .min_silence_duration(0.4)
+        .min_speech_duration(0.4)
+        .threshold(0.5)
+        .sample_rate(sample_rate)
+        .window_size(window_size.try_into().unwrap())
+        .build()?;
 
     let mut vad = Vad::new_from_config(config, 60.0 * 10.0).unwrap();
     let mut index = 0;
@@ -84,11 +73,10 @@ fn main() -> Result<()> {
         let window = &samples[index..index + window_size];
         vad.accept_waveform(window.to_vec()); // Convert slice to Vec
         if vad.is_speech() {
-            while !vad.is_empty() {
-                let segment = vad.front();
+            while let Some(segment) = vad.front() {
                 let start_sec = (segment.start as f32) / sample_rate as f32;
                 let duration_sec = (segment.samples.len() as f32) / sample_rate as f32;
-                let transcript = recognizer.transcribe(sample_rate, segment.samples.clone());
+                let transcript = recognizer.transcribe(sample_rate, segment.samples.clone())?;
 
                 // Compute the speaker embedding
                 let mut embedding =
@@ -119,11 +107,10 @@ fn main() -> Result<()> {
     if index < samples.len() {
         let remaining_samples = &samples[index..];
         vad.accept_waveform(remaining_samples.to_vec());
-        while !vad.is_empty() {
-            let segment = vad.front();
+        while let Some(segment) = vad.front() {
             let start_sec = (segment.start as f32) / sample_rate as f32;
             let duration_sec = (segment.samples.len() as f32) / sample_rate as f32;
-            let transcript = recognizer.transcribe(sample_rate, segment.samples.clone());
+            let transcript = recognizer.transcribe(sample_rate, segment.samples.clone())?;
 
             // Compute the speaker embedding
             let mut embedding =
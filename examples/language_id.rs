@@ -27,11 +27,18 @@ fn main() -> Result<()> {
         .map(|s| s.unwrap() as f32 / i16::MAX as f32)
         .collect();
 
-    let encoder = "sherpa-onnx-whisper-tiny/tiny-encoder.onnx".into();
-    let decoder = "sherpa-onnx-whisper-tiny/tiny-decoder.onnx".into();
-    let mut extractor = language_id::SpokenLanguageId::new(encoder, decoder, None, None, None);
+    let encoder = "sherpa-onnx-whisper-tiny/tiny-encoder.onnx";
+    let decoder = "sherpa-onnx-whisper-tiny/tiny-decoder.onnx";
+    let mut extractor = language_id::SpokenLanguageId::builder(encoder, decoder)
+        .num_threads(2)
+        .build()?;
+
+    let languages = extractor.compute_top_k(&samples, sample_rate, 3)?;
+    for language in &languages {
+        println!("{}: {} windows", language.lang, language.windows);
+    }
 
-    let language = extractor.compute(samples, sample_rate)?;
+    let language = extractor.compute(&samples, sample_rate)?;
     println!("Spoken language: {}", language);
 
     Ok(())
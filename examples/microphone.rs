@@ -0,0 +1,46 @@
+/*
+wget https://github.com/snakers4/silero-vad/raw/master/files/silero_vad.onnx
+wget https://github.com/k2-fsa/sherpa-onnx/releases/download/asr-models/sherpa-onnx-whisper-tiny.tar.bz2
+tar xvf sherpa-onnx-whisper-tiny.tar.bz2
+cargo run --example microphone --features audio-io
+*/
+use eyre::Result;
+use sherpa_rs::{
+    audio_io::Microphone,
+    transcribe::whisper::WhisperRecognizer,
+    vad::{Vad, VadConfig},
+};
+
+fn main() -> Result<()> {
+    let sample_rate = 16000;
+    let window_size = 512;
+
+    let config = VadConfig::builder("silero_vad.onnx")
+        .min_silence_duration(0.5)
+        .sample_rate(sample_rate)
+        .window_size(window_size)
+        .build()?;
+    let mut vad = Vad::new_from_config(config, 30.0)?;
+    let recognizer = WhisperRecognizer::builder(
+        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-tokens.txt",
+    )
+    .build()?;
+
+    // One VAD window per chunk
+    let microphone = Microphone::builder(sample_rate)
+        .chunk_seconds(window_size as f32 / sample_rate as f32)
+        .build()?;
+    println!("Listening, press Ctrl+C to stop");
+    for window in microphone {
+        vad.accept_waveform(window?);
+        while let Some(segment) = vad.front() {
+            vad.pop();
+            let result = recognizer.transcribe(sample_rate, segment.samples)?;
+            let start = segment.start as f32 / sample_rate as f32;
+            println!("[{:.1}s] {}", start, result.text.trim());
+        }
+    }
+    Ok(())
+}
@@ -7,7 +7,6 @@ cargo run --example speaker_embedding 16hz_mono_pcm_s16le.wav
 use eyre::{bail, Result};
 use sherpa_rs::speaker_id;
 use std::io::Cursor;
-use std::path::PathBuf;
 
 fn main() -> Result<()> {
     let file_path = std::env::args().nth(1).expect("Missing file path argument");
@@ -30,18 +29,13 @@ fn main() -> Result<()> {
         .collect();
 
     // Create the extractor configuration and extractor
-    let mut model_path = PathBuf::from(std::env::current_dir()?);
+    let mut model_path = std::env::current_dir()?;
     model_path.push("nemo_en_speakerverification_speakernet.onnx");
 
     println!("loading model from {}", model_path.display());
 
     // Create the extractor configuration and extractor
-    let config = speaker_id::ExtractorConfig::new(
-        model_path.into_os_string().into_string().unwrap(),
-        None,
-        None,
-        false,
-    );
+    let config = speaker_id::ExtractorConfig::builder(model_path).build()?;
     let mut extractor = speaker_id::EmbeddingExtractor::new_from_config(config).unwrap();
 
     // Compute the speaker embedding
@@ -6,7 +6,7 @@ cargo run --example transcribe
 */
 
 use eyre::{bail, Result};
-use sherpa_rs::transcribe::whisper::WhisperRecognizer;
+use sherpa_rs::{transcribe::whisper::WhisperRecognizer, Provider};
 
 fn read_audio_file(path: &str) -> Result<(i32, Vec<f32>)> {
     let mut reader = hound::WavReader::open(path)?;
@@ -34,17 +34,16 @@ fn main() -> Result<()> {
         bail!("The sample rate must be 16000.");
     }
 
-    let mut recognizer = WhisperRecognizer::new(
-        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx".into(),
-        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx".into(),
-        "sherpa-onnx-whisper-tiny/tiny-tokens.txt".into(),
-        "en".into(),
-        Some(true),
-        Some("directml".into()),
-        None,
-        None,
-    );
-    let result = recognizer.transcribe(sample_rate, samples);
+    let recognizer = WhisperRecognizer::builder(
+        "sherpa-onnx-whisper-tiny/tiny-encoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-decoder.onnx",
+        "sherpa-onnx-whisper-tiny/tiny-tokens.txt",
+    )
+    .language("en")
+    .debug(true)
+    .provider(Provider::DirectMl)
+    .build()?;
+    let result = recognizer.transcribe(sample_rate, samples)?;
     println!("{:?}", result);
     Ok(())
 }
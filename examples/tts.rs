@@ -46,10 +46,14 @@ struct Args {
     lexicon: Option<String>,
 
     #[arg(long)]
-    provider: Option<String>,
+    provider: Option<sherpa_rs::Provider>,
 
     #[arg(long)]
     debug: bool,
+
+    /// Treat the text as SSML-lite markup (<break>, <prosody>, <voice>, <say-as>)
+    #[arg(long)]
+    markup: bool,
 }
 
 fn main() {
@@ -62,24 +66,30 @@ fn main() {
         text = std::fs::read_to_string(args.text_file_input.unwrap()).unwrap();
     }
 
-    let vits_cfg = sherpa_rs::tts::TtsVitsModelConfig::new(
-        args.model,
-        args.lexicon.unwrap_or_default(),
-        args.tokens,
-        args.data_dir.unwrap_or_default(),
-        0.0,
-        0.0,
-        args.dict_dir.unwrap_or_default(),
-        0.0,
-    );
-    let max_num_sentences = 2;
-    let model_cfg =
-        sherpa_rs::tts::OfflineTtsModelConfig::new(args.debug, vits_cfg, args.provider, 1);
-    let tts_cfg =
-        sherpa_rs::tts::OfflineTtsConfig::new(model_cfg, max_num_sentences, "".into(), "".into());
-    let mut tts = sherpa_rs::tts::OfflineTts::new(tts_cfg);
+    let mut vits_cfg = sherpa_rs::tts::TtsVitsModelConfig::builder(args.model, args.tokens);
+    if let Some(lexicon) = args.lexicon {
+        vits_cfg = vits_cfg.lexicon(lexicon);
+    }
+    if let Some(data_dir) = args.data_dir {
+        vits_cfg = vits_cfg.data_dir(data_dir);
+    }
+    if let Some(dict_dir) = args.dict_dir {
+        vits_cfg = vits_cfg.dict_dir(dict_dir);
+    }
+    let mut tts_cfg = sherpa_rs::tts::OfflineTtsConfig::builder(vits_cfg)
+        .debug(args.debug)
+        .max_num_sentences(2);
+    if let Some(provider) = args.provider {
+        tts_cfg = tts_cfg.provider(provider);
+    }
+    let tts_cfg = tts_cfg.build().unwrap();
+    let mut tts = sherpa_rs::tts::OfflineTts::new(tts_cfg).unwrap();
     let speed = 1.0;
-    let audio = tts.generate(text, 0, speed).unwrap();
+    let audio = if args.markup {
+        tts.generate_markup(&text, 0, speed).unwrap().sample
+    } else {
+        tts.generate(text, 0, speed).unwrap()
+    };
     audio.write_to_wav(&args.output).unwrap(); // Use the provided output path
     println!("Created {}", args.output);
 }
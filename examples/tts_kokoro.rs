@@ -0,0 +1,71 @@
+/*
+Kokoro English model
+wget https://github.com/k2-fsa/sherpa-onnx/releases/download/tts-models/kokoro-en-v0_19.tar.bz2
+tar xf kokoro-en-v0_19.tar.bz2
+cargo run --example tts_kokoro --features="tts" -- --text 'liliana, the most beautiful and lovely assistant of our team!' --output audio.wav --model 'kokoro-en-v0_19/model.onnx' --voices 'kokoro-en-v0_19/voices.bin' --tokens 'kokoro-en-v0_19/tokens.txt' --data-dir 'kokoro-en-v0_19/espeak-ng-data'
+*/
+use clap::Parser;
+
+/// Kokoro TTS
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(short, long)]
+    tokens: String,
+
+    #[arg(short, long)]
+    model: String,
+
+    #[arg(long)]
+    voices: String,
+
+    #[arg(long)]
+    text: String,
+
+    #[arg(short, long)]
+    output: String,
+
+    #[arg(long)]
+    data_dir: String,
+
+    #[arg(long)]
+    dict_dir: Option<String>,
+
+    #[arg(long)]
+    lexicon: Option<String>,
+
+    #[arg(long, default_value_t = 0)]
+    sid: i32,
+
+    #[arg(long)]
+    provider: Option<sherpa_rs::Provider>,
+
+    #[arg(long)]
+    debug: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut kokoro_cfg =
+        sherpa_rs::tts::TtsKokoroModelConfig::builder(args.model, args.voices, args.tokens)
+            .data_dir(args.data_dir);
+    if let Some(dict_dir) = args.dict_dir {
+        kokoro_cfg = kokoro_cfg.dict_dir(dict_dir);
+    }
+    if let Some(lexicon) = args.lexicon {
+        kokoro_cfg = kokoro_cfg.lexicon(lexicon);
+    }
+    let mut tts_cfg = sherpa_rs::tts::OfflineTtsConfig::builder(kokoro_cfg)
+        .debug(args.debug)
+        .max_num_sentences(2);
+    if let Some(provider) = args.provider {
+        tts_cfg = tts_cfg.provider(provider);
+    }
+    let tts_cfg = tts_cfg.build().unwrap();
+    let mut tts = sherpa_rs::tts::OfflineTts::new(tts_cfg).unwrap();
+    let speed = 1.0;
+    let audio = tts.generate(args.text, args.sid, speed).unwrap();
+    audio.write_to_wav(&args.output).unwrap();
+    println!("Created {}", args.output);
+}
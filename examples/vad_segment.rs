@@ -24,27 +24,22 @@ fn main() -> Result<()> {
         .map(|s| s.unwrap() as f32 / i16::MAX as f32)
         .collect();
 
-    let model = "silero_vad.onnx".into();
     let window_size: usize = 512;
-    let config = VadConfig::new(
-        model,
-        0.5,
-        0.5,
-        0.5,
-        sample_rate,
-        window_size.try_into().unwrap(),
-        None,
-        None,
-        Some(true),
-    );
+    let config = VadConfig::builder("silero_vad.onnx")
+        .min_silence_duration(0.5)
+        .min_speech_duration(0.5)
+        .threshold(0.5)
+        .sample_rate(sample_rate)
+        .window_size(window_size.try_into().unwrap())
+        .debug(true)
+        .build()?;
 
     let mut vad = Vad::new_from_config(config, 3.0).unwrap();
     while samples.len() > window_size {
         let window = &samples[..window_size];
         vad.accept_waveform(window.to_vec()); // Convert slice to Vec
         if vad.is_speech() {
-            while !vad.is_empty() {
-                let segment = vad.front();
+            while let Some(segment) = vad.front() {
                 let start_sec = (segment.start as f32) / sample_rate as f32;
                 let duration_sec = (segment.samples.len() as f32) / sample_rate as f32;
                 println!("start={}s duration={}s", start_sec, duration_sec);
@@ -0,0 +1,633 @@
+use crate::Result;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::io::{Cursor, Write};
+
+/// Number of sinc zero crossings on each side of the resampling kernel.
+const RESAMPLE_ZERO_CROSSINGS: f64 = 16.0;
+
+/// Length of the windows overlap-added by `time_stretch`
+const STRETCH_WINDOW_SECONDS: f32 = 0.04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// WAV with 16-bit signed PCM samples
+    WavPcm16,
+    /// WAV with 32-bit float samples
+    WavFloat32,
+    /// Headerless 16-bit signed little endian PCM
+    PcmS16Le,
+    /// Headerless 32-bit float little endian PCM
+    PcmF32Le,
+    /// Opus in an Ogg container, always encoded at 48kHz
+    #[cfg(feature = "opus")]
+    OggOpus,
+    #[cfg(feature = "mp3")]
+    Mp3,
+}
+
+impl AudioFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioFormat::WavPcm16 | AudioFormat::WavFloat32 => "audio/wav",
+            AudioFormat::PcmS16Le | AudioFormat::PcmF32Le => "application/octet-stream",
+            #[cfg(feature = "opus")]
+            AudioFormat::OggOpus => "audio/ogg",
+            #[cfg(feature = "mp3")]
+            AudioFormat::Mp3 => "audio/mpeg",
+        }
+    }
+}
+
+/// Resample mono audio with a windowed sinc filter.
+/// When downsampling the cutoff follows the target rate so it doesn't alias.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let filter = Filter::new(from_rate, to_rate);
+    let out_len = (samples.len() as f64 * filter.ratio).round() as usize;
+    (0..out_len)
+        .map(|i| filter.apply(samples, i as f64 / filter.ratio))
+        .collect()
+}
+
+/// Resampler of audio that arrives in chunks, such as a capture stream.
+///
+/// Resampling each chunk on its own cuts the filter at every boundary, this keeps
+/// the input the filter still needs and produces each output sample once all of
+/// it has arrived. The output lags the input by half the filter width, about a
+/// millisecond.
+#[cfg(feature = "audio-io")]
+#[derive(Debug, Clone)]
+pub(crate) struct StreamResampler {
+    filter: Option<Filter>,
+    /// Input the next output samples depend on
+    history: Vec<f32>,
+    /// Index of the first sample of `history` in the stream
+    offset: usize,
+    /// Output samples produced so far
+    produced: usize,
+}
+
+#[cfg(feature = "audio-io")]
+impl StreamResampler {
+    pub(crate) fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            filter: (from_rate != to_rate).then(|| Filter::new(from_rate, to_rate)),
+            history: Vec::new(),
+            offset: 0,
+            produced: 0,
+        }
+    }
+
+    /// Output samples that `samples` completes
+    pub(crate) fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        let Some(filter) = &self.filter else {
+            return samples.to_vec();
+        };
+        self.history.extend_from_slice(samples);
+        let received = self.offset + self.history.len();
+        let mut output = Vec::new();
+        loop {
+            let position = self.produced as f64 / filter.ratio;
+            if (position + filter.half_width).floor() as usize >= received {
+                break;
+            }
+            output.push(filter.apply(&self.history, position - self.offset as f64));
+            self.produced += 1;
+        }
+
+        let position = self.produced as f64 / filter.ratio;
+        let needed = ((position - filter.half_width).ceil().max(0.0) as usize).min(received);
+        if needed > self.offset {
+            self.history.drain(..needed - self.offset);
+            self.offset = needed;
+        }
+        output
+    }
+
+    /// Start over, as after a gap in the stream
+    pub(crate) fn reset(&mut self) {
+        self.history.clear();
+        self.offset = 0;
+        self.produced = 0;
+    }
+}
+
+/// Windowed sinc low-pass filter for resampling between two rates
+#[derive(Debug, Clone)]
+struct Filter {
+    ratio: f64,
+    cutoff: f64,
+    half_width: f64,
+}
+
+impl Filter {
+    fn new(from_rate: u32, to_rate: u32) -> Self {
+        let ratio = to_rate as f64 / from_rate as f64;
+        let cutoff = ratio.min(1.0);
+        Self {
+            ratio,
+            cutoff,
+            half_width: RESAMPLE_ZERO_CROSSINGS / cutoff,
+        }
+    }
+
+    /// Value at `position` between the samples, those outside `samples` count as silence
+    fn apply(&self, samples: &[f32], position: f64) -> f32 {
+        let last = samples.len() as i64 - 1;
+        let first = ((position - self.half_width).ceil() as i64).max(0);
+        let end = ((position + self.half_width).floor() as i64).min(last);
+        let mut value = 0.0;
+        for j in first..=end {
+            let x = position - j as f64;
+            let window = 0.5 * (1.0 + (std::f64::consts::PI * x / self.half_width).cos());
+            value += samples[j as usize] as f64 * self.cutoff * sinc(self.cutoff * x) * window;
+        }
+        value as f32
+    }
+}
+
+/// Change the length of `samples` to `len` without changing the pitch (WSOLA).
+///
+/// Windows of the input are overlap-added at a fixed output hop, each one shifted
+/// by up to a quarter window to line up with the waveform of the previous one.
+pub fn time_stretch(samples: &[f32], sample_rate: u32, len: usize) -> Vec<f32> {
+    let window = (sample_rate as f32 * STRETCH_WINDOW_SECONDS) as usize & !1;
+    if samples.len() == len || samples.len() <= window || window < 4 || len == 0 {
+        // Shorter than a window, resampling only shifts the pitch of a few milliseconds
+        let mut stretched = resample(samples, samples.len() as u32, len as u32);
+        stretched.resize(len, 0.0);
+        return stretched;
+    }
+    let hop_out = window / 2;
+    let hop_in = hop_out as f64 * samples.len() as f64 / len as f64;
+    let tolerance = window / 4;
+    let last_start = samples.len() - window;
+    let hann: Vec<f32> = (0..window)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / window as f32).cos())
+        .collect();
+
+    let mut output = vec![0.0f32; len + window];
+    let mut weight = vec![0.0f32; len + window];
+    let mut previous = 0;
+    for frame in 0..=len / hop_out {
+        let nominal = ((frame as f64 * hop_in) as usize).min(last_start);
+        let start = match frame {
+            0 => 0,
+            _ => {
+                // Where the previous window would have continued without a jump
+                let natural = (previous + hop_out).min(last_start);
+                let reference = &samples[natural..natural + hop_out];
+                (nominal.saturating_sub(tolerance)..=(nominal + tolerance).min(last_start))
+                    .max_by(|&a, &b| {
+                        let score = |start: usize| -> f32 {
+                            samples[start..start + hop_out]
+                                .iter()
+                                .zip(reference)
+                                .map(|(x, y)| x * y)
+                                .sum()
+                        };
+                        score(a).total_cmp(&score(b))
+                    })
+                    .unwrap_or(nominal)
+            }
+        };
+        let position = frame * hop_out;
+        for i in 0..window {
+            output[position + i] += samples[start + i] * hann[i];
+            weight[position + i] += hann[i];
+        }
+        previous = start;
+    }
+
+    output.truncate(len);
+    for (sample, weight) in output.iter_mut().zip(weight) {
+        if weight > 1e-3 {
+            *sample /= weight;
+        }
+    }
+    output
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+pub(crate) fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Encode mono samples in the given format into any writer
+pub fn write_audio<W: Write>(
+    mut writer: W,
+    samples: &[f32],
+    sample_rate: u32,
+    format: AudioFormat,
+) -> Result<()> {
+    match format {
+        AudioFormat::WavPcm16 => {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            };
+            // hound needs Seek to patch the header, so encode in memory first
+            let mut buffer = Cursor::new(Vec::new());
+            let mut wav = WavWriter::new(&mut buffer, spec)?;
+            for &sample in samples {
+                wav.write_sample(to_i16(sample))?;
+            }
+            wav.finalize()?;
+            writer.write_all(buffer.get_ref())?;
+        }
+        AudioFormat::WavFloat32 => {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate,
+                bits_per_sample: 32,
+                sample_format: SampleFormat::Float,
+            };
+            let mut buffer = Cursor::new(Vec::new());
+            let mut wav = WavWriter::new(&mut buffer, spec)?;
+            for &sample in samples {
+                wav.write_sample(sample)?;
+            }
+            wav.finalize()?;
+            writer.write_all(buffer.get_ref())?;
+        }
+        AudioFormat::PcmS16Le => {
+            let bytes: Vec<u8> = samples
+                .iter()
+                .flat_map(|&s| to_i16(s).to_le_bytes())
+                .collect();
+            writer.write_all(&bytes)?;
+        }
+        AudioFormat::PcmF32Le => {
+            let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+            writer.write_all(&bytes)?;
+        }
+        #[cfg(feature = "opus")]
+        AudioFormat::OggOpus => write_ogg_opus(&mut writer, samples, sample_rate)?,
+        #[cfg(feature = "mp3")]
+        AudioFormat::Mp3 => write_mp3(&mut writer, samples, sample_rate)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "opus")]
+fn write_ogg_opus<W: Write>(writer: W, samples: &[f32], sample_rate: u32) -> Result<()> {
+    use audiopus::{coder::Encoder, Application, Channels, SampleRate};
+    use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+    const OPUS_RATE: u32 = 48000;
+    // 20ms frames
+    const FRAME_SIZE: usize = 960;
+    const SERIAL: u32 = 0x5348_5250;
+
+    let mut samples = resample(samples, sample_rate, OPUS_RATE);
+    let total = samples.len() as u64;
+    let encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip)
+        .map_err(|e| crate::Error::Encode(format!("failed to create opus encoder: {}", e)))?;
+    let pre_skip = encoder
+        .lookahead()
+        .map_err(|e| crate::Error::Encode(format!("failed to query opus lookahead: {}", e)))?
+        as u64;
+
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+
+    let vendor = concat!("sherpa-rs ", env!("CARGO_PKG_VERSION"));
+    let mut tags = Vec::new();
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes()); // user comments
+
+    let mut packets = PacketWriter::new(writer);
+    packets.write_packet(
+        head.into_boxed_slice(),
+        SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+    packets.write_packet(
+        tags.into_boxed_slice(),
+        SERIAL,
+        PacketWriteEndInfo::EndPage,
+        0,
+    )?;
+
+    // The encoder delays its output by pre_skip samples, padding the input as much
+    // flushes the end of the audio out of it
+    samples.resize(samples.len() + pre_skip as usize, 0.0);
+    let mut frame = [0f32; FRAME_SIZE];
+    let mut output = [0u8; 4000];
+    // Without audio a silent frame carries the end of stream, the granule position trims it
+    let chunks: Vec<&[f32]> = if samples.is_empty() {
+        vec![&[]]
+    } else {
+        samples.chunks(FRAME_SIZE).collect()
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        frame.fill(0.0);
+        frame[..chunk.len()].copy_from_slice(chunk);
+        let len = encoder
+            .encode_float(&frame[..], &mut output[..])
+            .map_err(|e| crate::Error::Encode(format!("failed to encode opus frame: {}", e)))?;
+        let last = i + 1 == chunks.len();
+        let granule = pre_skip + ((i + 1) * FRAME_SIZE) as u64;
+        let (info, granule) = if last {
+            (PacketWriteEndInfo::EndStream, pre_skip + total)
+        } else {
+            (PacketWriteEndInfo::NormalPacket, granule)
+        };
+        packets.write_packet(output[..len].into(), SERIAL, info, granule)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mp3")]
+fn write_mp3<W: Write>(mut writer: W, samples: &[f32], sample_rate: u32) -> Result<()> {
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, MonoPcm, Quality};
+
+    let mut builder =
+        Builder::new().ok_or_else(|| crate::Error::Encode("failed to create lame".into()))?;
+    builder
+        .set_num_channels(1)
+        .map_err(|e| crate::Error::Encode(format!("failed to set mp3 channels: {:?}", e)))?;
+    builder
+        .set_sample_rate(sample_rate)
+        .map_err(|e| crate::Error::Encode(format!("failed to set mp3 sample rate: {:?}", e)))?;
+    builder
+        .set_brate(Bitrate::Kbps64)
+        .map_err(|e| crate::Error::Encode(format!("failed to set mp3 bitrate: {:?}", e)))?;
+    builder
+        .set_quality(Quality::Good)
+        .map_err(|e| crate::Error::Encode(format!("failed to set mp3 quality: {:?}", e)))?;
+    let mut encoder = builder
+        .build()
+        .map_err(|e| crate::Error::Encode(format!("failed to create mp3 encoder: {:?}", e)))?;
+
+    let pcm: Vec<i16> = samples.iter().map(|&s| to_i16(s)).collect();
+    let mut output = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(pcm.len()));
+    encoder
+        .encode_to_vec(MonoPcm(&pcm), &mut output)
+        .map_err(|e| crate::Error::Encode(format!("failed to encode mp3: {:?}", e)))?;
+    encoder
+        .flush_to_vec::<FlushNoGap>(&mut output)
+        .map_err(|e| crate::Error::Encode(format!("failed to flush mp3: {:?}", e)))?;
+    writer.write_all(&output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn zero_crossings(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count()
+    }
+
+    #[test]
+    fn resample_same_rate_is_identity() {
+        let samples = sine(440.0, 16000, 100);
+        assert_eq!(resample(&samples, 16000, 16000), samples);
+        assert!(resample(&[], 16000, 48000).is_empty());
+    }
+
+    #[test]
+    fn resample_length_follows_ratio() {
+        let samples = vec![0.0; 16000];
+        assert_eq!(resample(&samples, 16000, 8000).len(), 8000);
+        assert_eq!(resample(&samples, 16000, 48000).len(), 48000);
+        assert_eq!(resample(&samples, 16000, 22050).len(), 22050);
+    }
+
+    #[test]
+    fn resample_keeps_tones_below_nyquist() {
+        let resampled = resample(&sine(440.0, 16000, 16000), 16000, 48000);
+        let expected = sine(440.0, 48000, 48000);
+        // Away from the edges, where the kernel runs out of input
+        for i in 1000..47000 {
+            assert!(
+                (resampled[i] - expected[i]).abs() < 0.01,
+                "sample {}: {} != {}",
+                i,
+                resampled[i],
+                expected[i]
+            );
+        }
+    }
+
+    #[cfg(feature = "audio-io")]
+    #[test]
+    fn stream_resampler_matches_whole_resampling() {
+        let samples = sine(440.0, 44100, 44100);
+        for to_rate in [16000, 48000] {
+            let expected = resample(&samples, 44100, to_rate);
+            let mut resampler = StreamResampler::new(44100, to_rate);
+            let streamed: Vec<f32> = samples
+                .chunks(1000)
+                .flat_map(|chunk| resampler.process(chunk))
+                .collect();
+            // Only the last few samples wait for input past the end
+            assert!(expected.len() - streamed.len() < 50, "{}", streamed.len());
+            for (i, (streamed, expected)) in streamed.iter().zip(&expected).enumerate() {
+                assert!(
+                    (streamed - expected).abs() < 1e-5,
+                    "sample {}: {} != {}",
+                    i,
+                    streamed,
+                    expected
+                );
+            }
+            assert!(resampler.history.len() < 100);
+        }
+    }
+
+    #[cfg(feature = "audio-io")]
+    #[test]
+    fn stream_resampler_same_rate_passes_through() {
+        let mut resampler = StreamResampler::new(16000, 16000);
+        assert_eq!(resampler.process(&[0.1, 0.2]), [0.1, 0.2]);
+    }
+
+    #[test]
+    fn resample_filters_tones_above_target_nyquist() {
+        let resampled = resample(&sine(7000.0, 16000, 16000), 16000, 8000);
+        assert!(rms(&resampled[500..7500]) < 0.05);
+    }
+
+    #[test]
+    fn time_stretch_returns_requested_length() {
+        let samples = sine(200.0, 16000, 16000);
+        for len in [0, 1, 100, 8000, 16000, 24000, 40000] {
+            assert_eq!(time_stretch(&samples, 16000, len).len(), len);
+        }
+        // Shorter than a window
+        assert_eq!(time_stretch(&samples[..100], 16000, 150).len(), 150);
+        assert!(time_stretch(&[], 16000, 0).is_empty());
+    }
+
+    #[test]
+    fn time_stretch_keeps_pitch() {
+        let samples = sine(200.0, 16000, 16000);
+        for len in [12000, 24000] {
+            let stretched = time_stretch(&samples, 16000, len);
+            // 400 crossings per second of a 200Hz tone
+            let per_second = zero_crossings(&stretched) as f32 * 16000.0 / len as f32;
+            assert!(
+                (per_second - 400.0).abs() < 20.0,
+                "{} crossings/s",
+                per_second
+            );
+        }
+    }
+
+    #[test]
+    fn wav_pcm16_has_header_and_samples() {
+        let mut bytes = Vec::new();
+        write_audio(
+            &mut bytes,
+            &[0.0, 0.5, -0.5, 2.0],
+            22050,
+            AudioFormat::WavPcm16,
+        )
+        .unwrap();
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(bytes.len(), 44 + 4 * 2);
+
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 1);
+        assert_eq!(spec.sample_rate, 22050);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.sample_format, SampleFormat::Int);
+        let samples: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, [0, 16383, -16383, i16::MAX]);
+    }
+
+    #[test]
+    fn wav_float32_round_trips() {
+        let samples = [0.0, 0.25, -1.0, 0.125];
+        let mut bytes = Vec::new();
+        write_audio(&mut bytes, &samples, 16000, AudioFormat::WavFloat32).unwrap();
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().sample_format, SampleFormat::Float);
+        assert_eq!(reader.spec().bits_per_sample, 32);
+        let read: Vec<f32> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(read, samples);
+    }
+
+    #[test]
+    fn raw_pcm_is_headerless_little_endian() {
+        let mut bytes = Vec::new();
+        write_audio(&mut bytes, &[1.0, -2.0], 16000, AudioFormat::PcmS16Le).unwrap();
+        assert_eq!(bytes, [0xff, 0x7f, 0x01, 0x80]);
+
+        let mut bytes = Vec::new();
+        write_audio(&mut bytes, &[0.5], 16000, AudioFormat::PcmF32Le).unwrap();
+        assert_eq!(bytes, 0.5f32.to_le_bytes());
+    }
+
+    #[cfg(feature = "opus")]
+    fn ogg_packets(bytes: Vec<u8>) -> Vec<ogg::Packet> {
+        let mut reader = ogg::reading::PacketReader::new(Cursor::new(bytes));
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.read_packet().unwrap() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn ogg_opus_has_headers_and_end_of_stream() {
+        let mut bytes = Vec::new();
+        let samples = sine(440.0, 16000, 16000);
+        write_audio(&mut bytes, &samples, 16000, AudioFormat::OggOpus).unwrap();
+        let packets = ogg_packets(bytes);
+
+        let head = &packets[0].data;
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(head[9], 1);
+        assert_eq!(u32::from_le_bytes(head[12..16].try_into().unwrap()), 16000);
+        assert_eq!(&packets[1].data[..8], b"OpusTags");
+        // One second at 48kHz and the encoder lookahead, in 20ms frames
+        assert_eq!(packets.len(), 2 + 51);
+        let last = packets.last().unwrap();
+        assert!(last.last_in_stream());
+        let pre_skip = u16::from_le_bytes(head[10..12].try_into().unwrap()) as u64;
+        assert_eq!(last.absgp_page(), pre_skip + 48000);
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn ogg_opus_keeps_the_end_of_whole_frames() {
+        use audiopus::{coder::Decoder, Channels, SampleRate};
+
+        // A whole number of 20ms frames, nothing pads the last one
+        let samples = sine(440.0, 48000, 960 * 10);
+        let mut bytes = Vec::new();
+        write_audio(&mut bytes, &samples, 48000, AudioFormat::OggOpus).unwrap();
+        let packets = ogg_packets(bytes);
+        let head = &packets[0].data;
+        let pre_skip = u16::from_le_bytes(head[10..12].try_into().unwrap()) as usize;
+        let end = packets.last().unwrap().absgp_page() as usize;
+        assert_eq!(end, pre_skip + samples.len());
+
+        let mut decoder = Decoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+        let mut decoded = Vec::new();
+        let mut frame = vec![0f32; 5760];
+        for packet in &packets[2..] {
+            let packet = packet.data.as_slice().try_into().unwrap();
+            let len = decoder
+                .decode_float(Some(packet), (&mut frame).try_into().unwrap(), false)
+                .unwrap();
+            decoded.extend_from_slice(&frame[..len]);
+        }
+        assert!(decoded.len() >= end, "{} < {}", decoded.len(), end);
+        let decoded = &decoded[pre_skip..end];
+        assert_eq!(decoded.len(), samples.len());
+        // The last frame carries the tone, not the encoder's silence
+        assert!(rms(&decoded[decoded.len() - 480..]) > 0.3);
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn ogg_opus_without_samples_ends_the_stream() {
+        let mut bytes = Vec::new();
+        write_audio(&mut bytes, &[], 16000, AudioFormat::OggOpus).unwrap();
+        let packets = ogg_packets(bytes);
+        assert_eq!(packets.len(), 3);
+        assert!(packets[2].last_in_stream());
+    }
+}
@@ -0,0 +1,562 @@
+//! Microphone capture and speaker playback with the default audio host (cpal).
+//!
+//! `Microphone` reads mono chunks at the rate the models expect, resampling when
+//! the device can't capture at it. `Speaker` plays mono audio such as a
+//! `TtsSample`. Code written against `AudioInput` and `AudioOutput` can be tested
+//! with `loopback`, which needs no device.
+//!
+//! ```
+//! use sherpa_rs::audio_io::{loopback, AudioInput, AudioOutput};
+//!
+//! let (mut output, mut input) = loopback(16000, 1600)?;
+//! output.play(&[0.5; 48000], 48000)?;
+//! drop(output);
+//! let mut samples = 0;
+//! while let Some(chunk) = input.read()? {
+//!     samples += chunk.len();
+//! }
+//! assert_eq!(samples, 16000);
+//! # Ok::<(), sherpa_rs::Error>(())
+//! ```
+
+use crate::{
+    audio::{resample, StreamResampler},
+    bail, Error, Result,
+};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+/// Source of mono audio
+pub trait AudioInput {
+    fn sample_rate(&self) -> i32;
+    /// Next chunk of samples, blocking until it's available. `None` once the stream ended
+    fn read(&mut self) -> Result<Option<Vec<f32>>>;
+}
+
+/// Sink of mono audio
+pub trait AudioOutput {
+    /// Queue `samples` for playback, they are resampled to the output rate
+    fn play(&mut self, samples: &[f32], sample_rate: i32) -> Result<()>;
+    /// Block until everything queued has played
+    fn wait(&mut self) -> Result<()>;
+}
+
+/// Names of the input devices of the default host
+pub fn input_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    Ok(host
+        .input_devices()
+        .map_err(device_error)?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// Names of the output devices of the default host
+pub fn output_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    Ok(host
+        .output_devices()
+        .map_err(device_error)?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+fn find_device<I: Iterator<Item = cpal::Device>>(
+    mut devices: I,
+    name: &str,
+) -> Result<cpal::Device> {
+    devices
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        .ok_or_else(|| Error::Device(format!("audio device {} not found", name)))
+}
+
+fn device_error(error: impl std::fmt::Display) -> Error {
+    Error::Device(error.to_string())
+}
+
+#[derive(Debug, Clone)]
+pub struct MicrophoneBuilder {
+    device: Option<String>,
+    sample_rate: i32,
+    chunk_seconds: f32,
+}
+
+impl MicrophoneBuilder {
+    pub fn new(sample_rate: i32) -> Self {
+        Self {
+            device: None,
+            sample_rate,
+            chunk_seconds: 0.1,
+        }
+    }
+
+    /// Name of the input device, as listed by `input_devices`. Defaults to the host's default
+    pub fn device(mut self, name: &str) -> Self {
+        self.device = Some(name.into());
+        self
+    }
+
+    /// Duration of the chunks returned by `read`
+    pub fn chunk_seconds(mut self, seconds: f32) -> Self {
+        self.chunk_seconds = seconds;
+        self
+    }
+
+    pub fn build(self) -> Result<Microphone> {
+        if self.sample_rate <= 0 {
+            bail!(
+                InvalidConfig,
+                "sample rate must be positive, got {}",
+                self.sample_rate
+            )
+        }
+        if self.chunk_seconds <= 0.0 {
+            bail!(
+                InvalidConfig,
+                "chunk duration must be positive, got {}",
+                self.chunk_seconds
+            )
+        }
+        let host = cpal::default_host();
+        let device = match &self.device {
+            Some(name) => find_device(host.input_devices().map_err(device_error)?, name)?,
+            None => host
+                .default_input_device()
+                .ok_or_else(|| Error::Device("no input device available".into()))?,
+        };
+
+        // Capture at the requested rate when the device supports it, to skip resampling
+        let sample_rate = self.sample_rate as u32;
+        let config = device
+            .supported_input_configs()
+            .map_err(device_error)?
+            .filter(|range| {
+                range.min_sample_rate().0 <= sample_rate && sample_rate <= range.max_sample_rate().0
+            })
+            .min_by_key(|range| range.channels())
+            .map(|range| range.with_sample_rate(cpal::SampleRate(sample_rate)));
+        let config = match config {
+            Some(config) => config,
+            None => device.default_input_config().map_err(device_error)?,
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => input_stream::<f32>(&device, &config.config(), sender)?,
+            cpal::SampleFormat::I16 => input_stream::<i16>(&device, &config.config(), sender)?,
+            cpal::SampleFormat::I32 => input_stream::<i32>(&device, &config.config(), sender)?,
+            cpal::SampleFormat::U16 => input_stream::<u16>(&device, &config.config(), sender)?,
+            cpal::SampleFormat::U8 => input_stream::<u8>(&device, &config.config(), sender)?,
+            format => bail!(Device, "unsupported input sample format {}", format),
+        };
+        stream.play().map_err(device_error)?;
+
+        Ok(Microphone {
+            stream,
+            receiver,
+            resampler: StreamResampler::new(config.sample_rate().0, sample_rate),
+            sample_rate,
+            chunk_len: ((self.chunk_seconds * sample_rate as f32) as usize).max(1),
+            pending: Vec::new(),
+        })
+    }
+}
+
+/// Audio captured by the callback of the input stream, or the error it reported
+type Captured = std::result::Result<Vec<f32>, cpal::StreamError>;
+
+fn input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sender: Sender<Captured>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let errors = sender.clone();
+    let stream = device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                // Mix down to mono
+                let samples = data
+                    .chunks(channels)
+                    .map(|frame| {
+                        frame
+                            .iter()
+                            .map(|&sample| cpal::Sample::to_sample::<f32>(sample))
+                            .sum::<f32>()
+                            / channels as f32
+                    })
+                    .collect();
+                let _ = sender.send(Ok(samples));
+            },
+            move |err| {
+                let _ = errors.send(Err(err));
+            },
+            None,
+        )
+        .map_err(device_error)?;
+    Ok(stream)
+}
+
+/// Mono capture from an input device, chunk by chunk.
+///
+/// The stream is not `Send` on every platform, keep it on the thread that opened it.
+pub struct Microphone {
+    stream: cpal::Stream,
+    receiver: Receiver<Captured>,
+    /// From the device rate, across chunks
+    resampler: StreamResampler,
+    sample_rate: u32,
+    /// Samples per chunk at `sample_rate`
+    chunk_len: usize,
+    /// Resampled audio not yet returned by `read`
+    pending: Vec<f32>,
+}
+
+impl Microphone {
+    /// Default input device, in chunks of 0.1 seconds at `sample_rate`
+    pub fn new(sample_rate: i32) -> Result<Self> {
+        MicrophoneBuilder::new(sample_rate).build()
+    }
+
+    pub fn builder(sample_rate: i32) -> MicrophoneBuilder {
+        MicrophoneBuilder::new(sample_rate)
+    }
+
+    /// Stop capturing, e.g. while the app is speaking
+    pub fn pause(&self) -> Result<()> {
+        self.stream.pause().map_err(device_error)?;
+        Ok(())
+    }
+
+    /// Capture again, audio from before the pause is dropped
+    pub fn resume(&mut self) -> Result<()> {
+        self.pending.clear();
+        self.resampler.reset();
+        while self.receiver.try_recv().is_ok() {}
+        self.stream.play().map_err(device_error)?;
+        Ok(())
+    }
+}
+
+impl AudioInput for Microphone {
+    fn sample_rate(&self) -> i32 {
+        self.sample_rate as i32
+    }
+
+    fn read(&mut self) -> Result<Option<Vec<f32>>> {
+        while self.pending.len() < self.chunk_len {
+            match self.receiver.recv() {
+                Ok(Ok(samples)) => self.pending.extend(self.resampler.process(&samples)),
+                Ok(Err(err)) => bail!(Device, "microphone stream failed: {}", err),
+                Err(_) => return Ok(None),
+            }
+        }
+        Ok(Some(self.pending.drain(..self.chunk_len).collect()))
+    }
+}
+
+impl Iterator for Microphone {
+    type Item = Result<Vec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Mono playback on an output device
+pub struct Speaker {
+    stream: cpal::Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    errors: Receiver<cpal::StreamError>,
+    device_rate: u32,
+}
+
+impl Speaker {
+    /// Default output device
+    pub fn new() -> Result<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| Error::Device("no output device available".into()))?;
+        Self::from_device(device)
+    }
+
+    /// Output device by name, as listed by `output_devices`
+    pub fn with_device(name: &str) -> Result<Self> {
+        let device = find_device(
+            cpal::default_host()
+                .output_devices()
+                .map_err(device_error)?,
+            name,
+        )?;
+        Self::from_device(device)
+    }
+
+    fn from_device(device: cpal::Device) -> Result<Self> {
+        let config = device.default_output_config().map_err(device_error)?;
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let (sender, errors) = mpsc::channel();
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => {
+                output_stream::<f32>(&device, &config.config(), queue.clone(), sender)?
+            }
+            cpal::SampleFormat::I16 => {
+                output_stream::<i16>(&device, &config.config(), queue.clone(), sender)?
+            }
+            cpal::SampleFormat::I32 => {
+                output_stream::<i32>(&device, &config.config(), queue.clone(), sender)?
+            }
+            cpal::SampleFormat::U16 => {
+                output_stream::<u16>(&device, &config.config(), queue.clone(), sender)?
+            }
+            cpal::SampleFormat::U8 => {
+                output_stream::<u8>(&device, &config.config(), queue.clone(), sender)?
+            }
+            format => bail!(Device, "unsupported output sample format {}", format),
+        };
+        stream.play().map_err(device_error)?;
+        Ok(Self {
+            stream,
+            queue,
+            errors,
+            device_rate: config.sample_rate().0,
+        })
+    }
+
+    /// Drop the queued audio
+    pub fn stop(&self) {
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    fn check(&self) -> Result<()> {
+        match self.errors.try_recv() {
+            Ok(err) => bail!(Device, "speaker stream failed: {}", err),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+fn output_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    errors: Sender<cpal::StreamError>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let stream = device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let mut queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+                for frame in data.chunks_mut(channels) {
+                    // Silence once the queue runs out
+                    let sample = T::from_sample(queue.pop_front().unwrap_or(0.0));
+                    frame.fill(sample);
+                }
+            },
+            move |err| {
+                let _ = errors.send(err);
+            },
+            None,
+        )
+        .map_err(device_error)?;
+    Ok(stream)
+}
+
+impl AudioOutput for Speaker {
+    fn play(&mut self, samples: &[f32], sample_rate: i32) -> Result<()> {
+        self.check()?;
+        if sample_rate <= 0 {
+            bail!(
+                InvalidAudio,
+                "sample rate must be positive, got {}",
+                sample_rate
+            )
+        }
+        let samples = resample(samples, sample_rate as u32, self.device_rate);
+        self.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(samples);
+        self.stream.play().map_err(device_error)?;
+        Ok(())
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        while !self
+            .queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+        {
+            self.check()?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.check()
+    }
+}
+
+/// Connected output and input without a device, the input reads what's played on
+/// the output in chunks of `chunk_size` samples at `sample_rate`.
+///
+/// The input ends once the output is dropped. After the input is dropped the
+/// output discards what it's given, like a null device.
+pub fn loopback(sample_rate: i32, chunk_size: usize) -> Result<(LoopbackOutput, LoopbackInput)> {
+    if sample_rate <= 0 {
+        bail!(
+            InvalidConfig,
+            "sample rate must be positive, got {}",
+            sample_rate
+        )
+    }
+    let (sender, receiver) = mpsc::channel();
+    Ok((
+        LoopbackOutput {
+            sender,
+            sample_rate,
+        },
+        LoopbackInput {
+            receiver,
+            sample_rate,
+            chunk_size: chunk_size.max(1),
+            pending: VecDeque::new(),
+        },
+    ))
+}
+
+#[derive(Debug)]
+pub struct LoopbackOutput {
+    sender: Sender<Vec<f32>>,
+    sample_rate: i32,
+}
+
+impl AudioOutput for LoopbackOutput {
+    fn play(&mut self, samples: &[f32], sample_rate: i32) -> Result<()> {
+        if sample_rate <= 0 {
+            bail!(
+                InvalidAudio,
+                "sample rate must be positive, got {}",
+                sample_rate
+            )
+        }
+        let samples = resample(samples, sample_rate as u32, self.sample_rate as u32);
+        let _ = self.sender.send(samples);
+        Ok(())
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct LoopbackInput {
+    receiver: Receiver<Vec<f32>>,
+    sample_rate: i32,
+    chunk_size: usize,
+    pending: VecDeque<f32>,
+}
+
+impl AudioInput for LoopbackInput {
+    fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// The last chunk is shorter when the audio doesn't fill it
+    fn read(&mut self) -> Result<Option<Vec<f32>>> {
+        while self.pending.len() < self.chunk_size {
+            match self.receiver.recv() {
+                Ok(samples) => self.pending.extend(samples),
+                Err(_) if self.pending.is_empty() => return Ok(None),
+                Err(_) => break,
+            }
+        }
+        let len = self.chunk_size.min(self.pending.len());
+        Ok(Some(self.pending.drain(..len).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &mut LoopbackInput) -> Vec<Vec<f32>> {
+        std::iter::from_fn(|| input.read().unwrap()).collect()
+    }
+
+    #[test]
+    fn reads_in_chunks_with_a_short_last_one() {
+        let (mut output, mut input) = loopback(16000, 4).unwrap();
+        output.play(&[0.1; 6], 16000).unwrap();
+        output.play(&[0.2; 4], 16000).unwrap();
+        drop(output);
+        let chunks = read_all(&mut input);
+        let lengths: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(lengths, [4, 4, 2]);
+        assert_eq!(chunks[1], [0.1, 0.1, 0.2, 0.2]);
+    }
+
+    #[test]
+    fn play_resamples_to_the_input_rate() {
+        let (mut output, mut input) = loopback(16000, 1000).unwrap();
+        output.play(&[0.0; 4800], 48000).unwrap();
+        output.play(&[0.0; 800], 8000).unwrap();
+        drop(output);
+        let samples: usize = read_all(&mut input).iter().map(Vec::len).sum();
+        assert_eq!(samples, 1600 + 1600);
+        assert_eq!(input.sample_rate(), 16000);
+    }
+
+    #[test]
+    fn ends_once_the_output_is_dropped() {
+        let (output, mut input) = loopback(16000, 4).unwrap();
+        drop(output);
+        assert_eq!(input.read().unwrap(), None);
+        assert_eq!(input.read().unwrap(), None);
+    }
+
+    #[test]
+    fn output_without_input_discards() {
+        let (mut output, input) = loopback(16000, 4).unwrap();
+        drop(input);
+        output.play(&[0.1; 4], 16000).unwrap();
+        output.wait().unwrap();
+    }
+
+    #[test]
+    fn rejects_non_positive_rates() {
+        for rate in [0, -16000] {
+            assert!(matches!(loopback(rate, 4), Err(Error::InvalidConfig(_))));
+        }
+        let (mut output, _input) = loopback(16000, 4).unwrap();
+        assert!(matches!(
+            output.play(&[0.1], 0),
+            Err(Error::InvalidAudio(_))
+        ));
+    }
+
+    #[test]
+    fn zero_chunk_size_reads_single_samples() {
+        let (mut output, mut input) = loopback(16000, 0).unwrap();
+        output.play(&[0.1, 0.2], 16000).unwrap();
+        drop(output);
+        assert_eq!(read_all(&mut input), [vec![0.1], vec![0.2]]);
+    }
+}
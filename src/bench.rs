@@ -0,0 +1,361 @@
+//! Real-time factor, latency and memory measurements for choosing thread counts
+//! and providers.
+//!
+//! `measure` times any closure. The `bench_*` functions build an engine for each
+//! thread count of a sweep and run it on `synthetic_audio`, so only the model
+//! files are needed. Results print as a table row with `Display`.
+//!
+//! ```no_run
+//! # #[cfg(feature = "asr")]
+//! # {
+//! use sherpa_rs::{bench, transcribe::whisper::WhisperRecognizer};
+//!
+//! let builder = WhisperRecognizer::builder("tiny-encoder.onnx", "tiny-decoder.onnx", "tiny-tokens.txt");
+//! println!("{}", bench::BenchResult::HEADER);
+//! for result in bench::bench_whisper(&builder, &[1, 2, 4], 10.0, &Default::default())? {
+//!     println!("{}", result);
+//! }
+//! # }
+//! # Ok::<(), sherpa_rs::Error>(())
+//! ```
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::Result;
+
+/// Rate of the synthetic audio, what the speech models expect
+#[cfg(any(feature = "asr", feature = "vad", feature = "speaker"))]
+const SAMPLE_RATE: i32 = 16000;
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Untimed runs before measuring, the first run loads kernels and caches
+    pub warmup: usize,
+    pub iterations: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            warmup: 1,
+            iterations: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: String,
+    pub num_threads: i32,
+    /// Seconds of audio processed, or generated for text to speech, per iteration
+    pub audio_seconds: f32,
+    /// Duration of each iteration, sorted
+    pub latencies: Vec<Duration>,
+    /// Peak resident memory of the process in bytes, where the OS reports it
+    pub peak_memory: Option<u64>,
+}
+
+impl BenchResult {
+    /// Column names matching `Display`
+    pub const HEADER: &'static str =
+        "name                 threads    rtf   mean ms    p50 ms    p90 ms    p99 ms  peak MiB";
+
+    pub fn mean(&self) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+    }
+
+    /// Latency at `percentile` (0 to 100), nearest rank
+    pub fn percentile(&self, percentile: f32) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (percentile / 100.0 * self.latencies.len() as f32).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+
+    /// Real-time factor: processing time over audio duration, below 1 is faster than real time
+    pub fn rtf(&self) -> f32 {
+        if self.audio_seconds <= 0.0 {
+            return 0.0;
+        }
+        self.mean().as_secs_f32() / self.audio_seconds
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{:<20} {:>8} {:>6.3} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9}",
+            self.name,
+            self.num_threads,
+            self.rtf(),
+            ms(self.mean()),
+            ms(self.percentile(50.0)),
+            ms(self.percentile(90.0)),
+            ms(self.percentile(99.0)),
+            self.peak_memory.map_or("-".into(), |bytes| format!(
+                "{:.1}",
+                bytes as f64 / 1048576.0
+            ))
+        )
+    }
+}
+
+/// Time `run` after `options.warmup` untimed calls. Stops at the first error,
+/// a failing run would otherwise be timed as a fast one.
+pub fn measure<F: FnMut() -> Result<()>>(
+    name: &str,
+    num_threads: i32,
+    audio_seconds: f32,
+    options: &BenchOptions,
+    mut run: F,
+) -> Result<BenchResult> {
+    reset_peak_memory();
+    for _ in 0..options.warmup {
+        run()?;
+    }
+    let mut latencies: Vec<Duration> = (0..options.iterations.max(1))
+        .map(|_| {
+            let start = Instant::now();
+            run()?;
+            Ok(start.elapsed())
+        })
+        .collect::<Result<_>>()?;
+    latencies.sort();
+    Ok(BenchResult {
+        name: name.to_string(),
+        num_threads,
+        audio_seconds,
+        latencies,
+        peak_memory: peak_memory(),
+    })
+}
+
+/// Deterministic speech-like audio: a voiced tone with a drifting pitch and a few
+/// harmonics, modulated at syllable rate, over a little noise
+pub fn synthetic_audio(seconds: f32, sample_rate: i32) -> Vec<f32> {
+    let len = (seconds * sample_rate as f32) as usize;
+    let mut seed: u32 = 0x1234_5678;
+    let mut phase = 0.0f32;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let pitch = 140.0 + 30.0 * (2.0 * std::f32::consts::PI * 0.7 * t).sin();
+            phase += 2.0 * std::f32::consts::PI * pitch / sample_rate as f32;
+            let voiced: f32 = (1..=4).map(|k| (k as f32 * phase).sin() / k as f32).sum();
+            let envelope = 0.5 + 0.5 * (2.0 * std::f32::consts::PI * 4.0 * t).sin();
+            // Linear congruential generator, so every run gets the same noise
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+            0.2 * envelope * voiced + 0.01 * noise
+        })
+        .collect()
+}
+
+/// `WhisperRecognizer::transcribe` on `audio_seconds` of audio for each thread count
+#[cfg(feature = "asr")]
+pub fn bench_whisper(
+    builder: &crate::transcribe::whisper::WhisperRecognizerBuilder,
+    threads: &[i32],
+    audio_seconds: f32,
+    options: &BenchOptions,
+) -> Result<Vec<BenchResult>> {
+    let samples = synthetic_audio(audio_seconds, SAMPLE_RATE);
+    threads
+        .iter()
+        .map(|&num_threads| {
+            let recognizer = builder.clone().num_threads(num_threads).build()?;
+            measure("whisper", num_threads, audio_seconds, options, || {
+                recognizer.transcribe(SAMPLE_RATE, samples.clone())?;
+                Ok(())
+            })
+        })
+        .collect()
+}
+
+/// `Vad::accept_waveform` over `audio_seconds` of audio, window by window
+#[cfg(feature = "vad")]
+pub fn bench_vad(
+    builder: &crate::vad::VadConfigBuilder,
+    threads: &[i32],
+    audio_seconds: f32,
+    options: &BenchOptions,
+) -> Result<Vec<BenchResult>> {
+    use crate::vad::Vad;
+
+    let samples = synthetic_audio(audio_seconds, SAMPLE_RATE);
+    threads
+        .iter()
+        .map(|&num_threads| {
+            let config = builder
+                .clone()
+                .sample_rate(SAMPLE_RATE)
+                .num_threads(num_threads)
+                .build()?;
+            let window_size = config.cfg.silero_vad.window_size as usize;
+            let mut vad = Vad::new_from_config(config, audio_seconds + 1.0)?;
+            measure("vad", num_threads, audio_seconds, options, || {
+                for window in samples.chunks_exact(window_size) {
+                    vad.accept_waveform(window.to_vec());
+                }
+                vad.clear();
+                Ok(())
+            })
+        })
+        .collect()
+}
+
+/// `EmbeddingExtractor::compute_speaker_embedding` on `audio_seconds` of audio
+#[cfg(feature = "speaker")]
+pub fn bench_extractor(
+    builder: &crate::speaker_id::ExtractorConfigBuilder,
+    threads: &[i32],
+    audio_seconds: f32,
+    options: &BenchOptions,
+) -> Result<Vec<BenchResult>> {
+    use crate::speaker_id::EmbeddingExtractor;
+
+    let samples = synthetic_audio(audio_seconds, SAMPLE_RATE);
+    threads
+        .iter()
+        .map(|&num_threads| {
+            let config = builder.clone().num_threads(num_threads).build()?;
+            let mut extractor = EmbeddingExtractor::new_from_config(config)?;
+            extractor.compute_speaker_embedding(SAMPLE_RATE, samples.clone())?;
+            measure(
+                "speaker_embedding",
+                num_threads,
+                audio_seconds,
+                options,
+                || {
+                    extractor.compute_speaker_embedding(SAMPLE_RATE, samples.clone())?;
+                    Ok(())
+                },
+            )
+        })
+        .collect()
+}
+
+/// `OfflineTts::generate` of `text`. The real-time factor is relative to the
+/// duration of the generated audio.
+#[cfg(feature = "tts")]
+pub fn bench_tts(
+    builder: &crate::tts::OfflineTtsConfigBuilder,
+    threads: &[i32],
+    text: &str,
+    options: &BenchOptions,
+) -> Result<Vec<BenchResult>> {
+    use crate::tts::OfflineTts;
+
+    threads
+        .iter()
+        .map(|&num_threads| {
+            let mut tts = OfflineTts::new(builder.clone().num_threads(num_threads).build()?)?;
+            let audio_seconds = tts.generate(text.to_string(), 0, 1.0)?.duration;
+            measure("tts", num_threads, audio_seconds, options, || {
+                tts.generate(text.to_string(), 0, 1.0)?;
+                Ok(())
+            })
+        })
+        .collect()
+}
+
+/// Peak resident set size of the process, from `/proc/self/status` on Linux
+pub fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Start peak memory tracking over from the current usage, Linux only
+fn reset_peak_memory() {
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(latencies_ms: &[u64], audio_seconds: f32) -> BenchResult {
+        BenchResult {
+            name: "test".into(),
+            num_threads: 1,
+            audio_seconds,
+            latencies: latencies_ms
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect(),
+            peak_memory: None,
+        }
+    }
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let result = result(&[10, 20, 30, 40, 50, 60, 70, 80, 90, 100], 1.0);
+        let ms = |percentile| result.percentile(percentile).as_millis();
+        assert_eq!(ms(50.0), 50);
+        assert_eq!(ms(90.0), 90);
+        assert_eq!(ms(99.0), 100);
+        assert_eq!(ms(100.0), 100);
+        assert_eq!(ms(0.0), 10);
+        assert_eq!(ms(1.0), 10);
+        assert_eq!(ms(11.0), 20);
+    }
+
+    #[test]
+    fn percentile_of_one_or_no_latency() {
+        assert_eq!(result(&[7], 1.0).percentile(99.0), Duration::from_millis(7));
+        assert_eq!(result(&[], 1.0).percentile(50.0), Duration::ZERO);
+        assert_eq!(result(&[], 1.0).mean(), Duration::ZERO);
+    }
+
+    #[test]
+    fn rtf_is_mean_time_over_audio_duration() {
+        let result = result(&[100, 300], 2.0);
+        assert_eq!(result.mean(), Duration::from_millis(200));
+        assert!((result.rtf() - 0.1).abs() < 1e-6);
+        assert_eq!(self::result(&[100], 0.0).rtf(), 0.0);
+        assert_eq!(self::result(&[], 5.0).rtf(), 0.0);
+    }
+
+    #[test]
+    fn measure_runs_warmup_and_iterations() {
+        let mut calls = 0;
+        let options = BenchOptions {
+            warmup: 2,
+            iterations: 3,
+        };
+        let result = measure("count", 4, 1.0, &options, || {
+            calls += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(calls, 5);
+        assert_eq!(result.latencies.len(), 3);
+        assert_eq!(result.num_threads, 4);
+        assert!(result.latencies.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn measure_stops_at_the_first_error() {
+        let mut calls = 0;
+        let result = measure("fail", 1, 1.0, &BenchOptions::default(), || {
+            calls += 1;
+            match calls {
+                3 => crate::bail!(Native, "generation failed"),
+                _ => Ok(()),
+            }
+        });
+        assert!(matches!(result, Err(crate::Error::Native(_))));
+        assert_eq!(calls, 3);
+    }
+}
@@ -0,0 +1,91 @@
+//! Model paths used when no flag is given.
+//!
+//! Read from `--config`, the `SHERPA_CONFIG` environment variable or `sherpa.toml`
+//! in the working directory. Relative paths are relative to the config file.
+//!
+//! ```toml
+//! [models]
+//! asr = "sherpa-onnx-whisper-tiny"
+//! vad = "silero_vad.onnx"
+//! speaker = "nemo_en_speakerverification_speakernet.onnx"
+//! lang_id = "sherpa-onnx-whisper-tiny"
+//! tts = "vits-piper-en_US-amy-low"
+//! kws = "sherpa-onnx-kws-zipformer-gigaspeech-3.3M-2024-01-01"
+//! speakers = "speakers.json"
+//! ```
+
+use eyre::{bail, eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PATH: &str = "sherpa.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub models: Models,
+}
+
+/// Model directories, model files or manifests, see `sherpa_rs::manifest`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Models {
+    pub asr: Option<PathBuf>,
+    pub vad: Option<PathBuf>,
+    pub speaker: Option<PathBuf>,
+    pub lang_id: Option<PathBuf>,
+    pub tts: Option<PathBuf>,
+    pub kws: Option<PathBuf>,
+    /// Database of enrolled speakers
+    pub speakers: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match std::env::var_os("SHERPA_CONFIG") {
+                Some(path) => PathBuf::from(path),
+                None if Path::new(DEFAULT_PATH).exists() => PathBuf::from(DEFAULT_PATH),
+                None => return Ok(Self::default()),
+            },
+        };
+        let content = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("failed to read config {}", path.display()))?;
+        let mut config: Config = toml::from_str(&content)
+            .wrap_err_with(|| format!("invalid config {}", path.display()))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let models = &mut config.models;
+        for model in [
+            &mut models.asr,
+            &mut models.vad,
+            &mut models.speaker,
+            &mut models.lang_id,
+            &mut models.tts,
+            &mut models.kws,
+            &mut models.speakers,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *model = dir.join(&*model);
+        }
+        Ok(config)
+    }
+}
+
+/// The path given by flag, or else the configured one
+pub fn model(flag: Option<PathBuf>, configured: &Option<PathBuf>, key: &str) -> Result<PathBuf> {
+    let path = flag.or_else(|| configured.clone()).ok_or_else(|| {
+        eyre!(
+            "no {} model, pass it as a flag or set models.{} in the config file",
+            key,
+            key
+        )
+    })?;
+    if !path.exists() {
+        bail!("{} model not found at {}", key, path.display())
+    }
+    Ok(path)
+}
@@ -0,0 +1,56 @@
+use eyre::{bail, Result, WrapErr};
+use hound::{SampleFormat, WavReader};
+use sherpa_rs::audio::resample;
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+/// Rate all models run at
+pub const SAMPLE_RATE: i32 = 16000;
+
+/// Read a WAV file, or stdin for `-`, as mono samples at `SAMPLE_RATE`
+pub fn read_audio(path: &Path) -> Result<Vec<f32>> {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        bytes
+    } else {
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?
+    };
+    let mut reader = WavReader::new(Cursor::new(bytes))
+        .wrap_err_with(|| format!("{} is not a WAV file", path.display()))?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    if spec.channels == 0 {
+        bail!("{} has no channels", path.display())
+    }
+    let channels = spec.channels as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok(resample(&mono, spec.sample_rate, SAMPLE_RATE as u32))
+}
+
+/// Text from the argument, or stdin if there is none or it's `-`
+pub fn read_text(text: Option<String>) -> Result<String> {
+    match text {
+        Some(text) if text != "-" => Ok(text),
+        _ => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            Ok(text)
+        }
+    }
+}
@@ -0,0 +1,638 @@
+//! `sherpa` command line, run any engine of the crate on WAV files or stdin.
+//!
+//! ```console
+//! sherpa transcribe --model sherpa-onnx-whisper-tiny --vad silero_vad.onnx --format srt talk.wav
+//! sherpa diarize --vad silero_vad.onnx --speaker-model nemo_en_speakerverification_speakernet.onnx --format rttm meeting.wav
+//! echo "Hello world" | sherpa tts --model vits-piper-en_US-amy-low -o hello.wav
+//! sherpa eval wer --model sherpa-onnx-whisper-tiny test.tsv
+//! ```
+
+mod config;
+mod input;
+mod models;
+mod pipeline;
+mod speakers;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::Config;
+use eyre::{bail, Result};
+use input::{read_audio, read_text, SAMPLE_RATE};
+use pipeline::Diarizer;
+use sherpa_rs::{
+    audio::AudioFormat,
+    eval::{self, Normalization, Turn},
+    formats::{self, Segment, SubtitleOptions},
+};
+use speakers::{cosine_similarity, SpeakerDb};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Speech recognition, synthesis and speaker tools")]
+struct Cli {
+    /// Config file with model paths, defaults to $SHERPA_CONFIG or ./sherpa.toml
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Output format
+    #[arg(long, short, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Srt,
+    Vtt,
+    Rttm,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Speech to text
+    Transcribe(TranscribeArgs),
+    /// Speech segments
+    Vad(VadArgs),
+    /// Who spoke when, and optionally what
+    Diarize(DiarizeArgs),
+    /// Enroll, identify and verify speakers
+    Speaker(SpeakerArgs),
+    /// Spoken language identification
+    LangId(LangIdArgs),
+    /// Text to speech
+    Tts(TtsArgs),
+    /// Score a recognizer or diarizer over a manifest of recordings and references
+    Eval {
+        #[command(subcommand)]
+        command: EvalCommand,
+    },
+    /// Keyword spotting
+    Kws(KwsArgs),
+}
+
+#[derive(Args, Debug)]
+struct TranscribeArgs {
+    /// WAV files, `-` for stdin
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Recognizer model directory or manifest
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// Split the audio with this VAD model first
+    #[arg(long)]
+    vad: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct VadArgs {
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Silero VAD model or manifest
+    #[arg(long)]
+    model: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct DiarizeArgs {
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Silero VAD model or manifest
+    #[arg(long)]
+    vad: Option<PathBuf>,
+    /// Speaker embedding model or manifest
+    #[arg(long)]
+    speaker_model: Option<PathBuf>,
+    /// Also transcribe each segment with this recognizer
+    #[arg(long)]
+    asr: Option<PathBuf>,
+    #[arg(long, default_value_t = 4)]
+    max_speakers: usize,
+    /// Similarity above which a segment belongs to a known speaker
+    #[arg(long, default_value_t = 0.5)]
+    threshold: f32,
+}
+
+#[derive(Args, Debug)]
+struct SpeakerArgs {
+    #[command(subcommand)]
+    command: SpeakerCommand,
+    /// Speaker embedding model or manifest
+    #[arg(long, global = true)]
+    speaker_model: Option<PathBuf>,
+    /// JSON database of enrolled speakers
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct LangIdArgs {
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// Multilingual whisper model directory or manifest
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// Number of most likely languages to print
+    #[arg(long, default_value_t = 1)]
+    top: usize,
+}
+
+#[derive(Args, Debug)]
+struct TtsArgs {
+    /// Text to speak, stdin if missing or `-`
+    text: Option<String>,
+    /// TTS model directory or manifest
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// Output WAV file, `-` for stdout
+    #[arg(long, short)]
+    output: PathBuf,
+    #[arg(long, default_value_t = 0)]
+    sid: i32,
+    /// Speaker name from the model's speaker table, overrides --sid
+    #[arg(long)]
+    speaker: Option<String>,
+    #[arg(long, default_value_t = 1.0)]
+    speed: f32,
+}
+
+#[derive(Args, Debug)]
+struct KwsArgs {
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+    /// sherpa-onnx kws model directory
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// Keywords as model tokens separated by `/`, instead of the model's keywords.txt
+    #[arg(long)]
+    keywords: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum EvalCommand {
+    /// Word (or character) error rate, the manifest has `audio<TAB>transcript` lines
+    Wer(WerArgs),
+    /// Diarization error rate, the manifest has `audio<TAB>reference.rttm` lines
+    Der(DerArgs),
+}
+
+#[derive(Args, Debug)]
+struct WerArgs {
+    manifest: PathBuf,
+    /// Recognizer model directory or manifest
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// Split the audio with this VAD model first
+    #[arg(long)]
+    vad: Option<PathBuf>,
+    /// Character error rate instead
+    #[arg(long)]
+    cer: bool,
+    #[arg(long)]
+    keep_case: bool,
+    #[arg(long)]
+    keep_punctuation: bool,
+    /// Spell out numbers in English before comparing
+    #[arg(long)]
+    numbers_to_words: bool,
+}
+
+#[derive(Args, Debug)]
+struct DerArgs {
+    manifest: PathBuf,
+    /// Silero VAD model or manifest
+    #[arg(long)]
+    vad: Option<PathBuf>,
+    /// Speaker embedding model or manifest
+    #[arg(long)]
+    speaker_model: Option<PathBuf>,
+    #[arg(long, default_value_t = 4)]
+    max_speakers: usize,
+    #[arg(long, default_value_t = 0.5)]
+    threshold: f32,
+    /// Seconds around reference boundaries that aren't scored
+    #[arg(long, default_value_t = 0.25)]
+    collar: f32,
+}
+
+#[derive(Subcommand, Debug)]
+enum SpeakerCommand {
+    /// Add a speaker from one or more recordings
+    Enroll {
+        name: String,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Find the enrolled speaker of each recording
+    Identify {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[arg(long, default_value_t = 0.5)]
+        threshold: f32,
+    },
+    /// Check that a recording is of an enrolled speaker, fails if it isn't
+    Verify {
+        name: String,
+        file: PathBuf,
+        #[arg(long, default_value_t = 0.5)]
+        threshold: f32,
+    },
+}
+
+fn main() -> Result<ExitCode> {
+    env_logger::init();
+    sherpa_rs::logging::capture_native_stderr(true);
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let format = cli.format;
+
+    match cli.command {
+        Command::Transcribe(args) => transcribe(&config, format, args)?,
+        Command::Vad(args) => vad(&config, format, args)?,
+        Command::Diarize(args) => diarize(&config, format, args)?,
+        Command::Eval { command } => match command {
+            EvalCommand::Wer(args) => eval_wer(&config, format, args)?,
+            EvalCommand::Der(args) => eval_der(&config, format, args)?,
+        },
+        Command::Speaker(args) => return speaker(&config, format, args),
+        Command::LangId(args) => lang_id(&config, format, args)?,
+        Command::Tts(args) => tts(&config, args)?,
+        Command::Kws(args) => kws(&config, format, args)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn transcribe(config: &Config, format: Format, args: TranscribeArgs) -> Result<()> {
+    let configured = &config.models;
+    let recognizer = models::recognizer(&config::model(args.model, &configured.asr, "asr")?)?;
+    let vad = args.vad.or_else(|| configured.vad.clone());
+    for file in args.files {
+        let segments =
+            pipeline::transcribe(recognizer.as_ref(), vad.as_deref(), read_audio(&file)?)?;
+        print_segments(&segments, format, &file)?;
+    }
+    Ok(())
+}
+
+fn vad(config: &Config, format: Format, args: VadArgs) -> Result<()> {
+    let vad = config::model(args.model, &config.models.vad, "vad")?;
+    for file in args.files {
+        let segments = pipeline::speech(&vad, &read_audio(&file)?)?;
+        print_segments(&segments, format, &file)?;
+    }
+    Ok(())
+}
+
+fn diarize(config: &Config, format: Format, args: DiarizeArgs) -> Result<()> {
+    let configured = &config.models;
+    let mut diarizer = Diarizer {
+        vad: config::model(args.vad, &configured.vad, "vad")?,
+        extractor: models::extractor(&config::model(
+            args.speaker_model,
+            &configured.speaker,
+            "speaker",
+        )?)?,
+        recognizer: args.asr.map(|asr| models::recognizer(&asr)).transpose()?,
+        max_speakers: args.max_speakers,
+        threshold: args.threshold,
+    };
+    for file in args.files {
+        let segments = diarizer.diarize(&read_audio(&file)?)?;
+        print_segments(&segments, format, &file)?;
+    }
+    Ok(())
+}
+
+fn eval_wer(config: &Config, format: Format, args: WerArgs) -> Result<()> {
+    let recognizer = models::recognizer(&config::model(args.model, &config.models.asr, "asr")?)?;
+    let normalization = Normalization {
+        lowercase: !args.keep_case,
+        remove_punctuation: !args.keep_punctuation,
+        numbers_to_words: args.numbers_to_words,
+    };
+    let items = eval::read_transcript_manifest(&args.manifest)?;
+    let (scores, total) =
+        eval::score_transcripts(&items, &normalization, args.cer, |audio| -> Result<_> {
+            let segments =
+                pipeline::transcribe(recognizer.as_ref(), args.vad.as_deref(), read_audio(audio)?)?;
+            Ok(segments
+                .iter()
+                .map(|segment| segment.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "))
+        })?;
+    let metric = if args.cer { "cer" } else { "wer" };
+    if format == Format::Json {
+        let items = items
+            .iter()
+            .zip(&scores)
+            .map(|(item, score)| {
+                serde_json::json!({
+                    "audio": item.audio,
+                    metric: score.rate(),
+                    "substitutions": score.substitutions,
+                    "deletions": score.deletions,
+                    "insertions": score.insertions,
+                    "reference_len": score.reference_len,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::json!({ metric: total.rate(), "items": items })
+        );
+    } else {
+        for (item, score) in items.iter().zip(&scores) {
+            println!(
+                "{}\t{}={:.2}%",
+                item.audio.display(),
+                metric,
+                score.rate() * 100.0
+            );
+            if score.errors() > 0 {
+                println!("{}\n", score.alignment_table());
+            }
+        }
+        println!(
+            "{}={:.2}% ({} substitutions, {} deletions, {} insertions, {} reference)",
+            metric,
+            total.rate() * 100.0,
+            total.substitutions,
+            total.deletions,
+            total.insertions,
+            total.reference_len
+        );
+    }
+    Ok(())
+}
+
+fn eval_der(config: &Config, format: Format, args: DerArgs) -> Result<()> {
+    let configured = &config.models;
+    let mut diarizer = Diarizer {
+        vad: config::model(args.vad, &configured.vad, "vad")?,
+        extractor: models::extractor(&config::model(
+            args.speaker_model,
+            &configured.speaker,
+            "speaker",
+        )?)?,
+        recognizer: None,
+        max_speakers: args.max_speakers,
+        threshold: args.threshold,
+    };
+    let items = eval::read_rttm_manifest(&args.manifest)?;
+    let (scores, total) = eval::score_diarization(&items, args.collar, |audio| -> Result<_> {
+        Ok(diarizer
+            .diarize(&read_audio(audio)?)?
+            .into_iter()
+            .map(|segment| Turn {
+                file: String::new(),
+                speaker: segment.speaker.unwrap_or_default(),
+                start: segment.start,
+                end: segment.end,
+            })
+            .collect())
+    })?;
+    if format == Format::Json {
+        let items = items
+            .iter()
+            .zip(&scores)
+            .map(|(item, score)| {
+                serde_json::json!({
+                    "audio": item.audio,
+                    "der": score.rate(),
+                    "missed": score.missed,
+                    "false_alarm": score.false_alarm,
+                    "confusion": score.confusion,
+                    "total": score.total,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::json!({ "der": total.rate(), "items": items })
+        );
+    } else {
+        for (item, score) in items.iter().zip(&scores) {
+            println!("{}\tder={:.2}%", item.audio.display(), score.rate() * 100.0);
+        }
+        println!(
+            "der={:.2}% (missed {:.1}s, false alarm {:.1}s, confusion {:.1}s, total {:.1}s)",
+            total.rate() * 100.0,
+            total.missed,
+            total.false_alarm,
+            total.confusion,
+            total.total
+        );
+    }
+    Ok(())
+}
+
+/// Fails with `ExitCode::FAILURE` when `verify` finds no match
+fn speaker(config: &Config, format: Format, args: SpeakerArgs) -> Result<ExitCode> {
+    let configured = &config.models;
+    let mut extractor = models::extractor(&config::model(
+        args.speaker_model,
+        &configured.speaker,
+        "speaker",
+    )?)?;
+    let Some(db_path) = args.db.or_else(|| configured.speakers.clone()) else {
+        bail!("no speaker database, pass --db or set models.speakers in the config file")
+    };
+    let mut db = SpeakerDb::load(&db_path)?;
+    let embedding_size = extractor.embedding_size;
+    let mut embed = |file: &Path| -> Result<Vec<f32>> {
+        Ok(extractor.compute_speaker_embedding(SAMPLE_RATE, read_audio(file)?)?)
+    };
+    match args.command {
+        SpeakerCommand::Enroll { name, files } => {
+            let embeddings = files
+                .iter()
+                .map(|file| embed(file))
+                .collect::<Result<Vec<_>>>()?;
+            db.enroll(name.clone(), &embeddings)?;
+            db.save(&db_path)?;
+            eprintln!("enrolled {} from {} recordings", name, files.len());
+        }
+        SpeakerCommand::Identify { files, threshold } => {
+            let mut manager = db.manager(embedding_size)?;
+            let mut results = Vec::new();
+            for file in &files {
+                let embedding = embed(file)?;
+                let found = manager.get_best_matches(&embedding, threshold, 1)?;
+                let (name, score) = match found.first() {
+                    Some(found) => (Some(found.name.clone()), Some(found.score)),
+                    None => (None, None),
+                };
+                results.push(serde_json::json!({
+                    "file": file,
+                    "speaker": name,
+                    "score": score,
+                }));
+                if format != Format::Json {
+                    println!(
+                        "{}: {}",
+                        file.display(),
+                        name.as_deref().unwrap_or("unknown")
+                    );
+                }
+            }
+            if format == Format::Json {
+                println!("{}", serde_json::Value::from(results));
+            }
+        }
+        SpeakerCommand::Verify {
+            name,
+            file,
+            threshold,
+        } => {
+            let Some(enrolled) = db.get(&name) else {
+                bail!("{} is not enrolled in {}", name, db_path.display())
+            };
+            let score = cosine_similarity(enrolled, &embed(&file)?);
+            let verified = score >= threshold;
+            if format == Format::Json {
+                let result = serde_json::json!({
+                    "speaker": name,
+                    "score": score,
+                    "verified": verified,
+                });
+                println!("{}", result);
+            } else {
+                println!(
+                    "{} (score {:.3})",
+                    if verified { "match" } else { "no match" },
+                    score
+                );
+            }
+            if !verified {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn lang_id(config: &Config, format: Format, args: LangIdArgs) -> Result<()> {
+    let mut slid = models::language_id(&config::model(
+        args.model,
+        &config.models.lang_id,
+        "lang_id",
+    )?)?;
+    for file in args.files {
+        let samples = read_audio(&file)?;
+        let languages = slid.compute_top_k(&samples, SAMPLE_RATE, args.top.max(1))?;
+        if format == Format::Json {
+            let languages = languages
+                .iter()
+                .map(|language| {
+                    serde_json::json!({
+                        "lang": language.lang,
+                        "windows": language.windows,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!(
+                "{}",
+                serde_json::json!({ "file": file, "languages": languages })
+            );
+        } else {
+            for language in languages {
+                println!(
+                    "{}\t{}\t{}",
+                    file.display(),
+                    language.lang,
+                    language.windows
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn tts(config: &Config, args: TtsArgs) -> Result<()> {
+    let mut tts = models::tts(&config::model(args.model, &config.models.tts, "tts")?)?;
+    let text = read_text(args.text)?;
+    let sample = match args.speaker {
+        Some(speaker) => tts.generate_with_speaker(text, &speaker, args.speed)?,
+        None => tts.generate(text, args.sid, args.speed)?,
+    };
+    if args.output == Path::new("-") {
+        sample.write_to(std::io::stdout().lock(), AudioFormat::WavPcm16)?;
+    } else {
+        sample.write_to_wav(&args.output.to_string_lossy())?;
+    }
+    Ok(())
+}
+
+fn kws(config: &Config, format: Format, args: KwsArgs) -> Result<()> {
+    let spotter = models::keyword_spotter(&config::model(args.model, &config.models.kws, "kws")?)?;
+    for file in args.files {
+        let samples = read_audio(&file)?;
+        let detections = match &args.keywords {
+            Some(keywords) => spotter.spot_keywords(keywords, SAMPLE_RATE, &samples)?,
+            None => spotter.spot(SAMPLE_RATE, &samples)?,
+        };
+        if format == Format::Json {
+            let detections = detections
+                .iter()
+                .map(|detection| {
+                    serde_json::json!({
+                        "keyword": detection.keyword,
+                        "start": detection.start,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!(
+                "{}",
+                serde_json::json!({ "file": file, "keywords": detections })
+            );
+        } else {
+            for detection in detections {
+                println!(
+                    "{}\t{:.2}\t{}",
+                    file.display(),
+                    detection.start,
+                    detection.keyword
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_segments(segments: &[Segment], format: Format, file: &Path) -> Result<()> {
+    let options = SubtitleOptions::default();
+    match format {
+        Format::Text => {
+            for segment in segments {
+                let speaker = match &segment.speaker {
+                    Some(speaker) => format!(" {}:", speaker),
+                    None => String::new(),
+                };
+                println!(
+                    "[{:.2} - {:.2}]{} {}",
+                    segment.start, segment.end, speaker, segment.text
+                );
+            }
+        }
+        Format::Json => println!("{}", formats::to_json(segments)),
+        Format::Srt => print!("{}", formats::to_srt(segments, &options)),
+        Format::Vtt => print!("{}", formats::to_vtt(segments, &options)),
+        Format::Rttm => {
+            if segments.iter().all(|segment| segment.speaker.is_none()) {
+                bail!("rttm needs speakers, use it with diarize")
+            }
+            let file_id = file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "stdin".into());
+            print!("{}", formats::to_rttm(segments, &file_id));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Create engines from a model directory, a model file or a manifest.
+
+use crate::input::SAMPLE_RATE;
+use eyre::Result;
+use sherpa_rs::{
+    keyword_spot::KeywordSpotter,
+    language_id::SpokenLanguageId,
+    manifest::{ModelManifest, ModelType},
+    speaker_id::{EmbeddingExtractor, ExtractorConfig},
+    transcribe::{
+        offline::OfflineRecognizer, online::OnlineRecognizer, whisper::WhisperRecognizer,
+    },
+    tts::{OfflineTts, OfflineTtsConfig},
+    vad::{Vad, VadConfig},
+    Recognizer,
+};
+use std::path::Path;
+
+/// Samples per call to the VAD model at 16kHz
+pub const VAD_WINDOW_SIZE: usize = 512;
+
+fn manifest(path: &Path) -> Result<Option<ModelManifest>> {
+    let is_manifest = path.is_file()
+        && matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("toml" | "json")
+        );
+    Ok(if is_manifest {
+        Some(ModelManifest::load(path)?)
+    } else {
+        None
+    })
+}
+
+/// Whisper, streaming or other offline recognizer, from the manifest or the
+/// directory name
+pub fn recognizer(path: &Path) -> Result<Box<dyn Recognizer>> {
+    if let Some(manifest) = manifest(path)? {
+        return Ok(match manifest.model_type {
+            ModelType::Whisper => Box::new(manifest.whisper()?.build()?),
+            _ => Box::new(
+                OfflineRecognizer::builder(manifest.offline_model()?, manifest.file("tokens")?)
+                    .build()?,
+            ),
+        });
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    Ok(if name.contains("whisper") {
+        Box::new(WhisperRecognizer::from_dir(path)?)
+    } else if name.contains("streaming") {
+        Box::new(OnlineRecognizer::from_dir(path)?)
+    } else {
+        Box::new(OfflineRecognizer::from_dir(path)?)
+    })
+}
+
+pub fn vad(path: &Path) -> Result<Vad> {
+    let builder = match manifest(path)? {
+        Some(manifest) => manifest.vad()?,
+        None => VadConfig::builder(path),
+    };
+    let config = builder
+        .sample_rate(SAMPLE_RATE)
+        .window_size(VAD_WINDOW_SIZE as i32)
+        .build()?;
+    Ok(Vad::new_from_config(config, 60.0)?)
+}
+
+/// Speech segments of a recording, as start time in seconds and samples
+pub fn speech_segments(path: &Path, samples: &[f32]) -> Result<Vec<(f32, Vec<f32>)>> {
+    let mut vad = vad(path)?;
+    let mut segments = Vec::new();
+    let mut drain = |vad: &mut Vad| {
+        while let Some(segment) = vad.front() {
+            segments.push((segment.start as f32 / SAMPLE_RATE as f32, segment.samples));
+            vad.pop();
+        }
+    };
+    for window in samples.chunks(VAD_WINDOW_SIZE) {
+        let mut window = window.to_vec();
+        window.resize(VAD_WINDOW_SIZE, 0.0);
+        vad.accept_waveform(window);
+        drain(&mut vad);
+    }
+    vad.flush();
+    drain(&mut vad);
+    Ok(segments)
+}
+
+pub fn extractor(path: &Path) -> Result<EmbeddingExtractor> {
+    let builder = match manifest(path)? {
+        Some(manifest) => manifest.extractor()?,
+        None => ExtractorConfig::builder(path),
+    };
+    Ok(EmbeddingExtractor::new_from_config(builder.build()?)?)
+}
+
+pub fn language_id(path: &Path) -> Result<SpokenLanguageId> {
+    match manifest(path)? {
+        Some(manifest) => Ok(manifest.language_id()?.build()?),
+        None => Ok(SpokenLanguageId::from_dir(path)?),
+    }
+}
+
+pub fn tts(path: &Path) -> Result<OfflineTts> {
+    match manifest(path)? {
+        Some(manifest) => Ok(OfflineTts::new(
+            OfflineTtsConfig::builder(manifest.tts()?).build()?,
+        )?),
+        None => Ok(OfflineTts::from_dir(path)?),
+    }
+}
+
+pub fn keyword_spotter(path: &Path) -> Result<KeywordSpotter> {
+    Ok(KeywordSpotter::from_dir(path)?)
+}
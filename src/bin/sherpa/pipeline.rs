@@ -0,0 +1,92 @@
+//! Transcription and diarization of a whole recording, shared by their
+//! subcommands and `eval`.
+
+use crate::{input::SAMPLE_RATE, models};
+use eyre::Result;
+use sherpa_rs::{
+    embedding_manager::EmbeddingManager, formats::Segment, speaker_id::EmbeddingExtractor,
+    Recognizer,
+};
+use std::path::{Path, PathBuf};
+
+fn end(start: f32, samples: &[f32]) -> f32 {
+    start + samples.len() as f32 / SAMPLE_RATE as f32
+}
+
+fn transcribe_segment(recognizer: &dyn Recognizer, start: f32, samples: &[f32]) -> Result<Segment> {
+    let transcript = recognizer.transcribe(SAMPLE_RATE, samples)?;
+    Ok(
+        Segment::new(start, end(start, samples), transcript.text.trim())
+            .with_tokens(&transcript.tokens, &transcript.timestamps),
+    )
+}
+
+/// Transcribe the recording in one piece, or each speech segment if a VAD model is given
+pub fn transcribe(
+    recognizer: &dyn Recognizer,
+    vad: Option<&Path>,
+    samples: Vec<f32>,
+) -> Result<Vec<Segment>> {
+    let chunks = match vad {
+        Some(vad) => models::speech_segments(vad, &samples)?,
+        None => vec![(0.0, samples)],
+    };
+    chunks
+        .iter()
+        .map(|(start, samples)| transcribe_segment(recognizer, *start, samples))
+        .collect()
+}
+
+/// Speech segments without text
+pub fn speech(vad: &Path, samples: &[f32]) -> Result<Vec<Segment>> {
+    Ok(models::speech_segments(vad, samples)?
+        .into_iter()
+        .map(|(start, samples)| Segment::new(start, end(start, &samples), ""))
+        .collect())
+}
+
+pub struct Diarizer {
+    pub vad: PathBuf,
+    pub extractor: EmbeddingExtractor,
+    /// Also transcribe each segment
+    pub recognizer: Option<Box<dyn Recognizer>>,
+    pub max_speakers: usize,
+    /// Similarity above which a segment belongs to a known speaker
+    pub threshold: f32,
+}
+
+impl Diarizer {
+    /// Label speech segments with speakers named `speaker 0`, `speaker 1`, ...
+    /// in order of appearance. Speakers are not shared between recordings.
+    pub fn diarize(&mut self, samples: &[f32]) -> Result<Vec<Segment>> {
+        let mut manager = EmbeddingManager::new(self.extractor.embedding_size as i32);
+        let mut num_speakers = 0;
+        let mut segments = Vec::new();
+        for (start, samples) in models::speech_segments(&self.vad, samples)? {
+            let mut embedding = self
+                .extractor
+                .compute_speaker_embedding(SAMPLE_RATE, samples.clone())?;
+            let speaker = match manager.search(&embedding, self.threshold) {
+                Some(speaker) => speaker,
+                None if num_speakers < self.max_speakers => {
+                    let speaker = format!("speaker {}", num_speakers);
+                    manager.add(speaker.clone(), &mut embedding)?;
+                    num_speakers += 1;
+                    speaker
+                }
+                // Too many speakers, assign the closest one
+                None => manager
+                    .get_best_matches(&embedding, 0.0, 1)?
+                    .first()
+                    .map(|found| found.name.clone())
+                    .unwrap_or_else(|| "unknown".into()),
+            };
+            let segment = match &self.recognizer {
+                Some(recognizer) => transcribe_segment(recognizer.as_ref(), start, &samples)?,
+                None => Segment::new(start, end(start, &samples), ""),
+            };
+            segments.push(segment.with_speaker(speaker));
+        }
+        Ok(segments)
+    }
+}
@@ -0,0 +1,71 @@
+//! Enrolled speakers, stored as JSON: `{"alice": [0.12, -0.03, ...]}`
+
+use eyre::{bail, Result, WrapErr};
+use sherpa_rs::embedding_manager::EmbeddingManager;
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Debug, Default)]
+pub struct SpeakerDb {
+    speakers: BTreeMap<String, Vec<f32>>,
+}
+
+impl SpeakerDb {
+    /// Load the database, empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        let speakers = serde_json::from_str(&content)
+            .wrap_err_with(|| format!("invalid speaker database {}", path.display()))?;
+        Ok(Self { speakers })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.speakers)?)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Add or replace a speaker with the mean of its embeddings
+    pub fn enroll(&mut self, name: String, embeddings: &[Vec<f32>]) -> Result<()> {
+        let Some(size) = embeddings.first().map(Vec::len) else {
+            bail!("no recordings for {}", name)
+        };
+        if let Some(other) = self.speakers.values().next() {
+            if other.len() != size {
+                bail!(
+                    "embedding size {} doesn't match the database's {}, use the same model",
+                    size,
+                    other.len()
+                )
+            }
+        }
+        let mut mean = vec![0.0; size];
+        for embedding in embeddings {
+            for (mean, value) in mean.iter_mut().zip(embedding) {
+                *mean += value / embeddings.len() as f32;
+            }
+        }
+        self.speakers.insert(name, mean);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[f32]> {
+        self.speakers.get(name).map(Vec::as_slice)
+    }
+
+    pub fn manager(&self, embedding_size: usize) -> Result<EmbeddingManager> {
+        let mut manager = EmbeddingManager::new(embedding_size as i32);
+        for (name, embedding) in &self.speakers {
+            manager.add(name.clone(), &mut embedding.clone())?;
+        }
+        Ok(manager)
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    dot / (norm(a) * norm(b)).max(f32::EPSILON)
+}
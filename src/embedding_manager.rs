@@ -1,8 +1,6 @@
-use eyre::{bail, Result};
+use crate::{bail, cstr_to_string, Result};
 use std::ffi::{CStr, CString};
 
-use crate::cstr_to_string;
-
 #[derive(Debug, Clone)]
 pub struct EmbeddingManager {
     pub(crate) manager: *const sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingManager,
@@ -42,7 +40,7 @@ impl EmbeddingManager {
         embedding: &[f32],
         threshold: f32,
         n: i32,
-    ) -> Vec<SpeakerMatch> {
+    ) -> Result<Vec<SpeakerMatch>> {
         unsafe {
             let result_ptr = sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingManagerGetBestMatches(
                 self.manager,
@@ -51,18 +49,20 @@ impl EmbeddingManager {
                 n,
             );
             if result_ptr.is_null() {
-                return Vec::new();
+                return Ok(Vec::new());
             }
             let result = result_ptr.read();
 
             let matches_c = std::slice::from_raw_parts(result.matches, result.count as usize);
-            let mut matches: Vec<SpeakerMatch> = Vec::new();
-            for i in 0..result.count {
-                let match_c = matches_c[i as usize];
-                let name = cstr_to_string!(match_c.name);
-                let score = match_c.score;
-                matches.push(SpeakerMatch { name, score });
-            }
+            let matches = matches_c
+                .iter()
+                .map(|match_c| {
+                    Ok(SpeakerMatch {
+                        name: cstr_to_string!(match_c.name)?,
+                        score: match_c.score,
+                    })
+                })
+                .collect();
             sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingManagerFreeBestMatches(result_ptr);
             matches
         }
@@ -78,7 +78,7 @@ impl EmbeddingManager {
                 embedding.as_mut_ptr(),
             );
             if status.is_negative() {
-                bail!("Failed to register {}", name)
+                bail!(Native, "Failed to register {}", name)
             }
             Ok(())
         }
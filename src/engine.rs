@@ -0,0 +1,202 @@
+//! Traits over the engines, so code can take any implementation.
+//!
+//! Every engine of the crate implements its trait. Tests of downstream code can
+//! pass the scripted fakes of the `testing` module instead, which need no model.
+
+use crate::Result;
+
+#[cfg(feature = "asr")]
+use crate::transcribe::{
+    offline::{OfflineRecognizer, OfflineRecognizerResult},
+    online::{OnlineRecognizer, OnlineRecognizerResult},
+    whisper::{WhisperRecognizer, WhisperRecognizerResult},
+};
+#[cfg(feature = "vad")]
+use crate::vad::{SpeechSegment, Vad};
+
+/// Recognized text with its tokens and their start times, if the model gives them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    pub tokens: Vec<String>,
+    /// Start time in seconds of each token
+    pub timestamps: Vec<f32>,
+}
+
+impl From<&str> for Transcript {
+    fn from(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "asr")]
+impl From<WhisperRecognizerResult> for Transcript {
+    fn from(result: WhisperRecognizerResult) -> Self {
+        Self {
+            text: result.text,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "asr")]
+impl From<OfflineRecognizerResult> for Transcript {
+    fn from(result: OfflineRecognizerResult) -> Self {
+        Self {
+            text: result.text,
+            tokens: result.tokens,
+            timestamps: result.timestamps,
+        }
+    }
+}
+
+#[cfg(feature = "asr")]
+impl From<OnlineRecognizerResult> for Transcript {
+    fn from(result: OnlineRecognizerResult) -> Self {
+        Self {
+            text: result.text,
+            tokens: result.tokens,
+            timestamps: result.timestamps,
+        }
+    }
+}
+
+/// Speech to text of a whole recording. Takes `&self` so one recognizer can be
+/// shared, for example as `Arc<dyn Recognizer + Send + Sync>`.
+pub trait Recognizer {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript>;
+}
+
+/// Splits a stream of audio into speech segments
+#[cfg(feature = "vad")]
+pub trait VoiceActivityDetector {
+    /// Feed the next window of audio
+    fn accept_waveform(&mut self, samples: &[f32]);
+    /// End the stream, the speech in progress becomes a segment
+    fn flush(&mut self);
+    /// Take the oldest finished segment
+    fn pop_segment(&mut self) -> Option<SpeechSegment>;
+    /// Whether the last window contained speech
+    fn is_speech(&mut self) -> bool;
+    /// Forget the stream and its segments
+    fn clear(&mut self);
+}
+
+/// Voice print of a speaker, compared with cosine similarity
+pub trait SpeakerEmbedder {
+    fn embedding_size(&self) -> usize;
+    fn compute_speaker_embedding(&mut self, sample_rate: i32, samples: &[f32]) -> Result<Vec<f32>>;
+}
+
+/// Text to speech
+#[cfg(feature = "tts")]
+pub trait SpeechSynthesizer {
+    /// Sample rate of the generated audio
+    fn sample_rate(&self) -> i32;
+    fn generate(&mut self, text: &str, sid: i32, speed: f32) -> Result<crate::tts::TtsSample>;
+}
+
+/// Spoken language of a recording, as a code such as `en`
+pub trait LanguageIdentifier {
+    fn compute(&mut self, samples: &[f32], sample_rate: i32) -> Result<String>;
+}
+
+impl<T: Recognizer + ?Sized> Recognizer for Box<T> {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        (**self).transcribe(sample_rate, samples)
+    }
+}
+
+impl<T: Recognizer + ?Sized> Recognizer for std::sync::Arc<T> {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        (**self).transcribe(sample_rate, samples)
+    }
+}
+
+impl<T: Recognizer + ?Sized> Recognizer for &T {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        (**self).transcribe(sample_rate, samples)
+    }
+}
+
+#[cfg(feature = "asr")]
+impl Recognizer for WhisperRecognizer {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        Ok(WhisperRecognizer::transcribe(self, sample_rate, samples.to_vec())?.into())
+    }
+}
+
+#[cfg(feature = "asr")]
+impl Recognizer for OfflineRecognizer {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        Ok(OfflineRecognizer::transcribe(self, sample_rate, samples.to_vec())?.into())
+    }
+}
+
+#[cfg(feature = "asr")]
+impl Recognizer for OnlineRecognizer {
+    fn transcribe(&self, sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        Ok(OnlineRecognizer::transcribe(self, sample_rate, samples.to_vec())?.into())
+    }
+}
+
+#[cfg(feature = "vad")]
+impl VoiceActivityDetector for Vad {
+    fn accept_waveform(&mut self, samples: &[f32]) {
+        Vad::accept_waveform(self, samples.to_vec())
+    }
+
+    fn flush(&mut self) {
+        Vad::flush(self)
+    }
+
+    fn pop_segment(&mut self) -> Option<SpeechSegment> {
+        let segment = self.front()?;
+        self.pop();
+        Some(segment)
+    }
+
+    fn is_speech(&mut self) -> bool {
+        Vad::is_speech(self)
+    }
+
+    fn clear(&mut self) {
+        Vad::clear(self)
+    }
+}
+
+#[cfg(feature = "speaker")]
+impl SpeakerEmbedder for crate::speaker_id::EmbeddingExtractor {
+    fn embedding_size(&self) -> usize {
+        self.embedding_size
+    }
+
+    fn compute_speaker_embedding(&mut self, sample_rate: i32, samples: &[f32]) -> Result<Vec<f32>> {
+        crate::speaker_id::EmbeddingExtractor::compute_speaker_embedding(
+            self,
+            sample_rate,
+            samples.to_vec(),
+        )
+    }
+}
+
+#[cfg(feature = "tts")]
+impl SpeechSynthesizer for crate::tts::OfflineTts {
+    fn sample_rate(&self) -> i32 {
+        crate::tts::OfflineTts::sample_rate(self)
+    }
+
+    fn generate(&mut self, text: &str, sid: i32, speed: f32) -> Result<crate::tts::TtsSample> {
+        crate::tts::OfflineTts::generate(self, text.to_string(), sid, speed)
+    }
+}
+
+#[cfg(feature = "slid")]
+impl LanguageIdentifier for crate::language_id::SpokenLanguageId {
+    fn compute(&mut self, samples: &[f32], sample_rate: i32) -> Result<String> {
+        crate::language_id::SpokenLanguageId::compute(self, samples, sample_rate)
+    }
+}
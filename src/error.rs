@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A model file is missing or sherpa-onnx couldn't load it
+    #[error("{0}")]
+    ModelLoad(String),
+    /// A builder, manifest or model directory doesn't describe a usable engine
+    #[error("{0}")]
+    InvalidConfig(String),
+    /// Audio an engine can't take, such as more samples than the C API can count
+    #[error("{0}")]
+    InvalidAudio(String),
+    /// A call into sherpa-onnx failed
+    #[error("{0}")]
+    Native(String),
+    /// Text from sherpa-onnx, or a path for it on Windows, that isn't UTF-8
+    #[error("invalid UTF-8: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    /// A string or path for sherpa-onnx that contains a NUL byte
+    #[error("string passed to sherpa-onnx contains a NUL byte: {0}")]
+    Nul(#[from] std::ffi::NulError),
+    /// Malformed text input: markup, RTTM, manifests, provider and speaker names
+    #[error("{0}")]
+    Parse(String),
+    /// Encoding audio to Opus or MP3 failed
+    #[error("{0}")]
+    Encode(String),
+    /// An audio device failed or isn't available
+    #[error("{0}")]
+    Device(String),
+    /// The worker thread of an async engine stopped
+    #[error("{0}")]
+    Worker(String),
+    /// An error of a function passed in by the caller, e.g. to `eval::score_transcripts`
+    #[error(transparent)]
+    Callback(Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    /// An error with what was being done, e.g. the file of a manifest that failed
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Wrap the error with what was being done when it happened
+    pub fn context(self, context: impl Display) -> Self {
+        Error::Context {
+            context: context.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+pub(crate) trait ResultExt<T> {
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn with_context<C: Display, F: FnOnce() -> C>(self, context: F) -> Result<T> {
+        self.map_err(|error| error.into().context(context()))
+    }
+}
@@ -0,0 +1,1306 @@
+//! Score recognizers and diarizers against references.
+//!
+//! - `wer` and `cer` align a hypothesis with a reference transcript after
+//!   normalizing both, and count substitutions, deletions and insertions.
+//! - `der` and `jer` compare reference and hypothesis speaker turns, usually
+//!   read from RTTM files with `parse_rttm`.
+//! - `read_transcript_manifest` and `read_rttm_manifest` load lists of
+//!   recordings with their references, which `score_transcripts` and
+//!   `score_diarization` run an engine over.
+
+use crate::{bail, error::ResultExt, Error, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+};
+
+/// How transcripts are normalized before they are compared
+#[derive(Debug, Clone)]
+pub struct Normalization {
+    pub lowercase: bool,
+    /// Remove punctuation, apostrophes inside words are kept
+    pub remove_punctuation: bool,
+    /// Spell out numbers in English, `42` becomes `forty two`
+    pub numbers_to_words: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            remove_punctuation: true,
+            numbers_to_words: false,
+        }
+    }
+}
+
+/// Normalize `text` and collapse whitespace
+pub fn normalize(text: &str, normalization: &Normalization) -> String {
+    let mut words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+    if normalization.numbers_to_words {
+        words = words.iter().map(|word| spell_numbers(word)).collect();
+    }
+    if normalization.remove_punctuation {
+        words = words.iter().map(|word| strip_punctuation(word)).collect();
+    }
+    let text = words.join(" ");
+    let text = if normalization.lowercase {
+        text.to_lowercase()
+    } else {
+        text
+    };
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn strip_punctuation(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let inside_word = i > 0
+                && i + 1 < chars.len()
+                && chars[i - 1].is_alphanumeric()
+                && chars[i + 1].is_alphanumeric();
+            match c {
+                '\'' | '’' if inside_word => c,
+                c if c.is_ascii_punctuation() || is_unicode_punctuation(c) => ' ',
+                c => c,
+            }
+        })
+        .collect()
+}
+
+fn is_unicode_punctuation(c: char) -> bool {
+    "，。、！？：；“”‘’«»…—–¿¡".contains(c)
+}
+
+/// Spell out the numbers of a word: integers, with thousands separators, and decimals
+fn spell_numbers(word: &str) -> String {
+    let start = word.find(|c: char| c.is_ascii_digit());
+    let Some(start) = start else {
+        return word.to_string();
+    };
+    let rest = &word[start..];
+    let end = rest
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_digit()
+                || ((c == ',' || c == '.')
+                    && rest[i + 1..].starts_with(|c: char| c.is_ascii_digit())))
+        })
+        .map_or(rest.len(), |(i, _)| i);
+    let number = &rest[..end];
+    let (integer, fraction) = match number.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (number, None),
+    };
+    let mut spoken = match integer.replace(',', "").parse::<u64>() {
+        Ok(value) => integer_to_words(value),
+        // Too long to be a quantity, read digit by digit
+        Err(_) => digits_to_words(integer),
+    };
+    if let Some(fraction) = fraction {
+        spoken = format!("{} point {}", spoken, digits_to_words(fraction));
+    }
+    let mut suffix = &rest[end..];
+    if fraction.is_none()
+        && ["st", "nd", "rd", "th"]
+            .iter()
+            .any(|ordinal| suffix.to_lowercase().starts_with(ordinal))
+    {
+        spoken = ordinal(&spoken);
+        suffix = &suffix[2..];
+    }
+    format!("{} {} {}", &word[..start], spoken, spell_numbers(suffix))
+}
+
+/// `twenty one` to `twenty first`
+fn ordinal(spoken: &str) -> String {
+    let (head, last) = match spoken.rsplit_once(' ') {
+        Some((head, last)) => (format!("{} ", head), last),
+        None => (String::new(), spoken),
+    };
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        tens if tens.ends_with('y') => format!("{}ieth", &tens[..tens.len() - 1]),
+        other => format!("{}th", other),
+    };
+    head + &last
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+fn digits_to_words(digits: &str) -> String {
+    digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|digit| ONES[digit as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn integer_to_words(value: u64) -> String {
+    fn below_thousand(value: u64, words: &mut Vec<&'static str>) {
+        if value >= 100 {
+            words.push(ONES[(value / 100) as usize]);
+            words.push("hundred");
+        }
+        let rest = value % 100;
+        if rest >= 20 {
+            words.push(TENS[(rest / 10) as usize]);
+            let ones = rest % 10;
+            if ones > 0 {
+                words.push(ONES[ones as usize]);
+            }
+        } else if rest > 0 {
+            words.push(ONES[rest as usize]);
+        }
+    }
+
+    if value == 0 {
+        return ONES[0].to_string();
+    }
+    let mut words = Vec::new();
+    let mut rest = value;
+    for (scale, name) in SCALES {
+        if rest >= scale {
+            below_thousand(rest / scale, &mut words);
+            words.push(name);
+            rest %= scale;
+        }
+    }
+    below_thousand(rest, &mut words);
+    words.join(" ")
+}
+
+/// One step of the alignment between reference and hypothesis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Match(String),
+    Substitution {
+        reference: String,
+        hypothesis: String,
+    },
+    /// In the reference only
+    Deletion(String),
+    /// In the hypothesis only
+    Insertion(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorRate {
+    pub substitutions: usize,
+    pub deletions: usize,
+    pub insertions: usize,
+    /// Words, or characters, in the reference
+    pub reference_len: usize,
+    pub alignment: Vec<Edit>,
+}
+
+impl ErrorRate {
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.deletions + self.insertions
+    }
+
+    /// Errors per reference word or character, can exceed 1 with many insertions
+    pub fn rate(&self) -> f32 {
+        if self.reference_len == 0 {
+            return if self.errors() == 0 { 0.0 } else { 1.0 };
+        }
+        self.errors() as f32 / self.reference_len as f32
+    }
+
+    /// Reference and hypothesis in aligned columns, with `S`, `D` and `I` under errors
+    pub fn alignment_table(&self) -> String {
+        let mut reference = String::from("REF:");
+        let mut hypothesis = String::from("HYP:");
+        let mut marks = String::from("    ");
+        for edit in &self.alignment {
+            let (r, h, mark) = match edit {
+                Edit::Match(word) => (word.as_str(), word.as_str(), ' '),
+                Edit::Substitution {
+                    reference,
+                    hypothesis,
+                } => (reference.as_str(), hypothesis.as_str(), 'S'),
+                Edit::Deletion(word) => (word.as_str(), "", 'D'),
+                Edit::Insertion(word) => ("", word.as_str(), 'I'),
+            };
+            let width = r.chars().count().max(h.chars().count()).max(1);
+            let pad = |text: &str| {
+                if text.is_empty() {
+                    "*".repeat(width)
+                } else {
+                    format!("{:width$}", text, width = width)
+                }
+            };
+            let _ = write!(reference, " {}", pad(r));
+            let _ = write!(hypothesis, " {}", pad(h));
+            let _ = write!(marks, " {:width$}", mark, width = width);
+        }
+        format!(
+            "{}\n{}\n{}",
+            reference.trim_end(),
+            hypothesis.trim_end(),
+            marks.trim_end()
+        )
+    }
+}
+
+/// Corpus totals: counts are summed and alignments appended
+impl AddAssign<&ErrorRate> for ErrorRate {
+    fn add_assign(&mut self, other: &ErrorRate) {
+        self.substitutions += other.substitutions;
+        self.deletions += other.deletions;
+        self.insertions += other.insertions;
+        self.reference_len += other.reference_len;
+        self.alignment.extend(other.alignment.iter().cloned());
+    }
+}
+
+/// Word error rate
+pub fn wer(reference: &str, hypothesis: &str, normalization: &Normalization) -> ErrorRate {
+    let reference = normalize(reference, normalization);
+    let hypothesis = normalize(hypothesis, normalization);
+    align(
+        &reference.split_whitespace().collect::<Vec<_>>(),
+        &hypothesis.split_whitespace().collect::<Vec<_>>(),
+    )
+}
+
+/// Character error rate, whitespace is ignored so it suits languages written without spaces
+pub fn cer(reference: &str, hypothesis: &str, normalization: &Normalization) -> ErrorRate {
+    let chars = |text: &str| -> Vec<String> {
+        normalize(text, normalization)
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from)
+            .collect()
+    };
+    let reference = chars(reference);
+    let hypothesis = chars(hypothesis);
+    align(
+        &reference.iter().map(String::as_str).collect::<Vec<_>>(),
+        &hypothesis.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+}
+
+/// Levenshtein alignment, preferring substitutions over deletion and insertion pairs
+fn align(reference: &[&str], hypothesis: &[&str]) -> ErrorRate {
+    let (n, m) = (reference.len(), hypothesis.len());
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cost) in cost[0].iter_mut().enumerate() {
+        *cost = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let substitution =
+                cost[i - 1][j - 1] + usize::from(reference[i - 1] != hypothesis[j - 1]);
+            cost[i][j] = substitution.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut result = ErrorRate {
+        reference_len: n,
+        ..Default::default()
+    };
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let same = reference[i - 1] == hypothesis[j - 1];
+            if cost[i][j] == cost[i - 1][j - 1] + usize::from(!same) {
+                result.alignment.push(if same {
+                    Edit::Match(reference[i - 1].to_string())
+                } else {
+                    result.substitutions += 1;
+                    Edit::Substitution {
+                        reference: reference[i - 1].to_string(),
+                        hypothesis: hypothesis[j - 1].to_string(),
+                    }
+                });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            result.deletions += 1;
+            result
+                .alignment
+                .push(Edit::Deletion(reference[i - 1].to_string()));
+            i -= 1;
+        } else {
+            result.insertions += 1;
+            result
+                .alignment
+                .push(Edit::Insertion(hypothesis[j - 1].to_string()));
+            j -= 1;
+        }
+    }
+    result.alignment.reverse();
+    result
+}
+
+/// A speaker turn, as in a `SPEAKER` line of an RTTM file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub file: String,
+    pub speaker: String,
+    /// Start time in seconds
+    pub start: f32,
+    /// End time in seconds
+    pub end: f32,
+}
+
+/// Read the `SPEAKER` lines of an RTTM file, other line types are ignored
+pub fn parse_rttm(content: &str) -> Result<Vec<Turn>> {
+    let mut turns = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() != Some(&"SPEAKER") {
+            continue;
+        }
+        if fields.len() < 8 {
+            bail!(
+                Parse,
+                "line {}: expected at least 8 fields, got `{}`",
+                number + 1,
+                line
+            )
+        }
+        let parse = |field: &str| -> Result<f32> {
+            field
+                .parse()
+                .map_err(|_| Error::Parse(format!("line {}: invalid time `{}`", number + 1, field)))
+        };
+        let start = parse(fields[3])?;
+        let duration = parse(fields[4])?;
+        turns.push(Turn {
+            file: fields[1].to_string(),
+            speaker: fields[7].to_string(),
+            start,
+            end: start + duration,
+        });
+    }
+    Ok(turns)
+}
+
+/// Diarization errors in seconds of speech, summed over speakers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiarizationError {
+    /// Reference speech attributed to no speaker
+    pub missed: f32,
+    /// Hypothesis speech where the reference has none
+    pub false_alarm: f32,
+    /// Speech attributed to the wrong speaker
+    pub confusion: f32,
+    /// Scored reference speech
+    pub total: f32,
+}
+
+impl DiarizationError {
+    /// Diarization error rate
+    pub fn rate(&self) -> f32 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+        (self.missed + self.false_alarm + self.confusion) / self.total
+    }
+}
+
+impl AddAssign<&DiarizationError> for DiarizationError {
+    fn add_assign(&mut self, other: &DiarizationError) {
+        self.missed += other.missed;
+        self.false_alarm += other.false_alarm;
+        self.confusion += other.confusion;
+        self.total += other.total;
+    }
+}
+
+/// Time every reference and hypothesis speaker talks, and overlaps between them
+struct Overlaps {
+    reference: Vec<String>,
+    hypothesis: Vec<String>,
+    reference_time: Vec<f32>,
+    hypothesis_time: Vec<f32>,
+    /// `[reference][hypothesis]` seconds both talk
+    overlap: Vec<Vec<f32>>,
+    /// Elementary segments: duration and the speakers active in it
+    regions: Vec<(f32, Vec<usize>, Vec<usize>)>,
+}
+
+impl Overlaps {
+    /// Turns of one file. Time within `collar` seconds of a reference boundary isn't scored.
+    fn new(reference: &[&Turn], hypothesis: &[&Turn], collar: f32) -> Self {
+        let names = |turns: &[&Turn]| -> Vec<String> {
+            turns
+                .iter()
+                .map(|turn| turn.speaker.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect()
+        };
+        let reference_names = names(reference);
+        let hypothesis_names = names(hypothesis);
+        let index = |names: &[String], speaker: &str| {
+            names
+                .iter()
+                .position(|name| name == speaker)
+                .expect("names are collected from the turns")
+        };
+
+        let no_score: Vec<(f32, f32)> = if collar > 0.0 {
+            reference
+                .iter()
+                .flat_map(|turn| [turn.start, turn.end])
+                .map(|boundary| (boundary - collar, boundary + collar))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut boundaries: Vec<f32> = reference
+            .iter()
+            .chain(hypothesis)
+            .flat_map(|turn| [turn.start, turn.end])
+            .chain(no_score.iter().flat_map(|&(start, end)| [start, end]))
+            .collect();
+        boundaries.sort_by(f32::total_cmp);
+        boundaries.dedup();
+
+        let mut overlaps = Self {
+            reference_time: vec![0.0; reference_names.len()],
+            hypothesis_time: vec![0.0; hypothesis_names.len()],
+            overlap: vec![vec![0.0; hypothesis_names.len()]; reference_names.len()],
+            reference: reference_names,
+            hypothesis: hypothesis_names,
+            regions: Vec::new(),
+        };
+        for window in boundaries.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let duration = end - start;
+            let middle = (start + end) / 2.0;
+            if duration <= 0.0
+                || no_score
+                    .iter()
+                    .any(|&(start, end)| start <= middle && middle < end)
+            {
+                continue;
+            }
+            let active = |turns: &[&Turn], names: &[String]| -> Vec<usize> {
+                turns
+                    .iter()
+                    .filter(|turn| turn.start <= middle && middle < turn.end)
+                    .map(|turn| index(names, &turn.speaker))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect()
+            };
+            let active_reference = active(reference, &overlaps.reference);
+            let active_hypothesis = active(hypothesis, &overlaps.hypothesis);
+            if active_reference.is_empty() && active_hypothesis.is_empty() {
+                continue;
+            }
+            for &r in &active_reference {
+                overlaps.reference_time[r] += duration;
+                for &h in &active_hypothesis {
+                    overlaps.overlap[r][h] += duration;
+                }
+            }
+            for &h in &active_hypothesis {
+                overlaps.hypothesis_time[h] += duration;
+            }
+            overlaps
+                .regions
+                .push((duration, active_reference, active_hypothesis));
+        }
+        overlaps
+    }
+}
+
+/// Group turns by file, keeping files that are only in one of the lists
+fn by_file<'a>(
+    reference: &'a [Turn],
+    hypothesis: &'a [Turn],
+) -> BTreeMap<&'a str, (Vec<&'a Turn>, Vec<&'a Turn>)> {
+    let mut files: BTreeMap<&str, (Vec<&Turn>, Vec<&Turn>)> = BTreeMap::new();
+    for turn in reference {
+        files.entry(&turn.file).or_default().0.push(turn);
+    }
+    for turn in hypothesis {
+        files.entry(&turn.file).or_default().1.push(turn);
+    }
+    files
+}
+
+/// Diarization error rate with the speaker mapping that maximizes overlap, per file.
+/// `collar` is usually 0.25 seconds, overlapping speech is scored.
+pub fn der(reference: &[Turn], hypothesis: &[Turn], collar: f32) -> DiarizationError {
+    let mut error = DiarizationError::default();
+    for (reference, hypothesis) in by_file(reference, hypothesis).values() {
+        let overlaps = Overlaps::new(reference, hypothesis, collar);
+        let gain: Vec<Vec<f32>> = overlaps
+            .overlap
+            .iter()
+            .map(|row| row.iter().map(|overlap| -overlap).collect())
+            .collect();
+        let mapping = assign(&gain);
+
+        for (duration, active_reference, active_hypothesis) in &overlaps.regions {
+            let (n_reference, n_hypothesis) = (active_reference.len(), active_hypothesis.len());
+            let correct = active_reference
+                .iter()
+                .filter(|&&r| mapping[r].is_some_and(|h| active_hypothesis.contains(&h)))
+                .count();
+            error.total += n_reference as f32 * duration;
+            error.missed += n_reference.saturating_sub(n_hypothesis) as f32 * duration;
+            error.false_alarm += n_hypothesis.saturating_sub(n_reference) as f32 * duration;
+            error.confusion += (n_reference.min(n_hypothesis) - correct) as f32 * duration;
+        }
+    }
+    error
+}
+
+/// Jaccard error rate: the mean over reference speakers of one minus the
+/// intersection over union of their speech with their mapped hypothesis speaker
+pub fn jer(reference: &[Turn], hypothesis: &[Turn], collar: f32) -> f32 {
+    let mut errors = Vec::new();
+    for (reference, hypothesis) in by_file(reference, hypothesis).values() {
+        let overlaps = Overlaps::new(reference, hypothesis, collar);
+        let jaccard_error = |r: usize, h: usize| {
+            let intersection = overlaps.overlap[r][h];
+            let union = overlaps.reference_time[r] + overlaps.hypothesis_time[h] - intersection;
+            if union > 0.0 {
+                1.0 - intersection / union
+            } else {
+                1.0
+            }
+        };
+        let cost: Vec<Vec<f32>> = (0..overlaps.reference.len())
+            .map(|r| {
+                (0..overlaps.hypothesis.len())
+                    .map(|h| jaccard_error(r, h))
+                    .collect()
+            })
+            .collect();
+        let mapping = assign(&cost);
+        for (r, h) in mapping.into_iter().enumerate() {
+            errors.push(h.map_or(1.0, |h| jaccard_error(r, h)));
+        }
+    }
+    if errors.is_empty() {
+        return 0.0;
+    }
+    errors.iter().sum::<f32>() / errors.len() as f32
+}
+
+/// Minimum cost assignment of rows to columns (Hungarian algorithm).
+/// Rows left over when there are more rows than columns map to `None`.
+fn assign(cost: &[Vec<f32>]) -> Vec<Option<usize>> {
+    let rows = cost.len();
+    let columns = cost.first().map_or(0, Vec::len);
+    let size = rows.max(columns);
+    if rows == 0 || columns == 0 {
+        return vec![None; rows];
+    }
+    // Square matrix padded with zero costs, 1-based as in the usual formulation
+    let at = |i: usize, j: usize| -> f64 {
+        if i <= rows && j <= columns {
+            cost[i - 1][j - 1] as f64
+        } else {
+            0.0
+        }
+    };
+    let mut u = vec![0.0f64; size + 1];
+    let mut v = vec![0.0f64; size + 1];
+    let mut matched_row = vec![0usize; size + 1];
+    let mut way = vec![0usize; size + 1];
+    for i in 1..=size {
+        matched_row[0] = i;
+        let mut j0 = 0;
+        let mut min = vec![f64::INFINITY; size + 1];
+        let mut used = vec![false; size + 1];
+        loop {
+            used[j0] = true;
+            let i0 = matched_row[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=size {
+                if !used[j] {
+                    let reduced = at(i0, j) - u[i0] - v[j];
+                    if reduced < min[j] {
+                        min[j] = reduced;
+                        way[j] = j0;
+                    }
+                    if min[j] < delta {
+                        delta = min[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..=size {
+                if used[j] {
+                    u[matched_row[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if matched_row[j0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let j1 = way[j0];
+            matched_row[j0] = matched_row[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut mapping = vec![None; rows];
+    for (j, &i) in matched_row.iter().enumerate().take(columns + 1).skip(1) {
+        if (1..=rows).contains(&i) {
+            mapping[i - 1] = Some(j - 1);
+        }
+    }
+    mapping
+}
+
+/// A recording and its reference transcript
+#[derive(Debug, Clone)]
+pub struct TranscriptItem {
+    pub audio: PathBuf,
+    pub reference: String,
+}
+
+/// A recording and its reference speaker turns
+#[derive(Debug, Clone)]
+pub struct DiarizationItem {
+    pub audio: PathBuf,
+    pub reference: Vec<Turn>,
+}
+
+/// Tab separated `audio<TAB>reference` lines, `#` starts a comment.
+/// Relative paths are relative to the manifest.
+fn read_manifest(path: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut items = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let Some((audio, reference)) = line.split_once('\t') else {
+            bail!(
+                Parse,
+                "{} line {}: expected `audio<TAB>reference`",
+                path.display(),
+                number + 1
+            )
+        };
+        items.push((dir.join(audio), reference.to_string()));
+    }
+    Ok(items)
+}
+
+/// Manifest of recordings with their reference transcripts, see `read_manifest`
+pub fn read_transcript_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<TranscriptItem>> {
+    Ok(read_manifest(path.as_ref())?
+        .into_iter()
+        .map(|(audio, reference)| TranscriptItem { audio, reference })
+        .collect())
+}
+
+/// Manifest of recordings with the paths of their reference RTTM files
+pub fn read_rttm_manifest<P: AsRef<Path>>(path: P) -> Result<Vec<DiarizationItem>> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    read_manifest(path)?
+        .into_iter()
+        .map(|(audio, rttm)| {
+            let rttm = dir.join(rttm);
+            let content = std::fs::read_to_string(&rttm)
+                .with_context(|| format!("failed to read {}", rttm.display()))?;
+            let reference =
+                parse_rttm(&content).with_context(|| format!("invalid RTTM {}", rttm.display()))?;
+            Ok(DiarizationItem { audio, reference })
+        })
+        .collect()
+}
+
+/// Transcribe every item, returns the error rate of each and the corpus total.
+/// Uses `cer` if `characters` is set, `wer` otherwise.
+pub fn score_transcripts<F, E>(
+    items: &[TranscriptItem],
+    normalization: &Normalization,
+    characters: bool,
+    mut transcribe: F,
+) -> Result<(Vec<ErrorRate>, ErrorRate)>
+where
+    F: FnMut(&Path) -> Result<String, E>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut total = ErrorRate::default();
+    let mut scores = Vec::new();
+    for item in items {
+        let hypothesis = transcribe(&item.audio)
+            .map_err(|error| Error::Callback(error.into()))
+            .with_context(|| format!("failed to transcribe {}", item.audio.display()))?;
+        let score = if characters {
+            cer(&item.reference, &hypothesis, normalization)
+        } else {
+            wer(&item.reference, &hypothesis, normalization)
+        };
+        total += &score;
+        scores.push(score);
+    }
+    Ok((scores, total))
+}
+
+/// Diarize every item, returns the error of each and the corpus total.
+/// File ids of the hypothesis turns are ignored, each item is scored on its own.
+pub fn score_diarization<F, E>(
+    items: &[DiarizationItem],
+    collar: f32,
+    mut diarize: F,
+) -> Result<(Vec<DiarizationError>, DiarizationError)>
+where
+    F: FnMut(&Path) -> Result<Vec<Turn>, E>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut total = DiarizationError::default();
+    let mut scores = Vec::new();
+    for item in items {
+        let hypothesis = diarize(&item.audio)
+            .map_err(|error| Error::Callback(error.into()))
+            .with_context(|| format!("failed to diarize {}", item.audio.display()))?;
+        let reference: Vec<Turn> = item
+            .reference
+            .iter()
+            .map(|turn| Turn {
+                file: String::new(),
+                ..turn.clone()
+            })
+            .collect();
+        let hypothesis: Vec<Turn> = hypothesis
+            .into_iter()
+            .map(|turn| Turn {
+                file: String::new(),
+                ..turn
+            })
+            .collect();
+        let score = der(&reference, &hypothesis, collar);
+        total += &score;
+        scores.push(score);
+    }
+    Ok((scores, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn turn(file: &str, speaker: &str, start: f32, end: f32) -> Turn {
+        Turn {
+            file: file.into(),
+            speaker: speaker.into(),
+            start,
+            end,
+        }
+    }
+
+    /// Directory removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "sherpa-rs-eval-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, content) in files {
+                std::fs::write(dir.join(file), content).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn wer_counts_edits() {
+        let score = wer(
+            "the cat sat on the mat",
+            "the cat sit on mat",
+            &Normalization::default(),
+        );
+        assert_eq!(
+            (score.substitutions, score.deletions, score.insertions),
+            (1, 1, 0)
+        );
+        assert_eq!(score.reference_len, 6);
+        assert_close(score.rate(), 2.0 / 6.0);
+
+        let score = wer("a b c d", "a x c d e", &Normalization::default());
+        assert_eq!(
+            (score.substitutions, score.deletions, score.insertions),
+            (1, 0, 1)
+        );
+        assert_close(score.rate(), 0.5);
+    }
+
+    #[test]
+    fn wer_can_exceed_one() {
+        let score = wer("yes", "yes yes no", &Normalization::default());
+        assert_eq!(score.insertions, 2);
+        assert_close(score.rate(), 2.0);
+        assert_close(wer("a b c", "", &Normalization::default()).rate(), 1.0);
+    }
+
+    #[test]
+    fn empty_reference() {
+        let score = wer("", "", &Normalization::default());
+        assert_eq!(score.errors(), 0);
+        assert_eq!(score.rate(), 0.0);
+        let score = wer("", "uh hello", &Normalization::default());
+        assert_eq!((score.insertions, score.reference_len), (2, 0));
+        assert_eq!(score.rate(), 1.0);
+    }
+
+    #[test]
+    fn prefers_substitutions() {
+        let score = wer("a b", "c d", &Normalization::default());
+        assert_eq!(
+            (score.substitutions, score.deletions, score.insertions),
+            (2, 0, 0)
+        );
+    }
+
+    #[test]
+    fn alignment_table_marks_errors() {
+        let score = wer("the cat sat", "a cat sat down", &Normalization::default());
+        assert_eq!(
+            score.alignment_table(),
+            "REF: the cat sat ****\nHYP: a   cat sat down\n     S           I"
+        );
+    }
+
+    #[test]
+    fn corpus_totals_add_up() {
+        let mut total = wer("a b", "a", &Normalization::default());
+        total += &wer("c d e", "c x e", &Normalization::default());
+        assert_eq!(total.reference_len, 5);
+        assert_eq!(total.errors(), 2);
+        assert_eq!(total.alignment.len(), 5);
+    }
+
+    #[test]
+    fn cer_ignores_spaces() {
+        let score = cer("abcd", "a bed", &Normalization::default());
+        assert_eq!((score.substitutions, score.reference_len), (1, 4));
+        let score = cer("你好世界", "你好", &Normalization::default());
+        assert_eq!(score.deletions, 2);
+        assert_close(score.rate(), 0.5);
+    }
+
+    #[test]
+    fn normalizes_case_and_punctuation() {
+        let normalization = Normalization::default();
+        assert_eq!(
+            normalize("Hello,  World! Don't “stop”…", &normalization),
+            "hello world don't stop"
+        );
+        assert_eq!(
+            wer("Hello, world.", "hello world", &normalization).errors(),
+            0
+        );
+        let raw = Normalization {
+            lowercase: false,
+            remove_punctuation: false,
+            numbers_to_words: false,
+        };
+        assert_eq!(normalize(" Hello,\tWorld ", &raw), "Hello, World");
+    }
+
+    #[test]
+    fn spells_numbers() {
+        let normalization = Normalization {
+            numbers_to_words: true,
+            ..Default::default()
+        };
+        let cases = [
+            ("0", "zero"),
+            ("42", "forty two"),
+            ("115", "one hundred fifteen"),
+            ("1,234", "one thousand two hundred thirty four"),
+            ("2000000", "two million"),
+            ("3.14", "three point one four"),
+            ("21st", "twenty first"),
+            ("12th", "twelfth"),
+            ("3rd", "third"),
+            ("20th", "twentieth"),
+            ("100th", "one hundredth"),
+            ("$5.", "five"),
+            ("99999999999999999999", "nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine nine"),
+            ("it costs 7 euros", "it costs seven euros"),
+        ];
+        for (text, spoken) in cases {
+            assert_eq!(normalize(text, &normalization), spoken, "{}", text);
+        }
+        assert_eq!(
+            integer_to_words(u64::MAX),
+            "eighteen quintillion four hundred forty six quadrillion seven hundred forty four trillion seventy three billion seven hundred nine million five hundred fifty one thousand six hundred fifteen"
+        );
+    }
+
+    #[test]
+    fn parses_rttm() {
+        let rttm = "SPKR-INFO meeting 1 <NA> <NA> <NA> unknown alice <NA> <NA>\n\
+                    SPEAKER meeting 1 0.50 2.25 <NA> <NA> alice <NA> <NA>\n\
+                    \n\
+                    SPEAKER meeting 1 3.000 1.000 <NA> <NA> bob <NA> <NA>\n";
+        assert_eq!(
+            parse_rttm(rttm).unwrap(),
+            [
+                turn("meeting", "alice", 0.5, 2.75),
+                turn("meeting", "bob", 3.0, 4.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rttm() {
+        let error = parse_rttm("SPEAKER meeting 1 0.5 1.0 <NA> <NA>").unwrap_err();
+        assert!(error.to_string().contains("line 1"), "{}", error);
+        let error = parse_rttm("\nSPEAKER meeting 1 abc 1.0 <NA> <NA> alice").unwrap_err();
+        assert!(matches!(error, Error::Parse(_)));
+        assert!(
+            error.to_string().contains("line 2: invalid time `abc`"),
+            "{}",
+            error
+        );
+    }
+
+    /// Worked by hand with the md-eval definitions, which dscore follows:
+    /// errors are summed over elementary segments with the speaker mapping that
+    /// maximizes overlap, and overlapping reference speech counts once per speaker.
+    fn der_fixture() -> (Vec<Turn>, Vec<Turn>) {
+        let reference = vec![
+            turn("meeting", "A", 0.0, 10.0),
+            turn("meeting", "B", 10.0, 20.0),
+            turn("meeting", "A", 15.0, 18.0),
+        ];
+        let hypothesis = vec![
+            turn("meeting", "s1", 0.0, 9.0),
+            turn("meeting", "s2", 9.0, 20.0),
+            turn("meeting", "s1", 21.0, 22.0),
+        ];
+        (reference, hypothesis)
+    }
+
+    #[test]
+    fn der_without_collar() {
+        let (reference, hypothesis) = der_fixture();
+        let error = der(&reference, &hypothesis, 0.0);
+        // A is missed in the overlap 15-18, s2 speaks for A 9-10, s1 speaks alone 21-22
+        assert_close(error.total, 23.0);
+        assert_close(error.missed, 3.0);
+        assert_close(error.false_alarm, 1.0);
+        assert_close(error.confusion, 1.0);
+        assert_close(error.rate(), 5.0 / 23.0);
+    }
+
+    #[test]
+    fn der_with_collar() {
+        let (reference, hypothesis) = der_fixture();
+        let error = der(&reference, &hypothesis, 0.25);
+        // 0.25 seconds on both sides of 0, 10, 15, 18 and 20 aren't scored
+        assert_close(error.total, 20.5);
+        assert_close(error.missed, 2.5);
+        assert_close(error.false_alarm, 1.0);
+        assert_close(error.confusion, 0.75);
+        assert_close(error.rate(), 4.25 / 20.5);
+    }
+
+    #[test]
+    fn der_ignores_speaker_names() {
+        let (reference, _) = der_fixture();
+        let renamed: Vec<Turn> = reference
+            .iter()
+            .map(|turn| Turn {
+                speaker: format!("spk-{}", turn.speaker),
+                ..turn.clone()
+            })
+            .collect();
+        let error = der(&reference, &renamed, 0.0);
+        assert_eq!(error.rate(), 0.0);
+        assert_close(error.total, 23.0);
+    }
+
+    #[test]
+    fn der_scores_files_separately() {
+        let reference = [turn("a", "x", 0.0, 1.0), turn("b", "x", 0.0, 1.0)];
+        let hypothesis = [turn("a", "1", 0.0, 1.0), turn("b", "2", 0.0, 1.0)];
+        assert_eq!(der(&reference, &hypothesis, 0.0).rate(), 0.0);
+        let error = der(&reference, &[], 0.0);
+        assert_close(error.missed, 2.0);
+        assert_close(error.rate(), 1.0);
+        assert_eq!(der(&[], &[], 0.0).rate(), 0.0);
+    }
+
+    #[test]
+    fn jer_of_fixture() {
+        let (reference, hypothesis) = der_fixture();
+        // A: 1 - 9/14 with s1, B: 1 - 10/11 with s2
+        assert_close(
+            jer(&reference, &hypothesis, 0.0),
+            (5.0 / 14.0 + 1.0 / 11.0) / 2.0,
+        );
+        assert_eq!(jer(&reference, &reference, 0.0), 0.0);
+        // Unmapped reference speakers count as fully wrong
+        let single = [turn("meeting", "s", 0.0, 10.0)];
+        assert_close(
+            jer(&reference, &single, 0.0),
+            (1.0 - 10.0 / 13.0 + 1.0) / 2.0,
+        );
+    }
+
+    #[test]
+    fn jer_with_collar() {
+        let (reference, hypothesis) = der_fixture();
+        // Scored seconds: A 12, B 8.5, s1 9.75, s2 9.25, A with s1 8.75, B with s2 8.5
+        let a = 1.0 - 8.75 / (12.0 + 9.75 - 8.75);
+        let b = 1.0 - 8.5 / (8.5 + 9.25 - 8.5);
+        assert_close(jer(&reference, &hypothesis, 0.25), (a + b) / 2.0);
+    }
+
+    fn total_cost(cost: &[Vec<f32>], mapping: &[Option<usize>]) -> f32 {
+        mapping
+            .iter()
+            .enumerate()
+            .filter_map(|(r, c)| c.map(|c| cost[r][c]))
+            .sum()
+    }
+
+    #[test]
+    fn assigns_rectangular_matrices() {
+        let wide = vec![vec![4.0, 1.0, 5.0], vec![2.0, 0.0, 6.0]];
+        assert_eq!(assign(&wide), [Some(1), Some(0)]);
+        let tall = vec![vec![1.0, 5.0], vec![4.0, 2.0], vec![3.0, 3.0]];
+        assert_eq!(assign(&tall), [Some(0), Some(1), None]);
+        assert_eq!(assign(&[]), []);
+        assert_eq!(assign(&[vec![], vec![]]), [None, None]);
+    }
+
+    #[test]
+    fn assignment_is_optimal() {
+        fn best(cost: &[Vec<f32>], row: usize, used: &mut Vec<bool>) -> f32 {
+            if row == cost.len() {
+                return 0.0;
+            }
+            let columns = used.len();
+            let free = used.iter().filter(|used| !**used).count();
+            // A row can stay unassigned only if there are more rows than columns
+            let mut best_cost = if cost.len() - row > free {
+                best(cost, row + 1, used)
+            } else {
+                f32::INFINITY
+            };
+            for c in 0..columns {
+                if !used[c] {
+                    used[c] = true;
+                    best_cost = best_cost.min(cost[row][c] + best(cost, row + 1, used));
+                    used[c] = false;
+                }
+            }
+            best_cost
+        }
+
+        let mut seed = 12345u32;
+        let mut random = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as f32 / 65536.0
+        };
+        for (rows, columns) in [(3, 3), (2, 4), (4, 2), (5, 5), (1, 3), (4, 1)] {
+            let cost: Vec<Vec<f32>> = (0..rows)
+                .map(|_| (0..columns).map(|_| random()).collect())
+                .collect();
+            let mapping = assign(&cost);
+            let mut columns_used: Vec<usize> = mapping.iter().flatten().copied().collect();
+            assert_eq!(columns_used.len(), rows.min(columns));
+            columns_used.sort();
+            columns_used.dedup();
+            assert_eq!(columns_used.len(), rows.min(columns));
+            assert_close(
+                total_cost(&cost, &mapping),
+                best(&cost, 0, &mut vec![false; columns]),
+            );
+        }
+    }
+
+    #[test]
+    fn reads_manifests() {
+        let fixture = Fixture::new(
+            "manifest",
+            &[(
+                "transcripts.tsv",
+                "# audio\treference\n\none.wav\tHello world\n\nsub/two.wav\tgood bye\n",
+            )],
+        );
+        let items = read_transcript_manifest(fixture.0.join("transcripts.tsv")).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].audio, fixture.0.join("one.wav"));
+        assert_eq!(items[0].reference, "Hello world");
+        assert_eq!(items[1].audio, fixture.0.join("sub/two.wav"));
+
+        let invalid = Fixture::new("invalid-manifest", &[("bad.tsv", "one.wav hello\n")]);
+        let error = read_transcript_manifest(invalid.0.join("bad.tsv")).unwrap_err();
+        assert!(error.to_string().contains("line 1"), "{}", error);
+        assert!(read_transcript_manifest(invalid.0.join("missing.tsv")).is_err());
+    }
+
+    #[test]
+    fn scores_transcripts() {
+        let items = [
+            TranscriptItem {
+                audio: "one.wav".into(),
+                reference: "the cat sat".into(),
+            },
+            TranscriptItem {
+                audio: "two.wav".into(),
+                reference: "on the mat".into(),
+            },
+        ];
+        let (scores, total) = score_transcripts(
+            &items,
+            &Normalization::default(),
+            false,
+            |audio| -> Result<String> {
+                Ok(match audio.to_str() {
+                    Some("one.wav") => "The cat sat.".into(),
+                    _ => "on a mat".into(),
+                })
+            },
+        )
+        .unwrap();
+        assert_eq!(scores[0].errors(), 0);
+        assert_eq!(scores[1].substitutions, 1);
+        assert_eq!((total.errors(), total.reference_len), (1, 6));
+
+        let error = score_transcripts(&items, &Normalization::default(), true, |_| {
+            Err::<String, _>("no model")
+        })
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("failed to transcribe one.wav"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn scores_diarization_manifests() {
+        let (reference, _) = der_fixture();
+        let rttm: String = reference
+            .iter()
+            .map(|turn| {
+                format!(
+                    "SPEAKER meeting 1 {} {} <NA> <NA> {} <NA> <NA>\n",
+                    turn.start,
+                    turn.end - turn.start,
+                    turn.speaker
+                )
+            })
+            .collect();
+        let fixture = Fixture::new(
+            "rttm-manifest",
+            &[
+                ("meeting.rttm", &rttm),
+                ("manifest.tsv", "meeting.wav\tmeeting.rttm\n"),
+            ],
+        );
+        let items = read_rttm_manifest(fixture.0.join("manifest.tsv")).unwrap();
+        assert_eq!(items[0].reference, reference);
+
+        // Hypothesis file ids don't need to match the reference
+        let (scores, total) = score_diarization(&items, 0.0, |_| -> Result<Vec<Turn>> {
+            let (_, hypothesis) = der_fixture();
+            Ok(hypothesis
+                .into_iter()
+                .map(|turn| Turn {
+                    file: "other".into(),
+                    ..turn
+                })
+                .collect())
+        })
+        .unwrap();
+        assert_close(scores[0].rate(), 5.0 / 23.0);
+        assert_close(total.rate(), 5.0 / 23.0);
+    }
+}
@@ -0,0 +1,498 @@
+//! Render recognition and diarization results as subtitles, RTTM or JSON.
+//!
+//! Every format takes a list of `Segment`s, in seconds from the start of the
+//! recording. Subtitle formats split long segments into cues of at most
+//! `SubtitleOptions::max_lines` lines of `max_line_length` characters, timing the
+//! cues from word timings when the segment has them.
+
+use std::fmt::Write;
+
+/// A word, or token, with its timing
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Word {
+    /// Start time in seconds
+    pub start: f32,
+    /// End time in seconds
+    pub end: f32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment {
+    /// Start time in seconds
+    pub start: f32,
+    /// End time in seconds
+    pub end: f32,
+    pub text: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub speaker: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub words: Vec<Word>,
+}
+
+impl Segment {
+    pub fn new<S: Into<String>>(start: f32, end: f32, text: S) -> Self {
+        Self {
+            start,
+            end,
+            text: text.into(),
+            speaker: None,
+            words: Vec::new(),
+        }
+    }
+
+    pub fn with_speaker<S: Into<String>>(mut self, speaker: S) -> Self {
+        self.speaker = Some(speaker.into());
+        self
+    }
+
+    /// Word timings from recognizer tokens and their start times, relative to the
+    /// segment start. Tokens beginning with a space or `▁` start a new word.
+    pub fn with_tokens(mut self, tokens: &[String], timestamps: &[f32]) -> Self {
+        let mut words: Vec<Word> = Vec::new();
+        for (token, &timestamp) in tokens.iter().zip(timestamps) {
+            let starts_word = token.starts_with(' ') || token.starts_with('▁');
+            let text = token.trim_start_matches([' ', '▁']);
+            match words.last_mut() {
+                Some(word) if !starts_word => word.text.push_str(text),
+                _ if text.is_empty() => {}
+                _ => words.push(Word {
+                    start: self.start + timestamp,
+                    end: self.end,
+                    text: text.to_string(),
+                }),
+            }
+        }
+        // A word ends where the next one starts
+        for i in 1..words.len() {
+            words[i - 1].end = words[i].start;
+        }
+        self.words = words;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    /// Characters per line, longer words get a line of their own
+    pub max_line_length: usize,
+    /// Lines per cue
+    pub max_lines: usize,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        // Common broadcast guidelines
+        Self {
+            max_line_length: 42,
+            max_lines: 2,
+        }
+    }
+}
+
+/// A subtitle cue, `text` holds its lines separated by `\n`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+    pub speaker: Option<String>,
+}
+
+/// Split segments into cues that fit `options`
+pub fn split_segments(segments: &[Segment], options: &SubtitleOptions) -> Vec<Cue> {
+    let max_line_length = options.max_line_length.max(1);
+    let max_lines = options.max_lines.max(1);
+    let mut cues = Vec::new();
+
+    for segment in segments {
+        // Words without timings are timed by their share of the characters
+        let words = if segment.words.is_empty() {
+            let words: Vec<&str> = segment.text.split_whitespace().collect();
+            let total: usize = words.iter().map(|word| word.chars().count() + 1).sum();
+            let seconds = (segment.end - segment.start) / total.max(1) as f32;
+            let mut start = segment.start;
+            words
+                .into_iter()
+                .map(|word| {
+                    let end = start + (word.chars().count() + 1) as f32 * seconds;
+                    let word = Word {
+                        start,
+                        end,
+                        text: word.to_string(),
+                    };
+                    start = end;
+                    word
+                })
+                .collect()
+        } else {
+            segment.words.clone()
+        };
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut cue_start = segment.start;
+        let mut cue_end = segment.start;
+        for word in &words {
+            let fits = lines.last().is_some_and(|line| {
+                line.chars().count() + 1 + word.text.chars().count() <= max_line_length
+            });
+            if fits {
+                let line = lines.last_mut().expect("checked above");
+                line.push(' ');
+                line.push_str(&word.text);
+            } else if lines.len() < max_lines {
+                lines.push(word.text.clone());
+            } else {
+                cues.push(Cue {
+                    start: cue_start,
+                    end: cue_end,
+                    text: lines.join("\n"),
+                    speaker: segment.speaker.clone(),
+                });
+                lines = vec![word.text.clone()];
+                cue_start = word.start;
+            }
+            cue_end = word.end;
+        }
+        if !lines.is_empty() {
+            cues.push(Cue {
+                start: cue_start,
+                end: segment.end.max(cue_end),
+                text: lines.join("\n"),
+                speaker: segment.speaker.clone(),
+            });
+        }
+    }
+    cues
+}
+
+/// SubRip subtitles. The speaker, if any, prefixes the first line of each cue.
+pub fn to_srt(segments: &[Segment], options: &SubtitleOptions) -> String {
+    let mut srt = String::new();
+    for (i, cue) in split_segments(segments, options).iter().enumerate() {
+        let _ = writeln!(
+            srt,
+            "{}\n{} --> {}",
+            i + 1,
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ',')
+        );
+        if let Some(speaker) = &cue.speaker {
+            let _ = write!(srt, "{}: ", speaker);
+        }
+        let _ = writeln!(srt, "{}\n", cue.text);
+    }
+    srt
+}
+
+/// WebVTT subtitles, speakers are marked with `<v>` voice tags
+pub fn to_vtt(segments: &[Segment], options: &SubtitleOptions) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for cue in split_segments(segments, options) {
+        let _ = writeln!(
+            vtt,
+            "{} --> {}",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.')
+        );
+        if let Some(speaker) = &cue.speaker {
+            let _ = write!(vtt, "<v {}>", escape_vtt(speaker));
+        }
+        let _ = writeln!(vtt, "{}\n", escape_vtt(&cue.text));
+    }
+    vtt
+}
+
+/// RTTM speaker turns, as read by diarization scoring tools. Segments without a
+/// speaker are skipped and whitespace in names is replaced with `_`.
+pub fn to_rttm(segments: &[Segment], file_id: &str) -> String {
+    let mut rttm = String::new();
+    for segment in segments {
+        let Some(speaker) = &segment.speaker else {
+            continue;
+        };
+        let speaker: String = speaker
+            .chars()
+            .map(|c| if c.is_whitespace() { '_' } else { c })
+            .collect();
+        let _ = writeln!(
+            rttm,
+            "SPEAKER {} 1 {:.3} {:.3} <NA> <NA> {} <NA> <NA>",
+            file_id,
+            segment.start,
+            segment.end - segment.start,
+            speaker
+        );
+    }
+    rttm
+}
+
+/// JSON with a stable schema:
+///
+/// ```json
+/// {"segments": [{"start": 0.0, "end": 1.5, "text": "hello there", "speaker": "alice",
+///   "words": [{"start": 0.0, "end": 0.6, "text": "hello"}, ...]}]}
+/// ```
+///
+/// Times are in seconds rounded to milliseconds, and `null` if they're not finite.
+/// `speaker` is `null` when unknown and `words` is empty when the recognizer gave
+/// no timings.
+pub fn to_json(segments: &[Segment]) -> String {
+    let segments: Vec<_> = segments
+        .iter()
+        .map(|segment| {
+            let words: Vec<_> = segment
+                .words
+                .iter()
+                .map(|word| {
+                    serde_json::json!({
+                        "start": seconds(word.start),
+                        "end": seconds(word.end),
+                        "text": word.text,
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "start": seconds(segment.start),
+                "end": seconds(segment.end),
+                "text": segment.text,
+                "speaker": segment.speaker,
+                "words": words,
+            })
+        })
+        .collect();
+    serde_json::json!({ "segments": segments }).to_string()
+}
+
+/// Rounded to milliseconds, serialized as `null` if not finite
+fn seconds(seconds: f32) -> f64 {
+    (seconds as f64 * 1000.0).round() / 1000.0
+}
+
+/// `HH:MM:SS,mmm` for SRT or `HH:MM:SS.mmm` for WebVTT
+fn timestamp(seconds: f32, separator: char) -> String {
+    let millis = (seconds.max(0.0) as f64 * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(segment: Segment, words: &[(f32, f32, &str)]) -> Segment {
+        Segment {
+            words: words
+                .iter()
+                .map(|&(start, end, text)| Word {
+                    start,
+                    end,
+                    text: text.into(),
+                })
+                .collect(),
+            ..segment
+        }
+    }
+
+    fn parse_timestamp(timestamp: &str) -> f32 {
+        let parts: Vec<f32> = timestamp
+            .split([':', ',', '.'])
+            .map(|part| part.parse().unwrap())
+            .collect();
+        parts[0] * 3600.0 + parts[1] * 60.0 + parts[2] + parts[3] / 1000.0
+    }
+
+    /// Cues of an SRT or WebVTT file, with the text lines of each
+    fn parse_cues(subtitles: &str) -> Vec<(f32, f32, String)> {
+        subtitles
+            .split("\n\n")
+            .filter_map(|block| {
+                let mut lines = block.lines().skip_while(|line| !line.contains(" --> "));
+                let (start, end) = lines.next()?.split_once(" --> ")?;
+                let text = lines.collect::<Vec<_>>().join("\n");
+                Some((parse_timestamp(start), parse_timestamp(end), text))
+            })
+            .collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.001, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(3723.4567, ','), "01:02:03,457");
+        assert_eq!(timestamp(59.9996, '.'), "00:01:00.000");
+        assert_eq!(timestamp(-1.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(0.001, '.'), "00:00:00.001");
+    }
+
+    #[test]
+    fn wraps_lines_and_splits_cues() {
+        let text = "the quick brown fox jumps over the lazy dog and keeps on running";
+        let options = SubtitleOptions {
+            max_line_length: 15,
+            max_lines: 2,
+        };
+        let cues = split_segments(&[Segment::new(2.0, 8.0, text)], &options);
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            let lines: Vec<&str> = cue.text.lines().collect();
+            assert!(lines.len() <= 2, "{:?}", cue);
+            assert!(
+                lines.iter().all(|line| line.chars().count() <= 15),
+                "{:?}",
+                cue
+            );
+        }
+        let joined: Vec<&str> = cues
+            .iter()
+            .flat_map(|cue| cue.text.split_whitespace())
+            .collect();
+        assert_eq!(joined.join(" "), text);
+        // Cues follow each other and cover the segment
+        assert_close(cues[0].start, 2.0);
+        assert_close(cues.last().unwrap().end, 8.0);
+        for pair in cues.windows(2) {
+            assert_close(pair[0].end, pair[1].start);
+        }
+    }
+
+    #[test]
+    fn long_words_get_their_own_line() {
+        let options = SubtitleOptions {
+            max_line_length: 5,
+            max_lines: 3,
+        };
+        let cues = split_segments(&[Segment::new(0.0, 1.0, "a extraordinary b")], &options);
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].text, "a\nextraordinary\nb");
+    }
+
+    #[test]
+    fn word_timings_time_the_cues() {
+        let segment = words(
+            Segment::new(1.0, 4.0, "hello big world"),
+            &[(1.0, 1.5, "hello"), (2.0, 2.5, "big"), (3.0, 3.5, "world")],
+        );
+        let options = SubtitleOptions {
+            max_line_length: 5,
+            max_lines: 1,
+        };
+        let cues = split_segments(&[segment], &options);
+        let timings: Vec<_> = cues.iter().map(|cue| (cue.start, cue.end)).collect();
+        assert_eq!(timings, [(1.0, 1.5), (2.0, 2.5), (3.0, 4.0)]);
+    }
+
+    #[test]
+    fn tokens_become_words() {
+        let tokens: Vec<String> = ["▁he", "llo", " world", "▁"].map(String::from).to_vec();
+        let segment =
+            Segment::new(10.0, 12.0, "hello world").with_tokens(&tokens, &[0.0, 0.2, 0.8, 1.5]);
+        let words: Vec<_> = segment
+            .words
+            .iter()
+            .map(|word| (word.text.as_str(), word.start, word.end))
+            .collect();
+        assert_eq!(words, [("hello", 10.0, 10.8), ("world", 10.8, 12.0)]);
+    }
+
+    #[test]
+    fn srt_round_trips() {
+        let segments = [
+            Segment::new(0.0, 1.25, "first line").with_speaker("alice"),
+            Segment::new(3661.5, 3663.0, "second"),
+        ];
+        let options = SubtitleOptions::default();
+        let srt = to_srt(&segments, &options);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:01,250\nalice: first line\n\n2\n"));
+        let cues = parse_cues(&srt);
+        let expected = split_segments(&segments, &options);
+        assert_eq!(cues.len(), expected.len());
+        for ((start, end, text), cue) in cues.iter().zip(&expected) {
+            assert_close(*start, cue.start);
+            assert_close(*end, cue.end);
+            let prefix = cue
+                .speaker
+                .as_ref()
+                .map(|s| format!("{}: ", s))
+                .unwrap_or_default();
+            assert_eq!(*text, prefix + &cue.text);
+        }
+    }
+
+    #[test]
+    fn vtt_round_trips() {
+        let segments = [
+            Segment::new(0.5, 2.0, "a <b> & c").with_speaker("bob"),
+            Segment::new(2.0, 3.0, "plain"),
+        ];
+        let vtt = to_vtt(&segments, &SubtitleOptions::default());
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.500 --> 00:00:02.000\n"));
+        let cues = parse_cues(&vtt);
+        assert_eq!(
+            cues,
+            [
+                (0.5, 2.0, "<v bob>a &lt;b&gt; &amp; c".to_string()),
+                (2.0, 3.0, "plain".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let segments = [
+            words(
+                Segment::new(0.0, 1.2345, "say \"hi\"\n").with_speaker("alice"),
+                &[(0.0, 0.5, "say"), (0.5, 1.2345, "\"hi\"")],
+            ),
+            Segment::new(1.5, 2.0, "tab\there"),
+        ];
+        let json: serde_json::Value = serde_json::from_str(&to_json(&segments)).unwrap();
+        let parsed = json["segments"].as_array().unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0]["text"], "say \"hi\"\n");
+        assert_eq!(parsed[0]["speaker"], "alice");
+        assert_eq!(parsed[0]["end"], 1.235);
+        assert_eq!(parsed[0]["words"][1]["text"], "\"hi\"");
+        assert_eq!(parsed[0]["words"][1]["end"], 1.235);
+        assert_eq!(parsed[1]["start"], 1.5);
+        assert_eq!(parsed[1]["text"], "tab\there");
+        assert!(parsed[1]["speaker"].is_null());
+        assert_eq!(parsed[1]["words"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn json_writes_non_finite_times_as_null() {
+        let segments = [Segment::new(f32::NAN, f32::INFINITY, "broken")];
+        let json: serde_json::Value = serde_json::from_str(&to_json(&segments)).unwrap();
+        assert!(json["segments"][0]["start"].is_null());
+        assert!(json["segments"][0]["end"].is_null());
+    }
+
+    #[test]
+    fn rttm_skips_unknown_speakers() {
+        let segments = [
+            Segment::new(1.0, 2.5, "a").with_speaker("Speaker 1"),
+            Segment::new(3.0, 4.0, "b"),
+        ];
+        assert_eq!(
+            to_rttm(&segments, "meeting"),
+            "SPEAKER meeting 1 1.000 1.500 <NA> <NA> Speaker_1 <NA> <NA>\n"
+        );
+    }
+}
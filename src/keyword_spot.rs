@@ -0,0 +1,298 @@
+//! Keyword spotting with streaming transducer models, such as
+//! `sherpa-onnx-kws-zipformer-gigaspeech-3.3M-2024-01-01`.
+//!
+//! Keywords are given as model tokens, one keyword per line, in the format written
+//! by `sherpa-onnx-cli text2token`, for example `▁HE LL O ▁WORLD :1.5 #0.35 @HELLO_WORLD`.
+
+use crate::{
+    bail, check_file, cstr, cstr_to_string, logging, path_to_cstring, provider,
+    transcribe::online::OnlineModelConfig, Provider, Result,
+};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+};
+
+/// Silence appended after the audio so keywords at the very end are decoded
+const TAIL_PADDING_SECONDS: f32 = 0.66;
+
+#[derive(Debug)]
+pub struct KeywordSpotter {
+    spotter: *const sherpa_rs_sys::SherpaOnnxKeywordSpotter,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeywordSpotterBuilder {
+    model: OnlineModelConfig,
+    tokens: PathBuf,
+    keywords: PathBuf,
+    keywords_score: f32,
+    keywords_threshold: f32,
+    num_trailing_blanks: i32,
+    max_active_paths: i32,
+    providers: Vec<Provider>,
+    num_threads: i32,
+    debug: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeywordDetection {
+    pub keyword: String,
+    /// Start time in seconds of the keyword in the recording
+    pub start: f32,
+    pub tokens: Vec<String>,
+    /// Start time in seconds of each token
+    pub timestamps: Vec<f32>,
+}
+
+impl KeywordSpotterBuilder {
+    pub fn new<P: Into<PathBuf>>(model: OnlineModelConfig, tokens: P, keywords: P) -> Self {
+        Self {
+            model,
+            tokens: tokens.into(),
+            keywords: keywords.into(),
+            keywords_score: 1.0,
+            keywords_threshold: 0.25,
+            num_trailing_blanks: 1,
+            max_active_paths: 4,
+            providers: Vec::new(),
+            num_threads: 1,
+            debug: false,
+        }
+    }
+
+    /// Boost of keyword tokens during decoding, higher finds more keywords
+    pub fn keywords_score(mut self, keywords_score: f32) -> Self {
+        self.keywords_score = keywords_score;
+        self
+    }
+
+    /// Probability a keyword must reach to be reported, between 0 and 1
+    pub fn keywords_threshold(mut self, keywords_threshold: f32) -> Self {
+        self.keywords_threshold = keywords_threshold;
+        self
+    }
+
+    /// Blank frames required after a keyword, raise it for keywords that overlap
+    pub fn num_trailing_blanks(mut self, num_trailing_blanks: i32) -> Self {
+        self.num_trailing_blanks = num_trailing_blanks;
+        self
+    }
+
+    pub fn max_active_paths(mut self, max_active_paths: i32) -> Self {
+        self.max_active_paths = max_active_paths;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn build(self) -> Result<KeywordSpotter> {
+        self.model.check_files()?;
+        check_file(&self.tokens)?;
+        check_file(&self.keywords)?;
+        if !(0.0..=1.0).contains(&self.keywords_threshold) {
+            bail!(
+                InvalidConfig,
+                "keywords_threshold must be between 0 and 1, got {}",
+                self.keywords_threshold
+            )
+        }
+        if self.max_active_paths < 1 {
+            bail!(
+                InvalidConfig,
+                "max_active_paths must be at least 1, got {}",
+                self.max_active_paths
+            )
+        }
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+
+        let keywords_c = path_to_cstring(&self.keywords)?;
+        let model_config = self
+            .model
+            .into_sys(&self.tokens, self.debug, self.num_threads)?;
+        let mut config = sherpa_rs_sys::SherpaOnnxKeywordSpotterConfig {
+            feat_config: sherpa_rs_sys::SherpaOnnxFeatureConfig {
+                sample_rate: 16000,
+                feature_dim: 80,
+            },
+            model_config,
+            max_active_paths: self.max_active_paths,
+            num_trailing_blanks: self.num_trailing_blanks,
+            keywords_score: self.keywords_score,
+            keywords_threshold: self.keywords_threshold,
+            keywords_file: keywords_c.into_raw(),
+            ..unsafe { std::mem::zeroed() }
+        };
+        let spotter = provider::create(&self.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.model_config.provider = provider_c.as_ptr();
+            Ok(logging::load("keyword spotter", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateKeywordSpotter(&config)
+            }))
+        })?;
+        if spotter.is_null() {
+            bail!(
+                ModelLoad,
+                "failed to create keyword spotter, check the keywords file"
+            )
+        }
+        Ok(KeywordSpotter { spotter })
+    }
+}
+
+impl KeywordSpotter {
+    pub fn builder<P: Into<PathBuf>>(
+        model: OnlineModelConfig,
+        tokens: P,
+        keywords: P,
+    ) -> KeywordSpotterBuilder {
+        KeywordSpotterBuilder::new(model, tokens, keywords)
+    }
+
+    /// Detect the transducer of a sherpa-onnx kws release and use its `keywords.txt`
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let (model, tokens) = OnlineModelConfig::detect(dir)?;
+        Self::builder(model, tokens, dir.join("keywords.txt")).build()
+    }
+
+    /// Find the keywords of the keywords file in a recording
+    pub fn spot(&self, sample_rate: i32, samples: &[f32]) -> Result<Vec<KeywordDetection>> {
+        let stream = unsafe { sherpa_rs_sys::SherpaOnnxCreateKeywordStream(self.spotter) };
+        self.decode(stream, sample_rate, samples)
+    }
+
+    /// Find other keywords than those of the keywords file, in the same format
+    /// with keywords separated by `/`
+    pub fn spot_keywords(
+        &self,
+        keywords: &str,
+        sample_rate: i32,
+        samples: &[f32],
+    ) -> Result<Vec<KeywordDetection>> {
+        let keywords_c = cstr!(keywords);
+        let stream = unsafe {
+            sherpa_rs_sys::SherpaOnnxCreateKeywordStreamWithKeywords(
+                self.spotter,
+                keywords_c.as_ptr(),
+            )
+        };
+        if stream.is_null() {
+            bail!(InvalidConfig, "invalid keywords `{}`", keywords)
+        }
+        self.decode(stream, sample_rate, samples)
+    }
+
+    fn decode(
+        &self,
+        stream: *const sherpa_rs_sys::SherpaOnnxOnlineStream,
+        sample_rate: i32,
+        samples: &[f32],
+    ) -> Result<Vec<KeywordDetection>> {
+        let padding = vec![0.0; (TAIL_PADDING_SECONDS * sample_rate as f32) as usize];
+        let detections = logging::infer("keyword spotter", || unsafe {
+            for samples in [samples, &padding] {
+                // The C API counts samples with an i32, a stream can take them in parts
+                for part in samples.chunks(i32::MAX as usize) {
+                    sherpa_rs_sys::SherpaOnnxOnlineStreamAcceptWaveform(
+                        stream,
+                        sample_rate,
+                        part.as_ptr(),
+                        part.len() as i32,
+                    );
+                }
+            }
+            sherpa_rs_sys::SherpaOnnxOnlineStreamInputFinished(stream);
+
+            let mut detections = Vec::new();
+            while sherpa_rs_sys::SherpaOnnxIsKeywordStreamReady(self.spotter, stream) == 1 {
+                sherpa_rs_sys::SherpaOnnxDecodeKeywordStream(self.spotter, stream);
+                let result_ptr = sherpa_rs_sys::SherpaOnnxGetKeywordResult(self.spotter, stream);
+                let detection = Self::detection(&result_ptr.read());
+                // Free
+                sherpa_rs_sys::SherpaOnnxDestroyKeywordResult(result_ptr);
+                if let Some(detection) = detection? {
+                    detections.push(detection);
+                    // Start over so the same keyword isn't reported twice
+                    sherpa_rs_sys::SherpaOnnxResetKeywordStream(self.spotter, stream);
+                }
+            }
+            Ok(detections)
+        });
+        unsafe { sherpa_rs_sys::SherpaOnnxDestroyOnlineStream(stream) };
+        detections
+    }
+
+    /// The keyword of a decoding result, `None` if it found none
+    unsafe fn detection(
+        result: &sherpa_rs_sys::SherpaOnnxKeywordResult,
+    ) -> Result<Option<KeywordDetection>> {
+        let keyword = cstr_to_string!(result.keyword)?;
+        if keyword.is_empty() {
+            return Ok(None);
+        }
+        let count = result.count.max(0) as usize;
+        let tokens = if result.tokens_arr.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(result.tokens_arr, count)
+                .iter()
+                .map(|&token| cstr_to_string!(token))
+                .collect::<std::result::Result<_, _>>()?
+        };
+        // Token times are relative to the start of the decoded chunk
+        let timestamps: Vec<f32> = if result.timestamps.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(result.timestamps, count)
+                .iter()
+                .map(|timestamp| result.start_time + timestamp)
+                .collect()
+        };
+        Ok(Some(KeywordDetection {
+            keyword,
+            start: timestamps.first().copied().unwrap_or(result.start_time),
+            tokens,
+            timestamps,
+        }))
+    }
+}
+
+unsafe impl Send for KeywordSpotter {}
+// Like `OnlineRecognizer`, each call decodes its own stream
+unsafe impl Sync for KeywordSpotter {}
+
+impl Drop for KeywordSpotter {
+    fn drop(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxDestroyKeywordSpotter(self.spotter);
+        }
+    }
+}
@@ -1,43 +1,203 @@
-use crate::get_default_provider;
-use eyre::{bail, Result};
-use std::ffi::{CStr, CString};
+use crate::{
+    bail, check_file, cstr, logging,
+    model_dir::ModelDir,
+    path_to_cstring,
+    provider::{self, parse_provider, Provider},
+    sample_count, Result,
+};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+};
+
+/// Length of the windows voted on by `compute_top_k`
+const WINDOW_SECONDS: f32 = 5.0;
+
+/// Windows shorter than this are merged into the previous one
+const MIN_WINDOW_SECONDS: f32 = 1.0;
+
+/// Language voted for by windows of the audio, see `SpokenLanguageId::compute_top_k`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageVote {
+    pub lang: String,
+    /// Number of windows that voted for the language.
+    ///
+    /// sherpa-onnx doesn't report scores, so there is no confidence: a clip
+    /// shorter than one window always gives a single vote.
+    pub windows: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentLanguage {
+    /// Start in seconds
+    pub start: f32,
+    /// End in seconds
+    pub end: f32,
+    pub lang: String,
+}
 
 #[derive(Debug)]
 pub struct SpokenLanguageId {
     slid: *const sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentification,
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpokenLanguageIdBuilder {
+    encoder: PathBuf,
+    decoder: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    tail_paddings: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+}
+
+fn default_num_threads() -> i32 {
+    2
+}
+
+impl SpokenLanguageIdBuilder {
+    pub fn new<P: Into<PathBuf>>(encoder: P, decoder: P) -> Self {
+        Self {
+            encoder: encoder.into(),
+            decoder: decoder.into(),
+            tail_paddings: 0,
+            debug: false,
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+        }
+    }
+
+    /// Number of padding frames appended to the input, 0 uses the model default
+    pub fn tail_paddings(mut self, tail_paddings: i32) -> Self {
+        self.tail_paddings = tail_paddings;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn build(self) -> Result<SpokenLanguageId> {
+        check_file(&self.encoder)?;
+        check_file(&self.decoder)?;
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        if self.tail_paddings < 0 {
+            bail!(
+                InvalidConfig,
+                "tail_paddings can't be negative, got {}",
+                self.tail_paddings
+            )
+        }
+        SpokenLanguageId::from_builder(self)
+    }
+}
+
 impl SpokenLanguageId {
+    pub fn builder<P: Into<PathBuf>>(encoder: P, decoder: P) -> SpokenLanguageIdBuilder {
+        SpokenLanguageIdBuilder::new(encoder, decoder)
+    }
+
+    /// Use the encoder and decoder of a sherpa-onnx whisper release, such as
+    /// `sherpa-onnx-whisper-tiny`. Multilingual models only, not `*.en`.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref();
+        let dir = ModelDir::open(path)?;
+        let (Some(encoder), Some(decoder)) = (dir.onnx("encoder")?, dir.onnx("decoder")?) else {
+            bail!(
+                ModelLoad,
+                "no whisper encoder and decoder in {}. {}",
+                path.display(),
+                dir.found()
+            )
+        };
+        Self::builder(encoder, decoder).build()
+    }
+
+    #[deprecated(note = "use `SpokenLanguageId::builder` instead")]
     pub fn new(
         encoder: String,
         decoder: String,
         debug: Option<bool>,
         provider: Option<String>,
         num_threads: Option<i32>,
-    ) -> Self {
-        let provider = provider.unwrap_or(get_default_provider());
-        let provider_c = CString::new(provider).unwrap();
-        let debug = debug.unwrap_or_default();
-        let debug = if debug { 0 } else { 1 };
-
-        let encoder_c = CString::new(encoder).unwrap();
-        let decoder_c = CString::new(decoder).unwrap();
+    ) -> Result<Self> {
+        let mut builder = Self::builder(encoder, decoder)
+            .debug(debug.unwrap_or_default())
+            .num_threads(num_threads.unwrap_or(default_num_threads()));
+        builder.providers = parse_provider(provider).into_iter().collect();
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: SpokenLanguageIdBuilder) -> Result<Self> {
+        let debug = if builder.debug { 1 } else { 0 };
+
+        let encoder_c = path_to_cstring(&builder.encoder)?;
+        let decoder_c = path_to_cstring(&builder.decoder)?;
         let whisper = sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationWhisperConfig {
             decoder: decoder_c.into_raw(),
             encoder: encoder_c.into_raw(),
-            tail_paddings: 0,
+            tail_paddings: builder.tail_paddings,
         };
-        let config = sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationConfig {
+        let mut config = sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationConfig {
             debug,
-            num_threads: num_threads.unwrap_or(2),
-            provider: provider_c.into_raw(),
+            num_threads: builder.num_threads,
+            provider: std::ptr::null(),
             whisper,
         };
-        let slid = unsafe { sherpa_rs_sys::SherpaOnnxCreateSpokenLanguageIdentification(&config) };
-        Self { slid }
+        let slid = provider::create(&builder.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.provider = provider_c.as_ptr();
+            Ok(logging::load("spoken language identification", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateSpokenLanguageIdentification(&config)
+            }))
+        })?;
+        if slid.is_null() {
+            bail!(ModelLoad, "failed to create spoken language identification")
+        }
+        Ok(Self { slid })
     }
 
-    pub fn compute(&mut self, samples: Vec<f32>, sample_rate: i32) -> Result<String> {
+    pub fn compute(&mut self, samples: &[f32], sample_rate: i32) -> Result<String> {
+        check_sample_rate(sample_rate)?;
+        let count = sample_count(samples)?;
         unsafe {
             let stream =
                 sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationCreateOfflineStream(self.slid);
@@ -45,23 +205,99 @@ impl SpokenLanguageId {
                 stream,
                 sample_rate,
                 samples.as_ptr(),
-                samples.len().try_into().unwrap(),
+                count,
             );
-            let language_result_ptr =
-                sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationCompute(self.slid, stream);
+            let language_result_ptr = logging::infer("spoken language identification", || {
+                sherpa_rs_sys::SherpaOnnxSpokenLanguageIdentificationCompute(self.slid, stream)
+            });
             if language_result_ptr.is_null() || (*language_result_ptr).lang.is_null() {
-                bail!("language ptr is null")
+                sherpa_rs_sys::SherpaOnnxDestroyOfflineStream(stream);
+                bail!(Native, "language ptr is null")
             }
             let language_ptr = (*language_result_ptr).lang;
-            let c_language = CStr::from_ptr(language_ptr);
-            let language = c_language.to_str().unwrap().to_string();
+            let language = CStr::from_ptr(language_ptr).to_str().map(str::to_string);
             // Free
             sherpa_rs_sys::SherpaOnnxDestroySpokenLanguageIdentificationResult(language_result_ptr);
             sherpa_rs_sys::SherpaOnnxDestroyOfflineStream(stream);
 
-            Ok(language)
+            Ok(language?)
+        }
+    }
+
+    /// Top `k` languages by the number of windows that voted for them.
+    ///
+    /// sherpa-onnx only reports the most likely language, not probabilities, so
+    /// the audio is split into windows of a few seconds and each window votes for
+    /// its language. Windows are 5 seconds long, so this needs longer audio to
+    /// tell more than one language apart.
+    pub fn compute_top_k(
+        &mut self,
+        samples: &[f32],
+        sample_rate: i32,
+        k: usize,
+    ) -> Result<Vec<LanguageVote>> {
+        check_sample_rate(sample_rate)?;
+        let window = (WINDOW_SECONDS * sample_rate as f32) as usize;
+        let min_window = (MIN_WINDOW_SECONDS * sample_rate as f32) as usize;
+        let mut windows: Vec<&[f32]> = samples.chunks(window.max(1)).collect();
+        if windows.len() > 1 && windows.last().is_some_and(|last| last.len() < min_window) {
+            // Too short to vote on its own
+            let start = (windows.len() - 2) * window;
+            windows.truncate(windows.len() - 2);
+            windows.push(&samples[start..]);
         }
+
+        let votes = windows
+            .into_iter()
+            .map(|window| self.compute(window, sample_rate))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(tally(votes, k))
+    }
+
+    /// Language of each VAD segment, for recordings that switch language
+    #[cfg(feature = "vad")]
+    pub fn compute_segments(
+        &mut self,
+        segments: &[crate::vad::SpeechSegment],
+        sample_rate: i32,
+    ) -> Result<Vec<SegmentLanguage>> {
+        check_sample_rate(sample_rate)?;
+        segments
+            .iter()
+            .map(|segment| {
+                let start = segment.start as f32 / sample_rate as f32;
+                let end = start + segment.samples.len() as f32 / sample_rate as f32;
+                let lang = self.compute(&segment.samples, sample_rate)?;
+                Ok(SegmentLanguage { start, end, lang })
+            })
+            .collect()
+    }
+}
+
+fn check_sample_rate(sample_rate: i32) -> Result<()> {
+    if sample_rate <= 0 {
+        bail!(
+            InvalidConfig,
+            "sample_rate must be positive, got {}",
+            sample_rate
+        )
     }
+    Ok(())
+}
+
+/// Count the votes of each window, most voted first
+fn tally(votes: Vec<String>, k: usize) -> Vec<LanguageVote> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for lang in votes {
+        *counts.entry(lang).or_default() += 1;
+    }
+    let mut languages: Vec<LanguageVote> = counts
+        .into_iter()
+        .map(|(lang, windows)| LanguageVote { lang, windows })
+        .collect();
+    languages.sort_by(|a, b| b.windows.cmp(&a.windows).then(a.lang.cmp(&b.lang)));
+    languages.truncate(k);
+    languages
 }
 
 unsafe impl Send for SpokenLanguageId {}
@@ -74,3 +310,49 @@ impl Drop for SpokenLanguageId {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn votes(langs: &[&str]) -> Vec<String> {
+        langs.iter().map(|lang| lang.to_string()).collect()
+    }
+
+    #[test]
+    fn single_window_gives_one_vote() {
+        let languages = tally(votes(&["en"]), 3);
+        assert_eq!(
+            languages,
+            [LanguageVote {
+                lang: "en".into(),
+                windows: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn sorts_by_votes_then_name() {
+        let languages = tally(votes(&["fr", "en", "de", "en", "fr", "en"]), 2);
+        let langs: Vec<_> = languages
+            .iter()
+            .map(|l| (l.lang.as_str(), l.windows))
+            .collect();
+        assert_eq!(langs, [("en", 3), ("fr", 2)]);
+    }
+
+    #[test]
+    fn rejects_non_positive_sample_rates() {
+        assert!(matches!(
+            check_sample_rate(0),
+            Err(crate::Error::InvalidConfig(_))
+        ));
+        assert!(check_sample_rate(-16000).is_err());
+        assert!(check_sample_rate(16000).is_ok());
+    }
+
+    #[test]
+    fn no_windows() {
+        assert!(tally(Vec::new(), 3).is_empty());
+    }
+}
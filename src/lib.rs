@@ -1,33 +1,161 @@
-pub mod embedding_manager;
-pub mod language_id;
-pub mod speaker_id;
+pub mod audio;
+pub mod bench;
+pub mod engine;
+mod error;
+pub mod eval;
+pub mod formats;
+
+#[cfg(feature = "asr")]
 pub mod transcribe;
+
+#[cfg(feature = "vad")]
 pub mod vad;
 
+#[cfg(feature = "speaker")]
+pub mod embedding_manager;
+#[cfg(feature = "speaker")]
+pub mod speaker_id;
+
+#[cfg(feature = "slid")]
+pub mod language_id;
+
+#[cfg(feature = "kws")]
+pub mod keyword_spot;
+
 #[cfg(feature = "tts")]
 pub mod tts;
 
+#[cfg(feature = "serde")]
+pub mod manifest;
+
+#[cfg(feature = "async")]
+pub mod nonblocking;
+
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[cfg(feature = "audio-io")]
+pub mod audio_io;
+
+// Each engine uses a part of it
+#[cfg(any(
+    feature = "asr",
+    feature = "vad",
+    feature = "speaker",
+    feature = "slid",
+    feature = "tts"
+))]
+pub mod logging;
+#[cfg(any(feature = "asr", feature = "slid", feature = "tts"))]
+#[allow(dead_code)]
+mod model_dir;
+pub mod provider;
+
+pub use engine::{LanguageIdentifier, Recognizer, SpeakerEmbedder};
+pub use error::{Error, Result};
+pub use provider::Provider;
+
+#[cfg(feature = "vad")]
+pub use engine::VoiceActivityDetector;
+
+#[cfg(feature = "tts")]
+pub use engine::SpeechSynthesizer;
+
+use std::{ffi::CString, path::Path};
+
+/// Return early with an `Error` of the given kind, the message is formatted like `format!`
+macro_rules! bail {
+    ($kind:ident, $($arg:tt)*) => {
+        return Err($crate::Error::$kind(format!($($arg)*)))
+    };
+}
+pub(crate) use bail;
+
+#[deprecated(note = "use `Provider::default` instead")]
 pub fn get_default_provider() -> String {
-    if cfg!(feature = "cuda") {
-        "cuda"
-    } else if cfg!(target_os = "macos") {
-        "coreml"
-    } else {
-        "cpu"
+    Provider::default().to_string()
+}
+
+// The helpers below are unused when every engine feature is off
+#[allow(dead_code)]
+pub(crate) fn check_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        bail!(ModelLoad, "model not found at {}", path.display())
     }
-    .into()
+    Ok(())
+}
+
+/// Paths are passed to sherpa-onnx as bytes on Unix, and must be UTF-8 elsewhere
+#[allow(dead_code)]
+pub(crate) fn path_to_cstring(path: &Path) -> Result<CString> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str());
+    #[cfg(not(unix))]
+    let bytes = std::str::from_utf8(path.as_os_str().as_encoded_bytes())?.as_bytes();
+    Ok(CString::new(bytes)?)
 }
 
+/// Number of samples as the C API takes it
+#[allow(dead_code)]
+pub(crate) fn sample_count(samples: &[f32]) -> Result<i32> {
+    match samples.len().try_into() {
+        Ok(count) => Ok(count),
+        Err(_) => bail!(
+            InvalidAudio,
+            "{} samples is more than sherpa-onnx takes at once",
+            samples.len()
+        ),
+    }
+}
+
+/// `CString` for the C API, returns `Error::Nul` from the enclosing function if
+/// the string contains a NUL byte
 #[macro_export]
 macro_rules! cstr {
     ($s:expr) => {
-        CString::new($s).expect("Failed to create CString")
+        CString::new($s)?
     };
 }
 
+/// `String` from a C string of the C API, `Err(Utf8Error)` if it isn't UTF-8
 #[macro_export]
 macro_rules! cstr_to_string {
     ($ptr:expr) => {
-        std::ffi::CStr::from_ptr($ptr).to_string_lossy().to_string()
+        std::ffi::CStr::from_ptr($ptr)
+            .to_str()
+            .map(|s| s.to_string())
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_paths() {
+        let path = path_to_cstring(Path::new("models/tokens.txt")).unwrap();
+        assert_eq!(path.as_bytes(), b"models/tokens.txt");
+        assert!(matches!(
+            path_to_cstring(Path::new("a\0b")),
+            Err(Error::Nul(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_non_utf8_paths_on_unix() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9.onnx"));
+        assert_eq!(path_to_cstring(path).unwrap().as_bytes(), b"caf\xe9.onnx");
+    }
+
+    #[test]
+    fn rejects_invalid_utf8_from_c() {
+        let text = CString::new("héllo").unwrap();
+        let invalid = CString::new(b"caf\xe9".to_vec()).unwrap();
+        unsafe {
+            assert_eq!(cstr_to_string!(text.as_ptr()).unwrap(), "héllo");
+            assert!(cstr_to_string!(invalid.as_ptr()).is_err());
+        }
+    }
+}
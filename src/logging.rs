@@ -0,0 +1,261 @@
+//! Native logs and timing of model load and inference.
+//!
+//! Events go through `tracing`, and through `log` when no tracing subscriber is set.
+//!
+//! The C API has no log callback, sherpa-onnx and onnxruntime print to stderr.
+//! `capture_native_stderr(true)` logs that output under the `sherpa_onnx` target
+//! instead, see its docs for what it costs.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+use tracing::Level;
+
+static CAPTURE_NATIVE_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Log what sherpa-onnx and onnxruntime print while a model loads, which is when
+/// they print the config (with `debug` on), warnings and load errors. Off by
+/// default, and only supported on Unix.
+///
+/// This affects the whole process: while a model loads, file descriptor 2 is
+/// redirected into a pipe, so whatever any thread or library writes to stderr
+/// meanwhile is logged instead of printed, and model loads are serialized.
+/// Leave it off when something else also redirects stderr.
+pub fn capture_native_stderr(enabled: bool) {
+    CAPTURE_NATIVE_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `capture_native_stderr` is on
+pub fn captures_native_stderr() -> bool {
+    CAPTURE_NATIVE_STDERR.load(Ordering::Relaxed)
+}
+
+/// Handle returned by a `SherpaOnnxCreate*` function
+pub(crate) trait NativeHandle {
+    fn is_null(&self) -> bool;
+}
+
+impl<T> NativeHandle for *const T {
+    fn is_null(&self) -> bool {
+        <*const T>::is_null(*self)
+    }
+}
+
+impl<T> NativeHandle for *mut T {
+    fn is_null(&self) -> bool {
+        <*mut T>::is_null(*self)
+    }
+}
+
+/// Create a native engine in a `load` span, logging its output and how long
+/// it took. Output of a failed load is logged as errors.
+pub(crate) fn load<H: NativeHandle>(engine: &'static str, create: impl FnOnce() -> H) -> H {
+    let span = tracing::debug_span!("load", engine);
+    let _entered = span.enter();
+    let start = Instant::now();
+    let (handle, output) = if captures_native_stderr() {
+        capture_stderr(create)
+    } else {
+        (create(), String::new())
+    };
+    let elapsed_ms = elapsed_ms(start);
+    let failed = handle.is_null();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let level = if failed {
+            Level::ERROR
+        } else {
+            native_level(line)
+        };
+        native_event(level, line);
+    }
+    tracing::debug!(elapsed_ms, failed, "loaded {}", engine);
+    handle
+}
+
+/// Run inference in an `inference` span, logging how long it took
+pub(crate) fn infer<T>(engine: &'static str, run: impl FnOnce() -> T) -> T {
+    let span = tracing::debug_span!("inference", engine);
+    let _entered = span.enter();
+    let start = Instant::now();
+    let result = run();
+    tracing::debug!(elapsed_ms = elapsed_ms(start), "{} inference done", engine);
+    result
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// onnxruntime tags lines with its severity, e.g. `[W:onnxruntime:, ...]`.
+/// Everything else is sherpa-onnx, which logs errors and debug output alike.
+fn native_level(line: &str) -> Level {
+    let Some(tag) = line
+        .find(":onnxruntime:")
+        .and_then(|end| line[..end].chars().last())
+    else {
+        return Level::DEBUG;
+    };
+    match tag {
+        'V' => Level::TRACE,
+        'I' => Level::INFO,
+        'W' => Level::WARN,
+        'E' | 'F' => Level::ERROR,
+        _ => Level::DEBUG,
+    }
+}
+
+// The tracing macros take the level as a constant
+fn native_event(level: Level, line: &str) {
+    match level {
+        Level::TRACE => tracing::trace!(target: "sherpa_onnx", "{}", line),
+        Level::DEBUG => tracing::debug!(target: "sherpa_onnx", "{}", line),
+        Level::INFO => tracing::info!(target: "sherpa_onnx", "{}", line),
+        Level::WARN => tracing::warn!(target: "sherpa_onnx", "{}", line),
+        Level::ERROR => tracing::error!(target: "sherpa_onnx", "{}", line),
+    }
+}
+
+#[cfg(unix)]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> (T, String) {
+    use std::sync::{Mutex, PoisonError};
+
+    // Loads on several threads would restore each other's stderr
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    match redirect::Redirect::start() {
+        Some(redirect) => {
+            let result = f();
+            (result, redirect.finish())
+        }
+        None => (f(), String::new()),
+    }
+}
+
+#[cfg(not(unix))]
+fn capture_stderr<T>(f: impl FnOnce() -> T) -> (T, String) {
+    (f(), String::new())
+}
+
+#[cfg(unix)]
+mod redirect {
+    use std::{
+        fs::File,
+        io::Read,
+        os::fd::{AsRawFd, FromRawFd, OwnedFd},
+        thread::JoinHandle,
+    };
+
+    /// Stderr sent into a pipe until finished or dropped
+    pub(super) struct Redirect {
+        saved: OwnedFd,
+        reader: Option<JoinHandle<Vec<u8>>>,
+    }
+
+    impl Redirect {
+        pub(super) fn start() -> Option<Self> {
+            let mut fds = [0; 2];
+            unsafe {
+                if libc::pipe(fds.as_mut_ptr()) != 0 {
+                    return None;
+                }
+                let read = File::from(OwnedFd::from_raw_fd(fds[0]));
+                let write = OwnedFd::from_raw_fd(fds[1]);
+                let saved = libc::dup(libc::STDERR_FILENO);
+                if saved < 0 {
+                    return None;
+                }
+                let saved = OwnedFd::from_raw_fd(saved);
+                // Drain the pipe while the model loads, a full pipe would block it
+                let reader = std::thread::Builder::new()
+                    .name("sherpa-stderr".into())
+                    .spawn(move || {
+                        let mut read = read;
+                        let mut output = Vec::new();
+                        let _ = read.read_to_end(&mut output);
+                        output
+                    })
+                    .ok()?;
+                if libc::dup2(write.as_raw_fd(), libc::STDERR_FILENO) < 0 {
+                    // Closing `write` ends the reader
+                    return None;
+                }
+                Some(Self {
+                    saved,
+                    reader: Some(reader),
+                })
+            }
+        }
+
+        /// Restore stderr and return what was written meanwhile
+        pub(super) fn finish(mut self) -> String {
+            self.restore();
+            let output = self
+                .reader
+                .take()
+                .and_then(|reader| reader.join().ok())
+                .unwrap_or_default();
+            String::from_utf8_lossy(&output).into_owned()
+        }
+
+        // Replacing stderr closes the last write end of the pipe, so the reader
+        // sees the end of it
+        fn restore(&self) {
+            unsafe {
+                libc::dup2(self.saved.as_raw_fd(), libc::STDERR_FILENO);
+            }
+        }
+    }
+
+    impl Drop for Redirect {
+        fn drop(&mut self) {
+            if self.reader.is_some() {
+                self.restore();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onnxruntime_severity_sets_the_level() {
+        let line = |severity| {
+            format!(
+                "2024-01-01 00:00:00.0 [{}:onnxruntime:, session_state.cc:1162] message",
+                severity
+            )
+        };
+        assert_eq!(native_level(&line('V')), Level::TRACE);
+        assert_eq!(native_level(&line('I')), Level::INFO);
+        assert_eq!(native_level(&line('W')), Level::WARN);
+        assert_eq!(native_level(&line('E')), Level::ERROR);
+        assert_eq!(native_level(&line('F')), Level::ERROR);
+        assert_eq!(native_level(&line('X')), Level::DEBUG);
+    }
+
+    #[test]
+    fn other_lines_are_debug() {
+        assert_eq!(
+            native_level("OfflineTtsConfig(model=OfflineTtsModelConfig(...))"),
+            Level::DEBUG
+        );
+        assert_eq!(native_level(":onnxruntime: at the start"), Level::DEBUG);
+        assert_eq!(native_level(""), Level::DEBUG);
+    }
+
+    #[test]
+    fn capture_is_off_by_default() {
+        assert!(!captures_native_stderr());
+    }
+
+    #[test]
+    fn load_returns_the_handle() {
+        let value = 1;
+        let handle = load("test", || &value as *const i32);
+        assert_eq!(handle, &value as *const i32);
+        assert!(load("test", std::ptr::null::<i32>).is_null());
+    }
+}
@@ -0,0 +1,486 @@
+//! Model directory manifests.
+//!
+//! A `manifest.toml` (or `manifest.json`) next to the model files describes what
+//! the directory contains, so engines can be created without hard-coding file names.
+//! Paths in `files` are relative to the manifest.
+//!
+//! ```toml
+//! type = "whisper"
+//! sample_rate = 16000
+//! languages = ["en"]
+//!
+//! [files]
+//! encoder = "tiny-encoder.int8.onnx"
+//! decoder = "tiny-decoder.int8.onnx"
+//! tokens = "tiny-tokens.txt"
+//! ```
+
+#[cfg(feature = "slid")]
+use crate::language_id::{SpokenLanguageId, SpokenLanguageIdBuilder};
+#[cfg(feature = "speaker")]
+use crate::speaker_id::{ExtractorConfig, ExtractorConfigBuilder};
+#[cfg(feature = "asr")]
+use crate::transcribe::{
+    offline::OfflineModelConfig,
+    whisper::{WhisperRecognizer, WhisperRecognizerBuilder},
+};
+#[cfg(feature = "vad")]
+use crate::vad::{VadConfig, VadConfigBuilder};
+use crate::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelType {
+    /// Whisper recognizer
+    Whisper,
+    Transducer,
+    Paraformer,
+    NemoCtc,
+    SenseVoice,
+    /// Silero voice activity detection
+    SileroVad,
+    SpeakerEmbedding,
+    /// Whisper encoder and decoder used for spoken language identification
+    LanguageId,
+    Vits,
+    Matcha,
+    Kokoro,
+}
+
+impl ModelType {
+    /// Keys that must be present in `files`
+    pub fn required_files(&self) -> &'static [&'static str] {
+        match self {
+            Self::Whisper => &["encoder", "decoder", "tokens"],
+            Self::Transducer => &["encoder", "decoder", "joiner", "tokens"],
+            Self::Paraformer | Self::NemoCtc | Self::SenseVoice => &["model", "tokens"],
+            Self::SileroVad | Self::SpeakerEmbedding => &["model"],
+            Self::LanguageId => &["encoder", "decoder"],
+            Self::Vits => &["model", "tokens"],
+            Self::Matcha => &["acoustic_model", "vocoder", "tokens"],
+            Self::Kokoro => &["model", "voices", "tokens"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelManifest {
+    #[serde(rename = "type")]
+    pub model_type: ModelType,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Sample rate the model expects
+    #[serde(default)]
+    pub sample_rate: Option<i32>,
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Model files by role, such as `encoder` or `tokens`
+    pub files: BTreeMap<String, PathBuf>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl ModelManifest {
+    /// Read a TOML manifest, or JSON if the extension is `.json`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mut manifest: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            _ => toml::from_str(&content)?,
+        };
+        manifest.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        for key in manifest.model_type.required_files() {
+            if !manifest.files.contains_key(*key) {
+                bail!(
+                    InvalidConfig,
+                    "{} is missing file `{}` required by {:?} models",
+                    path.display(),
+                    key,
+                    manifest.model_type
+                )
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Directory the manifest was loaded from
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the file with the given role
+    pub fn file(&self, key: &str) -> Result<PathBuf> {
+        match self.optional_file(key) {
+            Some(path) => Ok(path),
+            None => bail!(InvalidConfig, "manifest has no file `{}`", key),
+        }
+    }
+
+    pub fn optional_file(&self, key: &str) -> Option<PathBuf> {
+        self.files.get(key).map(|path| self.dir.join(path))
+    }
+
+    #[cfg(feature = "vad")]
+    pub fn vad(&self) -> Result<VadConfigBuilder> {
+        self.expect_type(&[ModelType::SileroVad])?;
+        let mut builder = VadConfig::builder(self.file("model")?);
+        if let Some(sample_rate) = self.sample_rate {
+            builder = builder.sample_rate(sample_rate);
+        }
+        Ok(builder)
+    }
+
+    #[cfg(feature = "asr")]
+    pub fn whisper(&self) -> Result<WhisperRecognizerBuilder> {
+        self.expect_type(&[ModelType::Whisper])?;
+        let mut builder = WhisperRecognizer::builder(
+            self.file("encoder")?,
+            self.file("decoder")?,
+            self.file("tokens")?,
+        );
+        // Multilingual models detect the language themselves
+        if let [language] = self.languages.as_slice() {
+            builder = builder.language(language.as_str());
+        }
+        if let Some(bpe_vocab) = self.optional_file("bpe_vocab") {
+            builder = builder.bpe_vocab(bpe_vocab);
+        }
+        Ok(builder)
+    }
+
+    /// Model config for `OfflineRecognizer`, use `file("tokens")` for its tokens
+    #[cfg(feature = "asr")]
+    pub fn offline_model(&self) -> Result<OfflineModelConfig> {
+        let path = |key: &str| self.file(key);
+        Ok(match self.model_type {
+            ModelType::Transducer => OfflineModelConfig::Transducer {
+                encoder: path("encoder")?,
+                decoder: path("decoder")?,
+                joiner: path("joiner")?,
+            },
+            ModelType::Paraformer => OfflineModelConfig::Paraformer {
+                model: path("model")?,
+            },
+            ModelType::NemoCtc => OfflineModelConfig::NemoCtc {
+                model: path("model")?,
+            },
+            ModelType::SenseVoice => OfflineModelConfig::SenseVoice {
+                model: path("model")?,
+                language: match self.languages.as_slice() {
+                    [language] => language.clone(),
+                    _ => "auto".into(),
+                },
+                use_itn: true,
+            },
+            other => bail!(
+                InvalidConfig,
+                "{:?} is not an offline recognizer model",
+                other
+            ),
+        })
+    }
+
+    #[cfg(feature = "speaker")]
+    pub fn extractor(&self) -> Result<ExtractorConfigBuilder> {
+        self.expect_type(&[ModelType::SpeakerEmbedding])?;
+        Ok(ExtractorConfig::builder(self.file("model")?))
+    }
+
+    #[cfg(feature = "slid")]
+    pub fn language_id(&self) -> Result<SpokenLanguageIdBuilder> {
+        // Any multilingual whisper model can identify languages
+        self.expect_type(&[ModelType::LanguageId, ModelType::Whisper])?;
+        Ok(SpokenLanguageId::builder(
+            self.file("encoder")?,
+            self.file("decoder")?,
+        ))
+    }
+
+    #[cfg(feature = "tts")]
+    pub fn tts(&self) -> Result<crate::tts::TtsModelConfigBuilder> {
+        use crate::tts::{TtsKokoroModelConfig, TtsMatchaModelConfig, TtsVitsModelConfig};
+
+        Ok(match self.model_type {
+            ModelType::Vits => {
+                let mut builder =
+                    TtsVitsModelConfig::builder(self.file("model")?, self.file("tokens")?);
+                if let Some(lexicon) = self.optional_file("lexicon") {
+                    builder = builder.lexicon(lexicon);
+                }
+                if let Some(data_dir) = self.optional_file("data_dir") {
+                    builder = builder.data_dir(data_dir);
+                }
+                if let Some(dict_dir) = self.optional_file("dict_dir") {
+                    builder = builder.dict_dir(dict_dir);
+                }
+                builder.into()
+            }
+            ModelType::Matcha => {
+                let mut builder = TtsMatchaModelConfig::builder(
+                    self.file("acoustic_model")?,
+                    self.file("vocoder")?,
+                    self.file("tokens")?,
+                );
+                if let Some(lexicon) = self.optional_file("lexicon") {
+                    builder = builder.lexicon(lexicon);
+                }
+                if let Some(data_dir) = self.optional_file("data_dir") {
+                    builder = builder.data_dir(data_dir);
+                }
+                if let Some(dict_dir) = self.optional_file("dict_dir") {
+                    builder = builder.dict_dir(dict_dir);
+                }
+                builder.into()
+            }
+            ModelType::Kokoro => {
+                let mut builder = TtsKokoroModelConfig::builder(
+                    self.file("model")?,
+                    self.file("voices")?,
+                    self.file("tokens")?,
+                );
+                if let Some(data_dir) = self.optional_file("data_dir") {
+                    builder = builder.data_dir(data_dir);
+                }
+                if let Some(dict_dir) = self.optional_file("dict_dir") {
+                    builder = builder.dict_dir(dict_dir);
+                }
+                if let Some(lexicon) = self.optional_file("lexicon") {
+                    builder = builder.lexicon(lexicon);
+                }
+                builder.into()
+            }
+            other => bail!(InvalidConfig, "{:?} is not a text to speech model", other),
+        })
+    }
+
+    #[cfg(any(
+        feature = "asr",
+        feature = "vad",
+        feature = "speaker",
+        feature = "slid"
+    ))]
+    fn expect_type(&self, expected: &[ModelType]) -> Result<()> {
+        if !expected.contains(&self.model_type) {
+            bail!(
+                InvalidConfig,
+                "expected a {:?} model but the manifest describes {:?}",
+                expected[0],
+                self.model_type
+            )
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary directory with a manifest, removed on drop
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str, manifest_name: &str, manifest: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "sherpa-rs-manifest-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(manifest_name), manifest).unwrap();
+            Self(dir)
+        }
+
+        fn load(&self, manifest_name: &str) -> Result<ModelManifest> {
+            ModelManifest::load(self.0.join(manifest_name))
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const WHISPER: &str = r#"
+type = "whisper"
+name = "tiny"
+sample_rate = 16000
+languages = ["en"]
+
+[files]
+encoder = "tiny-encoder.int8.onnx"
+decoder = "tiny-decoder.int8.onnx"
+tokens = "tiny-tokens.txt"
+"#;
+
+    #[test]
+    fn loads_toml() {
+        let fixture = Fixture::new("toml", "manifest.toml", WHISPER);
+        let manifest = fixture.load("manifest.toml").unwrap();
+        assert_eq!(manifest.model_type, ModelType::Whisper);
+        assert_eq!(manifest.name.as_deref(), Some("tiny"));
+        assert_eq!(manifest.sample_rate, Some(16000));
+        assert_eq!(manifest.languages, ["en"]);
+        assert_eq!(manifest.dir(), fixture.0);
+        assert_eq!(
+            manifest.file("tokens").unwrap(),
+            fixture.0.join("tiny-tokens.txt")
+        );
+        assert_eq!(
+            manifest.optional_file("encoder"),
+            Some(fixture.0.join("tiny-encoder.int8.onnx"))
+        );
+        assert_eq!(manifest.optional_file("bpe_vocab"), None);
+        assert!(matches!(
+            manifest.file("bpe_vocab"),
+            Err(crate::Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn loads_json() {
+        let fixture = Fixture::new(
+            "json",
+            "manifest.json",
+            r#"{"type": "silero_vad", "files": {"model": "silero_vad.onnx"}}"#,
+        );
+        let manifest = fixture.load("manifest.json").unwrap();
+        assert_eq!(manifest.model_type, ModelType::SileroVad);
+        assert_eq!(manifest.name, None);
+        assert!(manifest.languages.is_empty());
+        assert_eq!(
+            manifest.file("model").unwrap(),
+            fixture.0.join("silero_vad.onnx")
+        );
+    }
+
+    #[test]
+    fn rejects_missing_required_files() {
+        let fixture = Fixture::new(
+            "missing",
+            "manifest.toml",
+            "type = \"transducer\"\n[files]\nencoder = \"e.onnx\"\ndecoder = \"d.onnx\"\ntokens = \"t.txt\"\n",
+        );
+        let error = fixture.load("manifest.toml").unwrap_err();
+        assert!(matches!(error, crate::Error::InvalidConfig(_)));
+        assert!(error.to_string().contains("`joiner`"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unknown_model_types() {
+        let fixture = Fixture::new(
+            "unknown",
+            "manifest.toml",
+            "type = \"wav2vec\"\n[files]\nmodel = \"m.onnx\"\n",
+        );
+        assert!(matches!(
+            fixture.load("manifest.toml"),
+            Err(crate::Error::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_manifests() {
+        let fixture = Fixture::new("absent", "other.toml", "");
+        assert!(matches!(
+            fixture.load("manifest.toml"),
+            Err(crate::Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn required_files_by_type() {
+        assert_eq!(
+            ModelType::Transducer.required_files(),
+            ["encoder", "decoder", "joiner", "tokens"]
+        );
+        assert_eq!(ModelType::SileroVad.required_files(), ["model"]);
+        assert_eq!(
+            ModelType::Matcha.required_files(),
+            ["acoustic_model", "vocoder", "tokens"]
+        );
+        assert_eq!(
+            ModelType::Kokoro.required_files(),
+            ["model", "voices", "tokens"]
+        );
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn whisper_builder_uses_manifest_files() {
+        let fixture = Fixture::new("whisper", "manifest.toml", WHISPER);
+        let manifest = fixture.load("manifest.toml").unwrap();
+        let builder = serde_json::to_value(manifest.whisper().unwrap()).unwrap();
+        assert_eq!(
+            builder["encoder"],
+            fixture.0.join("tiny-encoder.int8.onnx").to_str().unwrap()
+        );
+        assert_eq!(builder["language"], "en");
+        assert!(builder["bpe_vocab"].is_null());
+        assert!(manifest.offline_model().is_err());
+        #[cfg(feature = "vad")]
+        assert!(manifest.vad().is_err());
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn sense_voice_language_defaults_to_auto() {
+        let fixture = Fixture::new(
+            "sense-voice",
+            "manifest.toml",
+            "type = \"sense_voice\"\nlanguages = [\"zh\", \"en\"]\n[files]\nmodel = \"m.onnx\"\ntokens = \"t.txt\"\n",
+        );
+        let manifest = fixture.load("manifest.toml").unwrap();
+        let model = serde_json::to_value(manifest.offline_model().unwrap()).unwrap();
+        assert_eq!(model["type"], "sense_voice");
+        assert_eq!(model["language"], "auto");
+        assert_eq!(model["model"], fixture.0.join("m.onnx").to_str().unwrap());
+    }
+
+    #[cfg(feature = "vad")]
+    #[test]
+    fn vad_builder_uses_the_sample_rate() {
+        let fixture = Fixture::new(
+            "vad",
+            "manifest.toml",
+            "type = \"silero_vad\"\nsample_rate = 8000\n[files]\nmodel = \"silero_vad.onnx\"\n",
+        );
+        let manifest = fixture.load("manifest.toml").unwrap();
+        let builder = serde_json::to_value(manifest.vad().unwrap()).unwrap();
+        assert_eq!(builder["sample_rate"], 8000);
+        assert_eq!(
+            builder["model"],
+            fixture.0.join("silero_vad.onnx").to_str().unwrap()
+        );
+    }
+
+    #[cfg(feature = "tts")]
+    #[test]
+    fn tts_builder_picks_the_model_family() {
+        let fixture = Fixture::new(
+            "kokoro",
+            "manifest.toml",
+            "type = \"kokoro\"\n[files]\nmodel = \"model.onnx\"\nvoices = \"voices.bin\"\ntokens = \"tokens.txt\"\nlexicon = \"lexicon-us-en.txt\"\n",
+        );
+        let manifest = fixture.load("manifest.toml").unwrap();
+        let builder = serde_json::to_value(manifest.tts().unwrap()).unwrap();
+        assert_eq!(builder["type"], "kokoro");
+        assert_eq!(
+            builder["voices"],
+            fixture.0.join("voices.bin").to_str().unwrap()
+        );
+        assert_eq!(
+            builder["lexicon"],
+            fixture.0.join("lexicon-us-en.txt").to_str().unwrap()
+        );
+    }
+}
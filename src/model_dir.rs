@@ -0,0 +1,268 @@
+//! Find model files in directories laid out like the sherpa-onnx model releases.
+
+use crate::{bail, Provider, Result};
+use std::path::{Path, PathBuf};
+
+pub(crate) struct ModelDir {
+    dir: PathBuf,
+    files: Vec<String>,
+    /// int8 models are faster on CPU, GPUs want the fp32 ones
+    prefer_int8: bool,
+}
+
+impl ModelDir {
+    pub fn open(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!(ModelLoad, "model directory not found at {}", dir.display())
+        }
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            files.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        files.sort();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            files,
+            prefer_int8: Provider::default() == Provider::Cpu,
+        })
+    }
+
+    /// Lowercase directory name, release names carry the model family
+    pub fn name(&self) -> String {
+        self.dir
+            .canonicalize()
+            .unwrap_or_else(|_| self.dir.clone())
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Path of `name` if it exists in the directory
+    pub fn get(&self, name: &str) -> Option<PathBuf> {
+        self.files
+            .iter()
+            .any(|file| file == name)
+            .then(|| self.path(name))
+    }
+
+    /// Files named `<prefix>*.<extension>`, such as every `lexicon*.txt`
+    pub fn find(&self, prefix: &str, extension: &str) -> Vec<PathBuf> {
+        let suffix = format!(".{}", extension);
+        self.files
+            .iter()
+            .filter(|file| file.starts_with(prefix) && file.ends_with(&suffix))
+            .map(|file| self.path(file))
+            .collect()
+    }
+
+    /// The `.onnx` file whose name contains `role`
+    pub fn onnx(&self, role: &str) -> Result<Option<PathBuf>> {
+        let candidates = self
+            .onnx_files()
+            .filter(|file| file.to_lowercase().contains(role))
+            .collect();
+        self.pick(role, candidates)
+    }
+
+    /// The only `.onnx` file other than `exclude`, for single model directories
+    pub fn single_onnx(&self, exclude: Option<&Path>) -> Result<Option<PathBuf>> {
+        let candidates = self
+            .onnx_files()
+            .filter(|file| exclude != Some(self.path(file).as_path()))
+            .collect();
+        self.pick("model", candidates)
+    }
+
+    /// `tokens.txt`, or `<name>-tokens.txt` as shipped with whisper
+    pub fn tokens(&self) -> Result<PathBuf> {
+        if let Some(tokens) = self.get("tokens.txt") {
+            return Ok(tokens);
+        }
+        let candidates: Vec<&str> = self
+            .files
+            .iter()
+            .map(String::as_str)
+            .filter(|file| file.ends_with("tokens.txt"))
+            .collect();
+        match candidates.as_slice() {
+            [tokens] => Ok(self.path(tokens)),
+            [] => bail!(
+                ModelLoad,
+                "no tokens.txt in {}. {}",
+                self.dir.display(),
+                self.found()
+            ),
+            _ => bail!(
+                ModelLoad,
+                "found several tokens files in {}: {}",
+                self.dir.display(),
+                candidates.join(", ")
+            ),
+        }
+    }
+
+    /// Summary of the directory content for error messages
+    pub fn found(&self) -> String {
+        match self.files.is_empty() {
+            true => "The directory is empty".into(),
+            false => format!("Found: {}", self.files.join(", ")),
+        }
+    }
+
+    fn onnx_files(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .map(String::as_str)
+            .filter(|file| file.ends_with(".onnx"))
+    }
+
+    /// Prefer the int8 or fp32 variant depending on the provider
+    fn pick(&self, role: &str, candidates: Vec<&str>) -> Result<Option<PathBuf>> {
+        let mut models: Vec<String> = candidates
+            .iter()
+            .map(|file| file.replace(".int8", "").replace("-int8", ""))
+            .collect();
+        models.sort();
+        models.dedup();
+        if models.len() > 1 {
+            bail!(
+                ModelLoad,
+                "found several {} models in {}: {}. Pass the files explicitly instead",
+                role,
+                self.dir.display(),
+                candidates.join(", ")
+            )
+        }
+
+        let (int8, fp32): (Vec<&str>, Vec<&str>) = candidates
+            .into_iter()
+            .partition(|file| file.contains("int8"));
+        let (preferred, other) = match self.prefer_int8 {
+            true => (int8, fp32),
+            false => (fp32, int8),
+        };
+        Ok(preferred
+            .first()
+            .or(other.first())
+            .map(|file| self.path(file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory of empty files, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "sherpa-rs-model-dir-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            for file in files {
+                std::fs::write(dir.join(file), b"").unwrap();
+            }
+            Self(dir)
+        }
+
+        fn open(&self, prefer_int8: bool) -> ModelDir {
+            ModelDir {
+                prefer_int8,
+                ..ModelDir::open(&self.0).unwrap()
+            }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn prefers_int8_on_cpu_and_fp32_otherwise() {
+        let fixture = Fixture::new("int8", &["encoder.onnx", "encoder.int8.onnx", "tokens.txt"]);
+        let cpu = fixture.open(true).onnx("encoder").unwrap().unwrap();
+        assert_eq!(cpu, fixture.0.join("encoder.int8.onnx"));
+        let gpu = fixture.open(false).onnx("encoder").unwrap().unwrap();
+        assert_eq!(gpu, fixture.0.join("encoder.onnx"));
+    }
+
+    #[test]
+    fn falls_back_to_the_other_precision() {
+        let fixture = Fixture::new("fallback", &["tiny-decoder.onnx"]);
+        let decoder = fixture.open(true).onnx("decoder").unwrap().unwrap();
+        assert_eq!(decoder, fixture.0.join("tiny-decoder.onnx"));
+        assert_eq!(fixture.open(true).onnx("encoder").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_ambiguous_models() {
+        let fixture = Fixture::new("ambiguous", &["a-model.onnx", "b-model.onnx"]);
+        let error = fixture
+            .open(true)
+            .single_onnx(None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("found several model models"), "{}", error);
+        assert!(error.contains("a-model.onnx, b-model.onnx"), "{}", error);
+
+        let vad = fixture.0.join("b-model.onnx");
+        let model = fixture.open(true).single_onnx(Some(&vad)).unwrap();
+        assert_eq!(model, Some(fixture.0.join("a-model.onnx")));
+    }
+
+    #[test]
+    fn finds_whisper_tokens() {
+        let fixture = Fixture::new("tokens", &["tiny-tokens.txt", "tiny-encoder.onnx"]);
+        let tokens = fixture.open(true).tokens().unwrap();
+        assert_eq!(tokens, fixture.0.join("tiny-tokens.txt"));
+    }
+
+    #[test]
+    fn missing_files_list_the_directory() {
+        let fixture = Fixture::new("missing", &["model.onnx", "README.md"]);
+        let error = fixture.open(true).tokens().unwrap_err();
+        assert!(matches!(error, crate::Error::ModelLoad(_)));
+        let error = error.to_string();
+        assert!(error.contains("no tokens.txt in"), "{}", error);
+        assert!(error.contains("Found: README.md, model.onnx"), "{}", error);
+
+        let empty = Fixture::new("empty", &[]);
+        let error = empty.open(true).tokens().unwrap_err().to_string();
+        assert!(error.contains("The directory is empty"), "{}", error);
+    }
+
+    #[test]
+    fn missing_directory() {
+        let dir = std::env::temp_dir().join("sherpa-rs-model-dir-does-not-exist");
+        let error = ModelDir::open(&dir).err().unwrap().to_string();
+        assert!(error.contains("model directory not found"), "{}", error);
+    }
+
+    #[test]
+    fn finds_by_prefix_and_extension() {
+        let fixture = Fixture::new(
+            "find",
+            &[
+                "lexicon-us-en.txt",
+                "lexicon-zh.txt",
+                "lexicon.fst",
+                "tokens.txt",
+            ],
+        );
+        let dir = fixture.open(true);
+        assert_eq!(dir.find("lexicon", "txt").len(), 2);
+        assert_eq!(dir.get("tokens.txt"), Some(fixture.0.join("tokens.txt")));
+        assert_eq!(dir.get("espeak-ng-data"), None);
+    }
+}
@@ -0,0 +1,313 @@
+//! Async wrappers that run inference on dedicated worker threads.
+//!
+//! Every wrapper owns its engine and a small pool of OS threads, so long
+//! inference never blocks the async runtime. Methods return `'static` futures
+//! that resolve when the work is done and can be awaited from any runtime.
+//!
+//! Dropping a future cancels its work if it hasn't started yet. Native inference
+//! can't be interrupted, so work that already started runs to completion and
+//! its result is discarded.
+
+use crate::{Error, Result};
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+use tokio::sync::oneshot;
+
+#[cfg(feature = "speaker")]
+use crate::speaker_id::EmbeddingExtractor;
+#[cfg(feature = "asr")]
+use crate::transcribe::{
+    offline::{OfflineRecognizer, OfflineRecognizerResult},
+    online::{OnlineRecognizer, OnlineRecognizerResult},
+    whisper::{WhisperRecognizer, WhisperRecognizerResult},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads fed through a channel, they exit when the pool is dropped
+#[derive(Debug)]
+struct Workers {
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl Workers {
+    fn new(threads: usize) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..threads.max(1) {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name("sherpa-worker".into())
+                .spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    match job {
+                        // A panic drops the job's sender, its future reports the failure
+                        Ok(job) => {
+                            let _ = std::panic::catch_unwind(AssertUnwindSafe(job));
+                        }
+                        Err(_) => break,
+                    }
+                })
+                // Threads already spawned exit when `sender` is dropped
+                .map_err(|error| {
+                    Error::Worker(format!("failed to spawn worker thread: {}", error))
+                })?;
+        }
+        Ok(Self {
+            jobs: Mutex::new(sender),
+        })
+    }
+
+    fn spawn<T, F>(&self, work: F) -> impl Future<Output = Result<T>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let queued = self
+            .jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(Box::new(move || {
+                // The future was dropped while queued
+                if sender.is_closed() {
+                    return;
+                }
+                let _ = sender.send(work());
+            }))
+            .is_ok();
+        async move {
+            if !queued {
+                return Err(Error::Worker("worker threads stopped".into()));
+            }
+            receiver
+                .await
+                .map_err(|_| Error::Worker("inference panicked on the worker thread".into()))
+        }
+    }
+}
+
+/// Recognizer decoding on worker threads. Recognizers decode concurrently,
+/// so several threads share one instance.
+#[derive(Debug)]
+pub struct AsyncRecognizer<R> {
+    recognizer: Arc<R>,
+    workers: Workers,
+}
+
+impl<R: Send + Sync + 'static> AsyncRecognizer<R> {
+    pub fn new(recognizer: R) -> Result<Self> {
+        Self::with_threads(recognizer, 1)
+    }
+
+    /// Decode up to `threads` recordings at once
+    pub fn with_threads(recognizer: R, threads: usize) -> Result<Self> {
+        Ok(Self {
+            recognizer: Arc::new(recognizer),
+            workers: Workers::new(threads)?,
+        })
+    }
+
+    /// Run any blocking call on the recognizer
+    pub fn run<T, F>(&self, f: F) -> impl Future<Output = Result<T>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&R) -> T + Send + 'static,
+    {
+        let recognizer = self.recognizer.clone();
+        self.workers.spawn(move || f(&recognizer))
+    }
+}
+
+#[cfg(feature = "asr")]
+impl AsyncRecognizer<WhisperRecognizer> {
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> impl Future<Output = Result<WhisperRecognizerResult>> + Send + 'static {
+        let result = self.run(move |recognizer| recognizer.transcribe(sample_rate, samples));
+        async move { result.await? }
+    }
+}
+
+#[cfg(feature = "asr")]
+impl AsyncRecognizer<OfflineRecognizer> {
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> impl Future<Output = Result<OfflineRecognizerResult>> + Send + 'static {
+        let result = self.run(move |recognizer| recognizer.transcribe(sample_rate, samples));
+        async move { result.await? }
+    }
+}
+
+#[cfg(feature = "asr")]
+impl AsyncRecognizer<OnlineRecognizer> {
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> impl Future<Output = Result<OnlineRecognizerResult>> + Send + 'static {
+        let result = self.run(move |recognizer| recognizer.transcribe(sample_rate, samples));
+        async move { result.await? }
+    }
+}
+
+/// Embedding extractor on a worker thread, calls run one at a time
+#[cfg(feature = "speaker")]
+#[derive(Debug)]
+pub struct AsyncEmbeddingExtractor {
+    extractor: Arc<Mutex<EmbeddingExtractor>>,
+    // Read once, the lock is held during inference
+    embedding_size: usize,
+    workers: Workers,
+}
+
+#[cfg(feature = "speaker")]
+impl AsyncEmbeddingExtractor {
+    pub fn new(extractor: EmbeddingExtractor) -> Result<Self> {
+        Ok(Self {
+            embedding_size: extractor.embedding_size,
+            extractor: Arc::new(Mutex::new(extractor)),
+            workers: Workers::new(1)?,
+        })
+    }
+
+    pub fn embedding_size(&self) -> usize {
+        self.embedding_size
+    }
+
+    pub fn compute_speaker_embedding(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> impl Future<Output = Result<Vec<f32>>> + Send + 'static {
+        let extractor = self.extractor.clone();
+        let embedding = self.workers.spawn(move || {
+            extractor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .compute_speaker_embedding(sample_rate, samples)
+        });
+        async move { embedding.await? }
+    }
+}
+
+#[cfg(feature = "tts")]
+pub use tts::AsyncTts;
+
+#[cfg(feature = "tts")]
+mod tts {
+    use super::Workers;
+    use crate::tts::{markup::MarkupSample, OfflineTts, TtsSample};
+    use crate::Result;
+    use std::{
+        future::Future,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
+    /// Text to speech on a worker thread, calls run one at a time
+    #[derive(Debug)]
+    pub struct AsyncTts {
+        tts: Arc<Mutex<OfflineTts>>,
+        workers: Workers,
+    }
+
+    impl AsyncTts {
+        pub fn new(tts: OfflineTts) -> Result<Self> {
+            Ok(Self {
+                tts: Arc::new(Mutex::new(tts)),
+                workers: Workers::new(1)?,
+            })
+        }
+
+        /// Run any blocking call on the engine
+        pub fn run<T, F>(&self, f: F) -> impl Future<Output = Result<T>> + Send + 'static
+        where
+            T: Send + 'static,
+            F: FnOnce(&mut OfflineTts) -> T + Send + 'static,
+        {
+            let tts = self.tts.clone();
+            self.workers
+                .spawn(move || f(&mut tts.lock().unwrap_or_else(PoisonError::into_inner)))
+        }
+
+        pub fn generate(
+            &self,
+            text: String,
+            sid: i32,
+            speed: f32,
+        ) -> impl Future<Output = Result<TtsSample>> + Send + 'static {
+            let sample = self.run(move |tts| tts.generate(text, sid, speed));
+            async move { sample.await? }
+        }
+
+        pub fn generate_markup(
+            &self,
+            markup: String,
+            sid: i32,
+            speed: f32,
+        ) -> impl Future<Output = Result<MarkupSample>> + Send + 'static {
+            let sample = self.run(move |tts| tts.generate_markup(&markup, sid, speed));
+            async move { sample.await? }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Wake},
+    };
+
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // The futures don't need a runtime
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut context = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn runs_work_on_worker_threads() {
+        let recognizer = AsyncRecognizer::with_threads(2, 2).unwrap();
+        let caller = thread::current().id();
+        let (value, worker) =
+            block_on(recognizer.run(|value| (*value * 21, thread::current().id()))).unwrap();
+        assert_eq!(value, 42);
+        assert_ne!(worker, caller);
+    }
+
+    #[test]
+    fn panics_become_worker_errors() {
+        let recognizer = AsyncRecognizer::new(()).unwrap();
+        let result = block_on(recognizer.run(|_| -> () { panic!("inference failed") }));
+        assert!(matches!(result, Err(Error::Worker(_))));
+        // The worker survives the panic
+        assert_eq!(block_on(recognizer.run(|_| 1)).unwrap(), 1);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{bail, Error, Result};
+use std::{fmt, str::FromStr};
+
+/// ONNX Runtime execution provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Provider {
+    Cpu,
+    /// Requires the `cuda` feature and an NVIDIA driver
+    Cuda,
+    /// Always built in on macOS
+    CoreMl,
+    /// Requires the `directml` feature, Windows only
+    DirectMl,
+}
+
+/// Preferred order when no provider is requested
+const PREFERENCE: [Provider; 4] = [
+    Provider::Cuda,
+    Provider::DirectMl,
+    Provider::CoreMl,
+    Provider::Cpu,
+];
+
+impl Provider {
+    /// Name used by sherpa-onnx
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Cuda => "cuda",
+            Self::CoreMl => "coreml",
+            Self::DirectMl => "directml",
+        }
+    }
+
+    /// Whether the provider was compiled in and, for CUDA, a driver is installed.
+    /// The device itself is only checked by ONNX Runtime when a model is loaded.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Self::Cpu => true,
+            Self::Cuda => cfg!(feature = "cuda") && nvidia_driver_installed(),
+            Self::CoreMl => cfg!(target_os = "macos"),
+            Self::DirectMl => cfg!(feature = "directml") && cfg!(windows),
+        }
+    }
+
+    /// Available providers, most preferred first. Always ends with `Cpu`.
+    pub fn available() -> Vec<Provider> {
+        PREFERENCE
+            .into_iter()
+            .filter(Provider::is_available)
+            .collect()
+    }
+
+    /// First available provider of `providers`, `Cpu` if none is
+    pub fn first_available(providers: &[Provider]) -> Provider {
+        providers
+            .iter()
+            .copied()
+            .find(Provider::is_available)
+            .unwrap_or(Provider::Cpu)
+    }
+
+    /// The provider an engine tries first, see `Provider::candidates`
+    pub fn resolve(requested: Option<Provider>) -> Provider {
+        Self::candidates(requested.as_slice())[0]
+    }
+
+    /// Fallback policy shared by all engines: the available providers of
+    /// `requested` in order, or the default one if none is, then `Cpu` in case
+    /// the others can't load the model
+    pub fn candidates(requested: &[Provider]) -> Vec<Provider> {
+        let mut candidates = Vec::new();
+        for provider in requested {
+            if !provider.is_available() {
+                tracing::warn!("provider {} is not available, skipping it", provider);
+            } else if !candidates.contains(provider) {
+                candidates.push(*provider);
+            }
+        }
+        if candidates.is_empty() {
+            candidates.push(Provider::default());
+        }
+        if !candidates.contains(&Provider::Cpu) {
+            candidates.push(Provider::Cpu);
+        }
+        candidates
+    }
+}
+
+/// Engine handle returned by a sherpa-onnx `Create` function, null on failure
+pub(crate) trait Handle: Copy {
+    fn is_null(&self) -> bool;
+}
+
+impl<T> Handle for *mut T {
+    fn is_null(&self) -> bool {
+        <*mut T>::is_null(*self)
+    }
+}
+
+impl<T> Handle for *const T {
+    fn is_null(&self) -> bool {
+        <*const T>::is_null(*self)
+    }
+}
+
+/// Call `create` with each of `Provider::candidates(requested)` until it returns
+/// a handle. Returns the null handle of the last provider if none could load the model.
+#[allow(dead_code)]
+pub(crate) fn create<H: Handle>(
+    requested: &[Provider],
+    mut create: impl FnMut(Provider) -> Result<H>,
+) -> Result<H> {
+    let candidates = Provider::candidates(requested);
+    let mut handle = create(candidates[0])?;
+    for (failed, provider) in candidates.iter().zip(&candidates[1..]) {
+        if !handle.is_null() {
+            break;
+        }
+        tracing::warn!(
+            "failed to load the model with provider {}, trying {}",
+            failed,
+            provider
+        );
+        handle = create(*provider)?;
+    }
+    Ok(handle)
+}
+
+/// Providers in configs may be written as one name or a list
+#[cfg(feature = "serde")]
+#[allow(dead_code)]
+pub(crate) fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<Provider>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Provider),
+        Many(Vec<Provider>),
+    }
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        OneOrMany::One(provider) => vec![provider],
+        OneOrMany::Many(providers) => providers,
+    })
+}
+
+impl Default for Provider {
+    /// Most preferred available provider
+    fn default() -> Self {
+        Self::first_available(&PREFERENCE)
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Provider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "cpu" => Self::Cpu,
+            "cuda" => Self::Cuda,
+            "coreml" => Self::CoreMl,
+            "directml" => Self::DirectMl,
+            _ => bail!(
+                Parse,
+                "unknown provider `{}`, expected one of cpu, cuda, coreml, directml",
+                s
+            ),
+        })
+    }
+}
+
+/// Parse the provider names taken by the deprecated constructors, ignoring invalid ones
+#[allow(dead_code)]
+pub(crate) fn parse_provider(provider: Option<String>) -> Option<Provider> {
+    let provider = provider?;
+    match provider.parse() {
+        Ok(provider) => Some(provider),
+        Err(error) => {
+            tracing::warn!("{}, using the default provider", error);
+            None
+        }
+    }
+}
+
+fn nvidia_driver_installed() -> bool {
+    if cfg!(target_os = "linux") {
+        std::path::Path::new("/proc/driver/nvidia/version").exists()
+    } else if cfg!(windows) {
+        let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".into());
+        std::path::Path::new(&system_root)
+            .join("System32")
+            .join("nvcuda.dll")
+            .exists()
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_case_insensitively() {
+        for provider in PREFERENCE {
+            assert_eq!(provider.to_string().parse::<Provider>().unwrap(), provider);
+            assert_eq!(
+                provider
+                    .as_str()
+                    .to_uppercase()
+                    .parse::<Provider>()
+                    .unwrap(),
+                provider
+            );
+        }
+        assert_eq!("CoreML".parse::<Provider>().unwrap(), Provider::CoreMl);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let error = "tensorrt".parse::<Provider>().unwrap_err();
+        assert!(matches!(error, Error::Parse(_)));
+        assert!(error.to_string().contains("tensorrt"));
+        assert!("".parse::<Provider>().is_err());
+    }
+
+    #[test]
+    fn deprecated_names_fall_back_to_default() {
+        assert_eq!(parse_provider(None), None);
+        assert_eq!(parse_provider(Some("cuda".into())), Some(Provider::Cuda));
+        assert_eq!(parse_provider(Some("gpu".into())), None);
+    }
+
+    #[test]
+    fn available_ends_with_cpu() {
+        let available = Provider::available();
+        assert_eq!(available.last(), Some(&Provider::Cpu));
+        assert_eq!(available[0], Provider::default());
+        assert_eq!(Provider::first_available(&[]), Provider::Cpu);
+    }
+
+    #[test]
+    fn candidates_end_with_cpu() {
+        let candidates = Provider::candidates(&[]);
+        assert_eq!(candidates[0], Provider::default());
+        assert_eq!(candidates.last(), Some(&Provider::Cpu));
+        assert!(candidates.len() <= 2);
+        assert_eq!(Provider::candidates(&[Provider::Cpu]), [Provider::Cpu]);
+        assert_eq!(
+            Provider::candidates(&[Provider::Cpu, Provider::Cpu]),
+            [Provider::Cpu]
+        );
+        for provider in PREFERENCE {
+            let candidates = Provider::candidates(&[provider, Provider::Cpu]);
+            assert_eq!(candidates.last(), Some(&Provider::Cpu));
+            assert_eq!(candidates.contains(&provider), provider.is_available());
+        }
+    }
+
+    #[test]
+    fn create_tries_providers_until_one_loads() {
+        let mut tried = Vec::new();
+        let mut loaded = 1u8;
+        let handle = create(&[Provider::Cpu], |provider| {
+            tried.push(provider);
+            Ok(&mut loaded as *mut u8)
+        })
+        .unwrap();
+        assert!(!handle.is_null());
+        assert_eq!(tried, [Provider::Cpu]);
+
+        // Whatever is available is tried before falling back to the CPU
+        let requested = PREFERENCE;
+        let expected = Provider::candidates(&requested);
+        let mut tried = Vec::new();
+        let handle = create(&requested, |provider| {
+            tried.push(provider);
+            Ok(match provider {
+                Provider::Cpu => &mut loaded as *mut u8,
+                _ => std::ptr::null_mut(),
+            })
+        })
+        .unwrap();
+        assert!(!handle.is_null());
+        assert_eq!(tried, expected);
+    }
+
+    #[test]
+    fn create_returns_null_if_every_provider_fails() {
+        let mut calls = 0;
+        let handle = create(&[Provider::Cpu], |_| {
+            calls += 1;
+            Ok(std::ptr::null::<u8>())
+        })
+        .unwrap();
+        assert!(handle.is_null());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn create_stops_on_errors() {
+        let result = create::<*const u8>(&PREFERENCE, |_| crate::bail!(Native, "failed"));
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_one_provider_or_a_list() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            #[serde(default, alias = "provider", deserialize_with = "one_or_many")]
+            providers: Vec<Provider>,
+        }
+        let read = |json: &str| serde_json::from_str::<Config>(json).unwrap().providers;
+        assert_eq!(read(r#"{"provider": "cuda"}"#), [Provider::Cuda]);
+        assert_eq!(
+            read(r#"{"providers": ["cuda", "cpu"]}"#),
+            [Provider::Cuda, Provider::Cpu]
+        );
+        assert!(read("{}").is_empty());
+        assert!(serde_json::from_str::<Config>(r#"{"provider": "gpu"}"#).is_err());
+    }
+
+    #[test]
+    fn resolve_falls_back_to_default() {
+        assert_eq!(Provider::resolve(None), Provider::default());
+        assert_eq!(Provider::resolve(Some(Provider::Cpu)), Provider::Cpu);
+        for provider in PREFERENCE {
+            let expected = match provider.is_available() {
+                true => provider,
+                false => Provider::default(),
+            };
+            assert_eq!(Provider::resolve(Some(provider)), expected);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use eyre::{bail, Result};
 use std::{ffi::CString, path::PathBuf};
 
-use crate::get_default_provider;
+use crate::{
+    bail, check_file, cstr, logging, path_to_cstring,
+    provider::{self, parse_provider, Provider},
+    Result,
+};
 
 /// If similarity is greater or equal to thresold than it's a match!
 pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.5;
@@ -9,7 +12,8 @@ pub const DEFAULT_SIMILARITY_THRESHOLD: f32 = 0.5;
 #[derive(Debug)]
 pub struct ExtractorConfig {
     pub(crate) cfg: sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorConfig,
-    model: String,
+    model: PathBuf,
+    providers: Vec<Provider>,
 }
 
 #[derive(Debug)]
@@ -18,25 +22,110 @@ pub struct EmbeddingExtractor {
     pub embedding_size: usize,
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractorConfigBuilder {
+    model: PathBuf,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+}
+
+fn default_num_threads() -> i32 {
+    2
+}
+
+impl ExtractorConfigBuilder {
+    pub fn new<P: Into<PathBuf>>(model: P) -> Self {
+        Self {
+            model: model.into(),
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+            debug: false,
+        }
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn build(self) -> Result<ExtractorConfig> {
+        check_file(&self.model)?;
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        ExtractorConfig::from_builder(self)
+    }
+}
+
 impl ExtractorConfig {
+    pub fn builder<P: Into<PathBuf>>(model: P) -> ExtractorConfigBuilder {
+        ExtractorConfigBuilder::new(model)
+    }
+
+    #[deprecated(note = "use `ExtractorConfig::builder` instead")]
     pub fn new(
         model: String,
         provider: Option<String>,
         num_threads: Option<i32>,
         debug: bool,
-    ) -> Self {
-        let provider = provider.unwrap_or(get_default_provider());
-        let num_threads = num_threads.unwrap_or(2);
-        let debug = if debug { 1 } else { 0 };
-        let model_cstr = CString::new(model.clone()).unwrap();
-        let provider = CString::new(provider).unwrap();
+    ) -> Result<Self> {
+        let mut builder = Self::builder(model)
+            .num_threads(num_threads.unwrap_or(default_num_threads()))
+            .debug(debug);
+        builder.providers = parse_provider(provider).into_iter().collect();
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: ExtractorConfigBuilder) -> Result<Self> {
+        let provider = Provider::candidates(&builder.providers)[0].as_str();
+        let debug = if builder.debug { 1 } else { 0 };
+        let model_cstr = path_to_cstring(&builder.model)?;
+        let provider = cstr!(provider);
         let cfg = sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorConfig {
             debug,
             model: model_cstr.into_raw(),
-            num_threads,
+            num_threads: builder.num_threads,
             provider: provider.into_raw(),
         };
-        Self { cfg, model }
+        Ok(Self {
+            cfg,
+            model: builder.model,
+            providers: builder.providers,
+        })
     }
 
     pub fn as_ptr(&self) -> *const sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorConfig {
@@ -45,18 +134,23 @@ impl ExtractorConfig {
 }
 
 impl EmbeddingExtractor {
-    pub fn new_from_config(config: ExtractorConfig) -> Result<Self> {
-        let model_path = PathBuf::from(&config.model);
-        if !model_path.exists() {
-            bail!("model not found at {}", model_path.display())
+    pub fn new_from_config(mut config: ExtractorConfig) -> Result<Self> {
+        check_file(&config.model)?;
+        let extractor = provider::create(&config.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.cfg.provider = provider_c.as_ptr();
+            Ok(logging::load("speaker embedding extractor", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateSpeakerEmbeddingExtractor(&config.cfg)
+            }))
+        })?;
+        if extractor.is_null() {
+            bail!(ModelLoad, "failed to create speaker embedding extractor")
         }
-        let extractor =
-            unsafe { sherpa_rs_sys::SherpaOnnxCreateSpeakerEmbeddingExtractor(config.as_ptr()) };
-        // Assume embedding size is known or can be retrieved
-        let embedding_size =
-            unsafe { sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorDim(extractor) }
-                .try_into()
-                .unwrap();
+        let dim = unsafe { sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorDim(extractor) };
+        let Ok(embedding_size) = dim.try_into() else {
+            unsafe { sherpa_rs_sys::SherpaOnnxDestroySpeakerEmbeddingExtractor(extractor) };
+            bail!(Native, "invalid embedding size {}", dim)
+        };
         Ok(Self {
             extractor,
             embedding_size,
@@ -72,29 +166,34 @@ impl EmbeddingExtractor {
             let stream =
                 sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorCreateStream(self.extractor);
             if stream.is_null() {
-                bail!("Failed to create SherpaOnnxOnlineStream");
+                bail!(Native, "Failed to create SherpaOnnxOnlineStream");
             }
 
-            sherpa_rs_sys::SherpaOnnxOnlineStreamAcceptWaveform(
-                stream,
-                sample_rate,
-                samples.as_ptr(),
-                samples.len() as i32,
-            );
+            // The C API counts samples with an i32, a stream can take them in parts
+            for part in samples.chunks(i32::MAX as usize) {
+                sherpa_rs_sys::SherpaOnnxOnlineStreamAcceptWaveform(
+                    stream,
+                    sample_rate,
+                    part.as_ptr(),
+                    part.len() as i32,
+                );
+            }
             sherpa_rs_sys::SherpaOnnxOnlineStreamInputFinished(stream);
 
             if !self.is_ready(stream) {
-                bail!("Embedding extractor is not ready");
+                bail!(Native, "Embedding extractor is not ready");
             }
 
-            let embedding_ptr = sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorComputeEmbedding(
-                self.extractor,
-                stream,
-            );
+            let embedding_ptr = logging::infer("speaker embedding extractor", || {
+                sherpa_rs_sys::SherpaOnnxSpeakerEmbeddingExtractorComputeEmbedding(
+                    self.extractor,
+                    stream,
+                )
+            });
             if embedding_ptr.is_null() {
-                bail!("Failed to compute speaker embedding");
+                bail!(Native, "Failed to compute speaker embedding");
             }
-            log::debug!("using dimensions {}", self.embedding_size);
+            tracing::debug!("using dimensions {}", self.embedding_size);
             let embedding = std::slice::from_raw_parts(embedding_ptr, self.embedding_size).to_vec();
             // Free
             sherpa_rs_sys::SherpaOnnxDestroyOnlineStream(stream);
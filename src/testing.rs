@@ -0,0 +1,357 @@
+//! Deterministic engines for tests of downstream code, they need no model.
+//!
+//! Recognizers, embedders and language identifiers answer from a script, in
+//! order, and fail once it runs out. The VAD cuts scripted time ranges out of
+//! the audio it's fed, and the synthesizer generates a tone whose length follows
+//! the text. Each fake records what it was called with.
+//!
+//! ```
+//! use sherpa_rs::{testing::FakeRecognizer, Recognizer};
+//!
+//! fn caption(recognizer: &impl Recognizer, samples: &[f32]) -> sherpa_rs::Result<String> {
+//!     Ok(recognizer.transcribe(16000, samples)?.text.to_uppercase())
+//! }
+//!
+//! let recognizer = FakeRecognizer::new(["hello world"]);
+//! assert_eq!(caption(&recognizer, &[0.0; 16000])?, "HELLO WORLD");
+//! assert_eq!(recognizer.calls(), [16000]);
+//! # Ok::<(), sherpa_rs::Error>(())
+//! ```
+
+use crate::{
+    bail,
+    engine::{LanguageIdentifier, Recognizer, SpeakerEmbedder, Transcript},
+    Result,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, PoisonError},
+};
+
+#[cfg(feature = "vad")]
+use crate::{engine::VoiceActivityDetector, vad::SpeechSegment};
+
+/// Scripted responses, handed out in order
+#[derive(Debug, Clone)]
+struct Script<T> {
+    name: &'static str,
+    responses: VecDeque<T>,
+}
+
+impl<T> Script<T> {
+    fn new(name: &'static str, responses: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            name,
+            responses: responses.into_iter().collect(),
+        }
+    }
+
+    fn next(&mut self) -> Result<T> {
+        match self.responses.pop_front() {
+            Some(response) => Ok(response),
+            None => bail!(InvalidConfig, "{} has no scripted response left", self.name),
+        }
+    }
+}
+
+/// Returns scripted transcripts, in the order of the calls when shared between threads
+#[derive(Debug)]
+pub struct FakeRecognizer {
+    // Recognizers transcribe through `&self`
+    state: Mutex<(Script<Transcript>, Vec<usize>)>,
+}
+
+impl FakeRecognizer {
+    pub fn new<T: Into<Transcript>>(transcripts: impl IntoIterator<Item = T>) -> Self {
+        let script = Script::new("FakeRecognizer", transcripts.into_iter().map(Into::into));
+        Self {
+            state: Mutex::new((script, Vec::new())),
+        }
+    }
+
+    /// Number of samples of each call
+    pub fn calls(&self) -> Vec<usize> {
+        self.state().1.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, (Script<Transcript>, Vec<usize>)> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for FakeRecognizer {
+    fn clone(&self) -> Self {
+        Self {
+            state: Mutex::new(self.state().clone()),
+        }
+    }
+}
+
+impl Recognizer for FakeRecognizer {
+    fn transcribe(&self, _sample_rate: i32, samples: &[f32]) -> Result<Transcript> {
+        let mut state = self.state();
+        state.1.push(samples.len());
+        state.0.next()
+    }
+}
+
+/// Reports speech at scripted times of the stream
+#[cfg(feature = "vad")]
+#[derive(Debug, Clone)]
+pub struct FakeVad {
+    /// Speech ranges in samples, sorted
+    ranges: Vec<(usize, usize)>,
+    /// Ranges not yet returned as segments
+    next_range: usize,
+    audio: Vec<f32>,
+    segments: VecDeque<SpeechSegment>,
+    is_speech: bool,
+}
+
+#[cfg(feature = "vad")]
+impl FakeVad {
+    /// Speech between each `(start, end)` in seconds, for audio at `sample_rate`
+    pub fn new(sample_rate: i32, speech: &[(f32, f32)]) -> Self {
+        let mut ranges: Vec<(usize, usize)> = speech
+            .iter()
+            .map(|&(start, end)| {
+                let start = (start * sample_rate as f32) as usize;
+                let end = (end * sample_rate as f32) as usize;
+                (start, end.max(start))
+            })
+            .collect();
+        ranges.sort();
+        Self {
+            ranges,
+            next_range: 0,
+            audio: Vec::new(),
+            segments: VecDeque::new(),
+            is_speech: false,
+        }
+    }
+
+    /// Move the ranges ending before `end` to the finished segments
+    fn finish(&mut self, end: usize) {
+        while let Some(&(start, range_end)) = self.ranges.get(self.next_range) {
+            if range_end > end {
+                break;
+            }
+            self.segments.push_back(SpeechSegment {
+                start: start as i32,
+                samples: self.audio[start..range_end].to_vec(),
+            });
+            self.next_range += 1;
+        }
+    }
+}
+
+#[cfg(feature = "vad")]
+impl VoiceActivityDetector for FakeVad {
+    fn accept_waveform(&mut self, samples: &[f32]) {
+        let start = self.audio.len();
+        self.audio.extend_from_slice(samples);
+        let end = self.audio.len();
+        self.is_speech = self
+            .ranges
+            .iter()
+            .any(|&(speech_start, speech_end)| speech_start < end && start < speech_end);
+        self.finish(end);
+    }
+
+    fn flush(&mut self) {
+        let end = self.audio.len();
+        self.finish(end);
+        // Speech in progress ends with the stream
+        if let Some(&(start, _)) = self.ranges.get(self.next_range) {
+            if start < end {
+                self.segments.push_back(SpeechSegment {
+                    start: start as i32,
+                    samples: self.audio[start..].to_vec(),
+                });
+                self.next_range += 1;
+            }
+        }
+        self.is_speech = false;
+    }
+
+    fn pop_segment(&mut self) -> Option<SpeechSegment> {
+        self.segments.pop_front()
+    }
+
+    fn is_speech(&mut self) -> bool {
+        self.is_speech
+    }
+
+    /// Also restarts the script, the next stream gets the same segments
+    fn clear(&mut self) {
+        self.next_range = 0;
+        self.audio.clear();
+        self.segments.clear();
+        self.is_speech = false;
+    }
+}
+
+/// Returns scripted embeddings
+#[derive(Debug, Clone)]
+pub struct FakeEmbedder {
+    embedding_size: usize,
+    script: Script<Vec<f32>>,
+    calls: Vec<usize>,
+}
+
+impl FakeEmbedder {
+    pub fn new(embedding_size: usize, embeddings: impl IntoIterator<Item = Vec<f32>>) -> Self {
+        Self {
+            embedding_size,
+            script: Script::new("FakeEmbedder", embeddings),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Number of samples of each call
+    pub fn calls(&self) -> &[usize] {
+        &self.calls
+    }
+}
+
+impl SpeakerEmbedder for FakeEmbedder {
+    fn embedding_size(&self) -> usize {
+        self.embedding_size
+    }
+
+    fn compute_speaker_embedding(
+        &mut self,
+        _sample_rate: i32,
+        samples: &[f32],
+    ) -> Result<Vec<f32>> {
+        self.calls.push(samples.len());
+        let embedding = self.script.next()?;
+        if embedding.len() != self.embedding_size {
+            bail!(
+                InvalidConfig,
+                "scripted embedding has {} dimensions, expected {}",
+                embedding.len(),
+                self.embedding_size
+            )
+        }
+        Ok(embedding)
+    }
+}
+
+/// Generates a 440 Hz tone lasting `seconds_per_char` per character of text
+#[cfg(feature = "tts")]
+#[derive(Debug, Clone)]
+pub struct FakeSynthesizer {
+    sample_rate: i32,
+    seconds_per_char: f32,
+    calls: Vec<String>,
+}
+
+#[cfg(feature = "tts")]
+impl FakeSynthesizer {
+    pub fn new(sample_rate: i32) -> Self {
+        Self {
+            sample_rate,
+            seconds_per_char: 0.06,
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn seconds_per_char(mut self, seconds: f32) -> Self {
+        self.seconds_per_char = seconds;
+        self
+    }
+
+    /// Text of each call
+    pub fn calls(&self) -> &[String] {
+        &self.calls
+    }
+}
+
+#[cfg(feature = "tts")]
+impl crate::engine::SpeechSynthesizer for FakeSynthesizer {
+    fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    fn generate(&mut self, text: &str, _sid: i32, speed: f32) -> Result<crate::tts::TtsSample> {
+        if speed <= 0.0 {
+            bail!(InvalidConfig, "speed must be positive, got {}", speed)
+        }
+        self.calls.push(text.to_string());
+        let duration = text.chars().count() as f32 * self.seconds_per_char / speed;
+        let len = (duration * self.sample_rate as f32) as usize;
+        let samples = (0..len)
+            .map(|i| {
+                let t = i as f32 / self.sample_rate as f32;
+                0.1 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            })
+            .collect();
+        Ok(crate::tts::TtsSample {
+            samples,
+            sample_rate: self.sample_rate,
+            duration: len as f32 / self.sample_rate as f32,
+        })
+    }
+}
+
+/// Returns scripted language codes
+#[derive(Debug, Clone)]
+pub struct FakeLanguageIdentifier {
+    script: Script<String>,
+    calls: Vec<usize>,
+}
+
+impl FakeLanguageIdentifier {
+    pub fn new<T: Into<String>>(languages: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            script: Script::new(
+                "FakeLanguageIdentifier",
+                languages.into_iter().map(Into::into),
+            ),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Number of samples of each call
+    pub fn calls(&self) -> &[usize] {
+        &self.calls
+    }
+}
+
+impl LanguageIdentifier for FakeLanguageIdentifier {
+    fn compute(&mut self, samples: &[f32], _sample_rate: i32) -> Result<String> {
+        self.calls.push(samples.len());
+        self.script.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn recognizer_can_be_shared() {
+        let recognizer: Arc<dyn Recognizer + Send + Sync> =
+            Arc::new(FakeRecognizer::new(["one", "two", "three", "four"]));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let recognizer = Arc::clone(&recognizer);
+                scope.spawn(move || recognizer.transcribe(16000, &[0.0; 160]).unwrap());
+            }
+        });
+        assert!(recognizer.transcribe(16000, &[]).is_err());
+    }
+
+    #[test]
+    fn clones_keep_their_own_script() {
+        let recognizer = FakeRecognizer::new(["one", "two"]);
+        recognizer.transcribe(16000, &[0.0; 10]).unwrap();
+        let clone = recognizer.clone();
+        assert_eq!(recognizer.transcribe(16000, &[]).unwrap().text, "two");
+        assert_eq!(clone.transcribe(16000, &[]).unwrap().text, "two");
+        assert_eq!(recognizer.calls(), [10, 0]);
+        assert_eq!(clone.calls(), [10, 0]);
+    }
+}
@@ -1 +1,11 @@
+//! Speech recognizers.
+//!
+//! Recognizers are `Sync`. Decoding only reads the shared model: every call
+//! creates its own stream and sherpa-onnx runs it through the ONNX Runtime
+//! session, whose `Run` is thread-safe. See `pool::RecognizerPool` to bound the
+//! number of concurrent calls.
+
+pub mod offline;
+pub mod online;
+pub mod pool;
 pub mod whisper;
@@ -0,0 +1,348 @@
+use crate::{
+    bail, check_file, cstr, cstr_to_string, logging, model_dir::ModelDir, path_to_cstring,
+    provider, sample_count, Provider, Result,
+};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+};
+
+/// Offline model families that report token timestamps
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum OfflineModelConfig {
+    Transducer {
+        encoder: PathBuf,
+        decoder: PathBuf,
+        joiner: PathBuf,
+    },
+    Paraformer {
+        model: PathBuf,
+    },
+    NemoCtc {
+        model: PathBuf,
+    },
+    SenseVoice {
+        model: PathBuf,
+        #[cfg_attr(feature = "serde", serde(default))]
+        language: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        use_itn: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct OfflineRecognizer {
+    recognizer: *mut sherpa_rs_sys::SherpaOnnxOfflineRecognizer,
+}
+
+#[derive(Debug, Clone)]
+pub struct OfflineRecognizerResult {
+    pub text: String,
+    pub tokens: Vec<String>,
+    /// Start time in seconds of each token
+    pub timestamps: Vec<f32>,
+    pub lang: String,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfflineRecognizerBuilder {
+    model: OfflineModelConfig,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+}
+
+fn default_num_threads() -> i32 {
+    2
+}
+
+impl OfflineModelConfig {
+    fn files(&self) -> Vec<&Path> {
+        match self {
+            Self::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            } => vec![encoder, decoder, joiner],
+            Self::Paraformer { model }
+            | Self::NemoCtc { model }
+            | Self::SenseVoice { model, .. } => vec![model],
+        }
+    }
+}
+
+impl OfflineRecognizerBuilder {
+    pub fn new<P: Into<PathBuf>>(model: OfflineModelConfig, tokens: P) -> Self {
+        Self {
+            model,
+            tokens: tokens.into(),
+            debug: false,
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+        }
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn build(self) -> Result<OfflineRecognizer> {
+        for file in self.model.files() {
+            check_file(file)?;
+        }
+        check_file(&self.tokens)?;
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        OfflineRecognizer::from_builder(self)
+    }
+}
+
+impl OfflineRecognizer {
+    pub fn builder<P: Into<PathBuf>>(
+        model: OfflineModelConfig,
+        tokens: P,
+    ) -> OfflineRecognizerBuilder {
+        OfflineRecognizerBuilder::new(model, tokens)
+    }
+
+    fn from_builder(builder: OfflineRecognizerBuilder) -> Result<Self> {
+        let tokens_c = path_to_cstring(&builder.tokens)?;
+        let debug = if builder.debug { 1 } else { 0 };
+        let num_threads = builder.num_threads;
+
+        let mut model_config = sherpa_rs_sys::SherpaOnnxOfflineModelConfig {
+            debug,
+            num_threads,
+            tokens: tokens_c.into_raw(),
+            // The provider is set by `provider::create`. Models and options this
+            // crate doesn't set stay at their C API defaults
+            ..unsafe { std::mem::zeroed() }
+        };
+        match builder.model {
+            OfflineModelConfig::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            } => {
+                let transducer = &mut model_config.transducer;
+                transducer.encoder = path_to_cstring(&encoder)?.into_raw();
+                transducer.decoder = path_to_cstring(&decoder)?.into_raw();
+                transducer.joiner = path_to_cstring(&joiner)?.into_raw();
+            }
+            OfflineModelConfig::Paraformer { model } => {
+                model_config.paraformer.model = path_to_cstring(&model)?.into_raw();
+            }
+            OfflineModelConfig::NemoCtc { model } => {
+                model_config.nemo_ctc.model = path_to_cstring(&model)?.into_raw();
+            }
+            OfflineModelConfig::SenseVoice {
+                model,
+                language,
+                use_itn,
+            } => {
+                let sense_voice = &mut model_config.sense_voice;
+                sense_voice.model = path_to_cstring(&model)?.into_raw();
+                sense_voice.language = cstr!(language).into_raw();
+                sense_voice.use_itn = if use_itn { 1 } else { 0 };
+            }
+        }
+
+        let decoding_method_c = cstr!("greedy_search");
+        let mut config = sherpa_rs_sys::SherpaOnnxOfflineRecognizerConfig {
+            decoding_method: decoding_method_c.into_raw(),
+            feat_config: sherpa_rs_sys::SherpaOnnxFeatureConfig {
+                sample_rate: 16000,
+                feature_dim: 80,
+            },
+            model_config,
+            ..unsafe { std::mem::zeroed() }
+        };
+        let recognizer = provider::create(&builder.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.model_config.provider = provider_c.as_ptr();
+            Ok(logging::load("offline recognizer", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateOfflineRecognizer(&config)
+            }))
+        })?;
+        if recognizer.is_null() {
+            bail!(ModelLoad, "failed to create offline recognizer")
+        }
+
+        Ok(Self { recognizer })
+    }
+
+    /// Detect the model family and files of a sherpa-onnx release directory.
+    ///
+    /// Transducers are recognized by their encoder, decoder and joiner files. Single
+    /// file models are told apart by the directory name, which must contain
+    /// `paraformer`, `sense-voice` or `nemo` and `ctc` as in the release names.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref();
+        let dir = ModelDir::open(path)?;
+        let name = dir.name();
+        if name.contains("streaming") {
+            bail!(
+                InvalidConfig,
+                "{} is a streaming model, use OnlineRecognizer::from_dir",
+                path.display()
+            )
+        }
+        if name.contains("whisper") {
+            bail!(
+                InvalidConfig,
+                "{} is a whisper model, use WhisperRecognizer::from_dir",
+                path.display()
+            )
+        }
+
+        let transducer = (
+            dir.onnx("encoder")?,
+            dir.onnx("decoder")?,
+            dir.onnx("joiner")?,
+        );
+        let model = if let (Some(encoder), Some(decoder), Some(joiner)) = transducer {
+            OfflineModelConfig::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            }
+        } else if let Some(model) = dir.onnx("model")? {
+            if name.contains("paraformer") {
+                OfflineModelConfig::Paraformer { model }
+            } else if name.contains("sense-voice") || name.contains("sense_voice") {
+                OfflineModelConfig::SenseVoice {
+                    model,
+                    language: "auto".into(),
+                    use_itn: true,
+                }
+            } else if name.contains("nemo") && name.contains("ctc") {
+                OfflineModelConfig::NemoCtc { model }
+            } else {
+                bail!(
+                    InvalidConfig,
+                    "can't tell the model family of {} from its name, expected it to contain paraformer, sense-voice or nemo-ctc. {}",
+                    path.display(),
+                    dir.found()
+                )
+            }
+        } else {
+            bail!(
+                ModelLoad,
+                "no recognizer model in {}. {}",
+                path.display(),
+                dir.found()
+            )
+        };
+
+        Self::builder(model, dir.tokens()?).build()
+    }
+
+    /// Safe to call from several threads at once, each call decodes its own stream.
+    /// See `RecognizerPool` to bound the number of concurrent calls.
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> Result<OfflineRecognizerResult> {
+        let count = sample_count(&samples)?;
+        unsafe {
+            let stream = sherpa_rs_sys::SherpaOnnxCreateOfflineStream(self.recognizer);
+            sherpa_rs_sys::SherpaOnnxAcceptWaveformOffline(
+                stream,
+                sample_rate,
+                samples.as_ptr(),
+                count,
+            );
+            logging::infer("offline recognizer", || {
+                sherpa_rs_sys::SherpaOnnxDecodeOfflineStream(self.recognizer, stream)
+            });
+            let result_ptr = sherpa_rs_sys::SherpaOnnxGetOfflineStreamResult(stream);
+            let raw_result = result_ptr.read();
+            let count = raw_result.count.max(0) as usize;
+
+            let result = (|| {
+                let text = cstr_to_string!(raw_result.text)?;
+                let tokens = if raw_result.tokens_arr.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(raw_result.tokens_arr, count)
+                        .iter()
+                        .map(|&token| cstr_to_string!(token))
+                        .collect::<std::result::Result<_, _>>()?
+                };
+                let timestamps = if raw_result.timestamps.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(raw_result.timestamps, count).to_vec()
+                };
+                let lang = if raw_result.lang.is_null() {
+                    String::new()
+                } else {
+                    cstr_to_string!(raw_result.lang)?
+                };
+                Ok(OfflineRecognizerResult {
+                    text,
+                    tokens,
+                    timestamps,
+                    lang,
+                })
+            })();
+            // Free
+            sherpa_rs_sys::SherpaOnnxDestroyOfflineRecognizerResult(result_ptr);
+            sherpa_rs_sys::SherpaOnnxDestroyOfflineStream(stream);
+            result
+        }
+    }
+}
+
+unsafe impl Send for OfflineRecognizer {}
+unsafe impl Sync for OfflineRecognizer {}
+
+impl Drop for OfflineRecognizer {
+    fn drop(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxDestroyOfflineRecognizer(self.recognizer);
+        }
+    }
+}
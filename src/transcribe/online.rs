@@ -0,0 +1,418 @@
+use crate::{
+    bail, check_file, cstr, cstr_to_string, logging, model_dir::ModelDir, path_to_cstring,
+    provider, Provider, Result,
+};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+};
+
+/// Silence appended by `transcribe` so the last words are decoded
+const TAIL_PADDING_SECONDS: f32 = 0.66;
+
+/// Streaming model families
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum OnlineModelConfig {
+    Transducer {
+        encoder: PathBuf,
+        decoder: PathBuf,
+        joiner: PathBuf,
+    },
+    Paraformer {
+        encoder: PathBuf,
+        decoder: PathBuf,
+    },
+    Zipformer2Ctc {
+        model: PathBuf,
+    },
+}
+
+#[derive(Debug)]
+pub struct OnlineRecognizer {
+    recognizer: *const sherpa_rs_sys::SherpaOnnxOnlineRecognizer,
+}
+
+/// Audio fed to an `OnlineRecognizer` chunk by chunk
+#[derive(Debug)]
+pub struct OnlineStream<'a> {
+    recognizer: &'a OnlineRecognizer,
+    stream: *const sherpa_rs_sys::SherpaOnnxOnlineStream,
+}
+
+#[derive(Debug, Clone)]
+pub struct OnlineRecognizerResult {
+    pub text: String,
+    pub tokens: Vec<String>,
+    /// Start time in seconds of each token
+    pub timestamps: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnlineRecognizerBuilder {
+    model: OnlineModelConfig,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+}
+
+fn default_num_threads() -> i32 {
+    2
+}
+
+impl OnlineRecognizerBuilder {
+    pub fn new<P: Into<PathBuf>>(model: OnlineModelConfig, tokens: P) -> Self {
+        Self {
+            model,
+            tokens: tokens.into(),
+            debug: false,
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+        }
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn build(self) -> Result<OnlineRecognizer> {
+        self.model.check_files()?;
+        check_file(&self.tokens)?;
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        OnlineRecognizer::from_builder(self)
+    }
+}
+
+impl OnlineModelConfig {
+    /// Model and tokens of a sherpa-onnx streaming release directory
+    pub(crate) fn detect(path: &Path) -> Result<(Self, PathBuf)> {
+        let dir = ModelDir::open(path)?;
+        let name = dir.name();
+
+        let encoder = dir.onnx("encoder")?;
+        let decoder = dir.onnx("decoder")?;
+        let model = match (encoder, decoder, dir.onnx("joiner")?) {
+            (Some(encoder), Some(decoder), Some(joiner)) => Self::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            },
+            (Some(encoder), Some(decoder), None) if name.contains("paraformer") => {
+                Self::Paraformer {
+                    encoder,
+                    decoder,
+                }
+            }
+            (Some(_), Some(_), None) => bail!(
+                InvalidConfig,
+                "found an encoder and decoder but no joiner in {}, expected the directory name to contain paraformer. {}",
+                path.display(),
+                dir.found()
+            ),
+            _ => match dir.onnx("model")? {
+                Some(model) if name.contains("ctc") => {
+                    Self::Zipformer2Ctc { model }
+                }
+                Some(_) => bail!(
+                    InvalidConfig,
+                    "can't tell the model family of {} from its name, expected it to contain ctc. {}",
+                    path.display(),
+                    dir.found()
+                ),
+                None => bail!(
+                    ModelLoad,
+                    "no streaming recognizer model in {}. {}",
+                    path.display(),
+                    dir.found()
+                ),
+            },
+        };
+
+        Ok((model, dir.tokens()?))
+    }
+
+    pub(crate) fn check_files(&self) -> Result<()> {
+        let files = match self {
+            Self::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            } => vec![encoder, decoder, joiner],
+            Self::Paraformer { encoder, decoder } => vec![encoder, decoder],
+            Self::Zipformer2Ctc { model } => vec![model],
+        };
+        for file in files {
+            check_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// Model part of the recognizer and keyword spotter configs
+    pub(crate) fn into_sys(
+        self,
+        tokens: &Path,
+        debug: bool,
+        num_threads: i32,
+    ) -> Result<sherpa_rs_sys::SherpaOnnxOnlineModelConfig> {
+        let tokens_c = path_to_cstring(tokens)?;
+        let debug = if debug { 1 } else { 0 };
+
+        let mut model_config = sherpa_rs_sys::SherpaOnnxOnlineModelConfig {
+            tokens: tokens_c.into_raw(),
+            num_threads,
+            debug,
+            // The provider is set by `provider::create`. Models and options this
+            // crate doesn't set stay at their C API defaults
+            ..unsafe { std::mem::zeroed() }
+        };
+        match self {
+            OnlineModelConfig::Transducer {
+                encoder,
+                decoder,
+                joiner,
+            } => {
+                let transducer = &mut model_config.transducer;
+                transducer.encoder = path_to_cstring(&encoder)?.into_raw();
+                transducer.decoder = path_to_cstring(&decoder)?.into_raw();
+                transducer.joiner = path_to_cstring(&joiner)?.into_raw();
+            }
+            OnlineModelConfig::Paraformer { encoder, decoder } => {
+                let paraformer = &mut model_config.paraformer;
+                paraformer.encoder = path_to_cstring(&encoder)?.into_raw();
+                paraformer.decoder = path_to_cstring(&decoder)?.into_raw();
+            }
+            OnlineModelConfig::Zipformer2Ctc { model } => {
+                model_config.zipformer2_ctc.model = path_to_cstring(&model)?.into_raw();
+            }
+        }
+        Ok(model_config)
+    }
+}
+
+impl OnlineRecognizer {
+    pub fn builder<P: Into<PathBuf>>(
+        model: OnlineModelConfig,
+        tokens: P,
+    ) -> OnlineRecognizerBuilder {
+        OnlineRecognizerBuilder::new(model, tokens)
+    }
+
+    fn from_builder(builder: OnlineRecognizerBuilder) -> Result<Self> {
+        let model_config =
+            builder
+                .model
+                .into_sys(&builder.tokens, builder.debug, builder.num_threads)?;
+        let decoding_method_c = cstr!("greedy_search");
+        let mut config = sherpa_rs_sys::SherpaOnnxOnlineRecognizerConfig {
+            feat_config: sherpa_rs_sys::SherpaOnnxFeatureConfig {
+                sample_rate: 16000,
+                feature_dim: 80,
+            },
+            model_config,
+            decoding_method: decoding_method_c.into_raw(),
+            max_active_paths: 4,
+            // sherpa-onnx defaults
+            enable_endpoint: 1,
+            rule1_min_trailing_silence: 2.4,
+            rule2_min_trailing_silence: 1.2,
+            rule3_min_utterance_length: 20.0,
+            ..unsafe { std::mem::zeroed() }
+        };
+        let recognizer = provider::create(&builder.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.model_config.provider = provider_c.as_ptr();
+            Ok(logging::load("online recognizer", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateOnlineRecognizer(&config)
+            }))
+        })?;
+        if recognizer.is_null() {
+            bail!(ModelLoad, "failed to create online recognizer")
+        }
+
+        Ok(Self { recognizer })
+    }
+
+    /// Detect the model family and files of a sherpa-onnx streaming release directory.
+    ///
+    /// Transducers are recognized by their joiner. Paraformer and zipformer2 CTC
+    /// models are told apart by the directory name.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let (model, tokens) = OnlineModelConfig::detect(dir.as_ref())?;
+        Self::builder(model, tokens).build()
+    }
+
+    pub fn create_stream(&self) -> OnlineStream<'_> {
+        let stream = unsafe { sherpa_rs_sys::SherpaOnnxCreateOnlineStream(self.recognizer) };
+        OnlineStream {
+            recognizer: self,
+            stream,
+        }
+    }
+
+    /// Decode a whole recording at once. Like `create_stream`, safe to call from several threads.
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> Result<OnlineRecognizerResult> {
+        let mut stream = self.create_stream();
+        stream.accept_waveform(sample_rate, &samples);
+        let padding = vec![0.0; (TAIL_PADDING_SECONDS * sample_rate as f32) as usize];
+        stream.accept_waveform(sample_rate, &padding);
+        stream.input_finished();
+        stream.decode();
+        stream.result()
+    }
+}
+
+impl OnlineStream<'_> {
+    pub fn accept_waveform(&mut self, sample_rate: i32, samples: &[f32]) {
+        // The C API counts samples with an i32, a stream can take them in parts
+        for part in samples.chunks(i32::MAX as usize) {
+            unsafe {
+                sherpa_rs_sys::SherpaOnnxOnlineStreamAcceptWaveform(
+                    self.stream,
+                    sample_rate,
+                    part.as_ptr(),
+                    part.len() as i32,
+                );
+            }
+        }
+    }
+
+    /// No more audio will be accepted, the remaining frames can be decoded
+    pub fn input_finished(&mut self) {
+        unsafe { sherpa_rs_sys::SherpaOnnxOnlineStreamInputFinished(self.stream) }
+    }
+
+    /// Decode all the frames that are ready
+    pub fn decode(&mut self) {
+        logging::infer("online recognizer", || unsafe {
+            while sherpa_rs_sys::SherpaOnnxIsOnlineStreamReady(
+                self.recognizer.recognizer,
+                self.stream,
+            ) == 1
+            {
+                sherpa_rs_sys::SherpaOnnxDecodeOnlineStream(
+                    self.recognizer.recognizer,
+                    self.stream,
+                );
+            }
+        })
+    }
+
+    /// Text decoded since the last reset
+    pub fn result(&self) -> Result<OnlineRecognizerResult> {
+        unsafe {
+            let result_ptr = sherpa_rs_sys::SherpaOnnxGetOnlineStreamResult(
+                self.recognizer.recognizer,
+                self.stream,
+            );
+            let raw_result = result_ptr.read();
+            let count = raw_result.count.max(0) as usize;
+
+            let result = (|| {
+                let text = cstr_to_string!(raw_result.text)?;
+                let tokens = if raw_result.tokens_arr.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(raw_result.tokens_arr, count)
+                        .iter()
+                        .map(|&token| cstr_to_string!(token))
+                        .collect::<std::result::Result<_, _>>()?
+                };
+                let timestamps = if raw_result.timestamps.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(raw_result.timestamps, count).to_vec()
+                };
+                Ok(OnlineRecognizerResult {
+                    text,
+                    tokens,
+                    timestamps,
+                })
+            })();
+            // Free
+            sherpa_rs_sys::SherpaOnnxDestroyOnlineRecognizerResult(result_ptr);
+            result
+        }
+    }
+
+    /// Whether trailing silence ended the current utterance
+    pub fn is_endpoint(&self) -> bool {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxOnlineStreamIsEndpoint(self.recognizer.recognizer, self.stream)
+                == 1
+        }
+    }
+
+    /// Start a new utterance, usually after an endpoint
+    pub fn reset(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxOnlineStreamReset(self.recognizer.recognizer, self.stream)
+        }
+    }
+}
+
+unsafe impl Send for OnlineRecognizer {}
+// Streams hold the decoding state, the recognizer only holds the model
+unsafe impl Sync for OnlineRecognizer {}
+// A stream must not be decoded from two threads at once, `&mut self` ensures it
+unsafe impl Send for OnlineStream<'_> {}
+
+impl Drop for OnlineStream<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxDestroyOnlineStream(self.stream);
+        }
+    }
+}
+
+impl Drop for OnlineRecognizer {
+    fn drop(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxDestroyOnlineRecognizer(self.recognizer);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! One recognizer shared between threads, with a bound on concurrent calls.
+//!
+//! Recognizers can decode concurrently, but every call runs on the ONNX Runtime
+//! threads of its session, so many concurrent calls on one recognizer oversubscribe
+//! the CPU. A pool loads the model once and lets at most `size` callers use it at
+//! a time, which bounds concurrency to `size * num_threads`.
+
+use crate::{bail, Result};
+use std::{
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+#[derive(Debug)]
+pub struct RecognizerPool<R> {
+    recognizer: Arc<R>,
+    /// Calls that may start right now
+    permits: Mutex<usize>,
+    available: Condvar,
+    size: usize,
+}
+
+/// Permission to use the recognizer of a `RecognizerPool`, given back on drop
+#[derive(Debug)]
+pub struct PooledRecognizer<'a, R> {
+    pool: &'a RecognizerPool<R>,
+}
+
+impl<R> RecognizerPool<R> {
+    /// Share `recognizer` between at most `size` concurrent callers, for example
+    /// `RecognizerPool::new(WhisperRecognizer::builder(..).num_threads(2).build()?, 4)`
+    pub fn new(recognizer: impl Into<Arc<R>>, size: usize) -> Result<Self> {
+        if size == 0 {
+            bail!(InvalidConfig, "pool size must be at least 1")
+        }
+        Ok(Self {
+            recognizer: recognizer.into(),
+            permits: Mutex::new(size),
+            available: Condvar::new(),
+            size,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Calls that could start right now without waiting
+    pub fn idle(&self) -> usize {
+        *self.lock()
+    }
+
+    /// The shared recognizer, for calls that don't need to be bounded
+    pub fn recognizer(&self) -> &Arc<R> {
+        &self.recognizer
+    }
+
+    /// Wait until fewer than `size` callers use the recognizer
+    pub fn get(&self) -> PooledRecognizer<'_, R> {
+        let permits = self.lock();
+        let mut permits = self
+            .available
+            .wait_while(permits, |permits| *permits == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        *permits -= 1;
+        PooledRecognizer { pool: self }
+    }
+
+    /// Wait at most `timeout` for the recognizer
+    pub fn get_timeout(&self, timeout: Duration) -> Option<PooledRecognizer<'_, R>> {
+        let permits = self.lock();
+        let (mut permits, _) = self
+            .available
+            .wait_timeout_while(permits, timeout, |permits| *permits == 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        self.acquire(&mut permits)
+    }
+
+    /// The recognizer if fewer than `size` callers use it, without waiting
+    pub fn try_get(&self) -> Option<PooledRecognizer<'_, R>> {
+        self.acquire(&mut self.lock())
+    }
+
+    fn acquire(&self, permits: &mut usize) -> Option<PooledRecognizer<'_, R>> {
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(PooledRecognizer { pool: self })
+    }
+
+    // The lock only guards a counter, a panic while holding it can't leave it inconsistent
+    fn lock(&self) -> MutexGuard<'_, usize> {
+        self.permits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<R> Deref for PooledRecognizer<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.pool.recognizer
+    }
+}
+
+impl<R> Drop for PooledRecognizer<'_, R> {
+    fn drop(&mut self) {
+        *self.pool.lock() += 1;
+        self.pool.available.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn rejects_empty_pool() {
+        let error = RecognizerPool::new(0usize, 0).unwrap_err();
+        assert!(matches!(error, crate::Error::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn shares_one_recognizer() {
+        let recognizer = Arc::new(7usize);
+        let pool = RecognizerPool::new(recognizer.clone(), 2).unwrap();
+        let first = pool.try_get().unwrap();
+        let second = pool.try_get().unwrap();
+        assert!(std::ptr::eq(&*first, &*second));
+        assert!(Arc::ptr_eq(pool.recognizer(), &recognizer));
+        assert_eq!(*first, 7);
+        assert_eq!(pool.idle(), 0);
+        assert!(pool.try_get().is_none());
+        assert!(pool.get_timeout(Duration::from_millis(10)).is_none());
+
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+        assert!(pool.try_get().is_some());
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[test]
+    fn get_waits_for_a_release() {
+        let pool = RecognizerPool::new(1usize, 1).unwrap();
+        let held = pool.get();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| *pool.get());
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!waiter.is_finished());
+            drop(held);
+            assert_eq!(waiter.join().unwrap(), 1);
+        });
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn get_timeout_returns_once_released() {
+        let pool = RecognizerPool::new(1usize, 1).unwrap();
+        let held = pool.get();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.get_timeout(Duration::from_secs(10)).is_some());
+            std::thread::sleep(Duration::from_millis(20));
+            drop(held);
+            assert!(waiter.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn bounds_concurrent_use() {
+        let pool = RecognizerPool::new(0usize, 3).unwrap();
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..12 {
+                scope.spawn(|| {
+                    let _recognizer = pool.get();
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(5));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(pool.idle(), 3);
+    }
+}
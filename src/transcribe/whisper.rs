@@ -1,7 +1,13 @@
-use crate::{cstr, get_default_provider};
+use crate::{
+    bail, check_file, cstr, cstr_to_string, logging,
+    model_dir::ModelDir,
+    path_to_cstring,
+    provider::{self, parse_provider, Provider},
+    sample_count, Result,
+};
 use std::{
-    ffi::{CStr, CString},
-    ptr::null,
+    ffi::CString,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug)]
@@ -15,7 +21,163 @@ pub struct WhisperRecognizerResult {
     // pub timestamps: Vec<f32>,
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhisperRecognizerBuilder {
+    encoder: PathBuf,
+    decoder: PathBuf,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    language: String,
+    #[cfg_attr(feature = "serde", serde(default = "default_task"))]
+    task: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    tail_paddings: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    bpe_vocab: Option<PathBuf>,
+}
+
+fn default_task() -> String {
+    "transcribe".into()
+}
+
+fn default_num_threads() -> i32 {
+    2
+}
+
+impl WhisperRecognizerBuilder {
+    pub fn new<P: Into<PathBuf>>(encoder: P, decoder: P, tokens: P) -> Self {
+        Self {
+            encoder: encoder.into(),
+            decoder: decoder.into(),
+            tokens: tokens.into(),
+            language: String::new(),
+            task: default_task(),
+            tail_paddings: 0,
+            debug: false,
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+            bpe_vocab: None,
+        }
+    }
+
+    /// Language code such as `en`, empty to let whisper detect it
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// `transcribe` or `translate` (to English)
+    pub fn task(mut self, task: impl Into<String>) -> Self {
+        self.task = task.into();
+        self
+    }
+
+    pub fn tail_paddings(mut self, tail_paddings: i32) -> Self {
+        self.tail_paddings = tail_paddings;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn bpe_vocab<P: Into<PathBuf>>(mut self, bpe_vocab: P) -> Self {
+        self.bpe_vocab = Some(bpe_vocab.into());
+        self
+    }
+
+    pub fn build(self) -> Result<WhisperRecognizer> {
+        check_file(&self.encoder)?;
+        check_file(&self.decoder)?;
+        check_file(&self.tokens)?;
+        if let Some(bpe_vocab) = &self.bpe_vocab {
+            check_file(bpe_vocab)?;
+        }
+        if self.task != "transcribe" && self.task != "translate" {
+            bail!(
+                InvalidConfig,
+                "task must be transcribe or translate, got {}",
+                self.task
+            )
+        }
+        if self.tail_paddings < 0 {
+            bail!(
+                InvalidConfig,
+                "tail_paddings can't be negative, got {}",
+                self.tail_paddings
+            )
+        }
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        WhisperRecognizer::from_builder(self)
+    }
+}
+
 impl WhisperRecognizer {
+    pub fn builder<P: Into<PathBuf>>(
+        encoder: P,
+        decoder: P,
+        tokens: P,
+    ) -> WhisperRecognizerBuilder {
+        WhisperRecognizerBuilder::new(encoder, decoder, tokens)
+    }
+
+    /// Detect the encoder, decoder and tokens of a sherpa-onnx whisper release,
+    /// such as `sherpa-onnx-whisper-tiny`
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref();
+        let dir = ModelDir::open(path)?;
+        let (Some(encoder), Some(decoder)) = (dir.onnx("encoder")?, dir.onnx("decoder")?) else {
+            bail!(
+                ModelLoad,
+                "no whisper encoder and decoder in {}. {}",
+                path.display(),
+                dir.found()
+            )
+        };
+        Self::builder(encoder, decoder, dir.tokens()?).build()
+    }
+
+    #[deprecated(note = "use `WhisperRecognizer::builder` instead")]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         decoder: String,
         encoder: String,
@@ -25,102 +187,101 @@ impl WhisperRecognizer {
         provider: Option<String>,
         num_threads: Option<i32>,
         bpe_vocab: Option<String>,
-    ) -> Self {
-        let decoder_c = cstr!(decoder);
-        let encoder_c = cstr!(encoder);
-        let langauge_c = cstr!(language);
-        let task_c = cstr!("transcribe".to_string());
-        let tail_paddings = 0;
-        let tokens_c = cstr!(tokens);
-
-        let debug = debug.unwrap_or_default();
-        let debug = if debug { 1 } else { 0 };
-        let provider = provider.unwrap_or(get_default_provider());
-        let provider_c = cstr!(provider);
-        let num_threads = num_threads.unwrap_or(2);
-        let bpe_vocab = bpe_vocab.unwrap_or("".into());
-        let bpe_vocab_c = cstr!(bpe_vocab);
+    ) -> Result<Self> {
+        let mut builder = Self::builder(encoder, decoder, tokens)
+            .language(language)
+            .debug(debug.unwrap_or_default())
+            .num_threads(num_threads.unwrap_or(default_num_threads()));
+        builder.providers = parse_provider(provider).into_iter().collect();
+        builder.bpe_vocab = bpe_vocab.map(PathBuf::from);
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: WhisperRecognizerBuilder) -> Result<Self> {
+        let decoder_c = path_to_cstring(&builder.decoder)?;
+        let encoder_c = path_to_cstring(&builder.encoder)?;
+        let language_c = cstr!(builder.language);
+        let task_c = cstr!(builder.task);
+        let tail_paddings = builder.tail_paddings;
+        let tokens_c = path_to_cstring(&builder.tokens)?;
+
+        let debug = if builder.debug { 1 } else { 0 };
+        let num_threads = builder.num_threads;
+        let bpe_vocab_c = match &builder.bpe_vocab {
+            Some(bpe_vocab) => path_to_cstring(bpe_vocab)?,
+            None => cstr!(""),
+        };
 
         let whisper = sherpa_rs_sys::SherpaOnnxOfflineWhisperModelConfig {
             decoder: decoder_c.into_raw(),
             encoder: encoder_c.into_raw(),
-            language: langauge_c.into_raw(),
+            language: language_c.into_raw(),
             task: task_c.into_raw(),
             tail_paddings,
         };
 
-        let sense_voice_model_c = cstr!("".to_string());
-        let sense_voice_language_c = cstr!("".to_string());
-        let sense_voice = sherpa_rs_sys::SherpaOnnxOfflineSenseVoiceModelConfig {
-            model: sense_voice_model_c.into_raw(),
-            language: sense_voice_language_c.into_raw(),
-            use_itn: 0,
-        };
-
         let model_config = sherpa_rs_sys::SherpaOnnxOfflineModelConfig {
             bpe_vocab: bpe_vocab_c.into_raw(),
             debug,
-            model_type: null(),
-            modeling_unit: null(),
-            nemo_ctc: sherpa_rs_sys::SherpaOnnxOfflineNemoEncDecCtcModelConfig { model: null() },
             num_threads,
-            paraformer: sherpa_rs_sys::SherpaOnnxOfflineParaformerModelConfig { model: null() },
-            provider: provider_c.into_raw(),
-            tdnn: sherpa_rs_sys::SherpaOnnxOfflineTdnnModelConfig { model: null() },
-            telespeech_ctc: null(),
             tokens: tokens_c.into_raw(),
-            transducer: sherpa_rs_sys::SherpaOnnxOfflineTransducerModelConfig {
-                encoder: null(),
-                decoder: null(),
-                joiner: null(),
-            },
             whisper,
-            sense_voice,
+            // The provider is set by `provider::create`. Models and options this
+            // crate doesn't set stay at their C API defaults
+            ..unsafe { std::mem::zeroed() }
         };
-        let decoding_method_c = CString::new("greedy_search").unwrap();
-        let config = sherpa_rs_sys::SherpaOnnxOfflineRecognizerConfig {
+        let decoding_method_c = cstr!("greedy_search");
+        let mut config = sherpa_rs_sys::SherpaOnnxOfflineRecognizerConfig {
             decoding_method: decoding_method_c.into_raw(), // greedy_search, modified_beam_search
             feat_config: sherpa_rs_sys::SherpaOnnxFeatureConfig {
                 sample_rate: 16000,
                 feature_dim: 512,
             },
-            hotwords_file: null(),
-            hotwords_score: 0.0,
-            lm_config: sherpa_rs_sys::SherpaOnnxOfflineLMConfig {
-                model: null(),
-                scale: 0.0,
-            },
-            max_active_paths: 0,
             model_config,
-            rule_fars: null(),
-            rule_fsts: null(),
+            ..unsafe { std::mem::zeroed() }
         };
-        let recognizer = unsafe { sherpa_rs_sys::SherpaOnnxCreateOfflineRecognizer(&config) };
+        let recognizer = provider::create(&builder.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.model_config.provider = provider_c.as_ptr();
+            Ok(logging::load("whisper", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateOfflineRecognizer(&config)
+            }))
+        })?;
+        if recognizer.is_null() {
+            bail!(ModelLoad, "failed to create whisper recognizer")
+        }
 
-        Self { recognizer }
+        Ok(Self { recognizer })
     }
 
-    pub fn transcribe(&mut self, sample_rate: i32, samples: Vec<f32>) -> WhisperRecognizerResult {
+    /// Safe to call from several threads at once, each call decodes its own stream.
+    /// See `RecognizerPool` to bound the number of concurrent calls.
+    pub fn transcribe(
+        &self,
+        sample_rate: i32,
+        samples: Vec<f32>,
+    ) -> Result<WhisperRecognizerResult> {
+        let count = sample_count(&samples)?;
         unsafe {
             let stream = sherpa_rs_sys::SherpaOnnxCreateOfflineStream(self.recognizer);
             sherpa_rs_sys::SherpaOnnxAcceptWaveformOffline(
                 stream,
                 sample_rate,
                 samples.as_ptr(),
-                samples.len().try_into().unwrap(),
+                count,
             );
-            sherpa_rs_sys::SherpaOnnxDecodeOfflineStream(self.recognizer, stream);
+            logging::infer("whisper", || {
+                sherpa_rs_sys::SherpaOnnxDecodeOfflineStream(self.recognizer, stream)
+            });
             let result_ptr = sherpa_rs_sys::SherpaOnnxGetOfflineStreamResult(stream);
             let raw_result = result_ptr.read();
-            let text = CStr::from_ptr(raw_result.text);
-            let text = text.to_str().unwrap().to_string();
+            let text = cstr_to_string!(raw_result.text);
             // let timestamps: &[f32] =
             // std::slice::from_raw_parts(raw_result.timestamps, raw_result.count as usize);
-            let result = WhisperRecognizerResult { text };
             // Free
             sherpa_rs_sys::SherpaOnnxDestroyOfflineRecognizerResult(result_ptr);
             sherpa_rs_sys::SherpaOnnxDestroyOfflineStream(stream);
-            return result;
+            Ok(WhisperRecognizerResult { text: text? })
         }
     }
 }
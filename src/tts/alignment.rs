@@ -0,0 +1,325 @@
+//! Word timings for synthesized speech.
+//!
+//! The sherpa-onnx C API doesn't expose the durations predicted by TTS models,
+//! so timings are recovered from the generated audio instead: either estimated
+//! from the length of each word, or by recognizing the audio with an
+//! `OfflineRecognizer` that reports token timestamps and aligning the result to the input text.
+
+use super::TtsSample;
+#[cfg(feature = "asr")]
+use crate::Result;
+#[cfg(feature = "asr")]
+use crate::{audio, transcribe::offline::OfflineRecognizer};
+
+/// Recognizers are trained on 16kHz audio
+#[cfg(feature = "asr")]
+const RECOGNIZER_SAMPLE_RATE: u32 = 16000;
+
+/// Fraction of the peak amplitude below which leading and trailing audio counts as silence
+const SILENCE_THRESHOLD: f32 = 0.02;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordTiming {
+    /// Word from the input text
+    pub word: String,
+    /// Start in seconds
+    pub start: f32,
+    /// End in seconds
+    pub end: f32,
+}
+
+/// Spread the words of `text` over the voiced part of the sample, proportionally to their length.
+pub fn estimate_word_timings(sample: &TtsSample, text: &str) -> Vec<WordTiming> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (start, end) = voiced_range(sample);
+    distribute(&words, start, end)
+        .into_iter()
+        .zip(words)
+        .map(|((start, end), word)| WordTiming {
+            word: word.to_string(),
+            start,
+            end,
+        })
+        .collect()
+}
+
+/// Recognize the sample and align the recognized words back to `text`.
+/// Words the recognizer missed are interpolated between their neighbours.
+#[cfg(feature = "asr")]
+pub fn align_word_timings(
+    sample: &TtsSample,
+    text: &str,
+    recognizer: &OfflineRecognizer,
+) -> Result<Vec<WordTiming>> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (voiced_start, voiced_end) = voiced_range(sample);
+
+    let samples = audio::resample(
+        &sample.samples,
+        sample.sample_rate as u32,
+        RECOGNIZER_SAMPLE_RATE,
+    );
+    let result = recognizer.transcribe(RECOGNIZER_SAMPLE_RATE as i32, samples)?;
+    let recognized = recognized_words(&result.tokens, &result.timestamps, voiced_end);
+
+    let reference: Vec<String> = words.iter().map(|word| normalize(word)).collect();
+    let hypothesis: Vec<String> = recognized
+        .iter()
+        .map(|(word, ..)| normalize(word))
+        .collect();
+    let mut timings: Vec<Option<(f32, f32)>> = align(&reference, &hypothesis)
+        .into_iter()
+        .map(|matched| matched.map(|j| (recognized[j].1, recognized[j].2)))
+        .collect();
+
+    // Fill runs of unmatched words between the surrounding matches
+    let mut i = 0;
+    while i < timings.len() {
+        if timings[i].is_some() {
+            i += 1;
+            continue;
+        }
+        let gap_start = i;
+        while i < timings.len() && timings[i].is_none() {
+            i += 1;
+        }
+        let from = match gap_start {
+            0 => voiced_start,
+            _ => timings[gap_start - 1].map_or(voiced_start, |(_, end)| end),
+        };
+        let to = timings
+            .get(i)
+            .copied()
+            .flatten()
+            .map_or(voiced_end, |(start, _)| start);
+        let spread = distribute(&words[gap_start..i], from, to.max(from));
+        for (timing, span) in timings[gap_start..i].iter_mut().zip(spread) {
+            *timing = Some(span);
+        }
+    }
+
+    Ok(words
+        .into_iter()
+        .zip(timings)
+        .map(|(word, timing)| {
+            let (start, end) = timing.unwrap_or((voiced_start, voiced_end));
+            WordTiming {
+                word: word.to_string(),
+                start,
+                end,
+            }
+        })
+        .collect())
+}
+
+/// Join BPE pieces into words. A piece starting with `▁` or a space opens a new word.
+#[cfg(feature = "asr")]
+fn recognized_words(tokens: &[String], timestamps: &[f32], end: f32) -> Vec<(String, f32, f32)> {
+    let mut words: Vec<(String, f32, f32)> = Vec::new();
+    for (token, &start) in tokens.iter().zip(timestamps) {
+        let piece = token.trim_start_matches(['▁', ' ']);
+        let opens_word = words.is_empty() || piece.len() != token.len();
+        if opens_word {
+            if let Some(previous) = words.last_mut() {
+                previous.2 = start;
+            }
+            words.push((piece.to_string(), start, start));
+        } else if let Some(current) = words.last_mut() {
+            current.0.push_str(piece);
+        }
+    }
+    if let Some(last) = words.last_mut() {
+        last.2 = end.max(last.1);
+    }
+    words
+}
+
+#[cfg(feature = "asr")]
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Levenshtein alignment, returns the hypothesis index matched or substituted for each reference word
+#[cfg(feature = "asr")]
+fn align(reference: &[String], hypothesis: &[String]) -> Vec<Option<usize>> {
+    let (n, m) = (reference.len(), hypothesis.len());
+    let mut cost = vec![vec![0usize; m + 1]; n + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in cost[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let substitution =
+                cost[i - 1][j - 1] + usize::from(reference[i - 1] != hypothesis[j - 1]);
+            cost[i][j] = substitution.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut matches = vec![None; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 && j > 0 {
+        let substitution = cost[i - 1][j - 1] + usize::from(reference[i - 1] != hypothesis[j - 1]);
+        if cost[i][j] == substitution {
+            matches[i - 1] = Some(j - 1);
+            i -= 1;
+            j -= 1;
+        } else if cost[i][j] == cost[i - 1][j] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    matches
+}
+
+/// Split `start..end` between words proportionally to their character count
+fn distribute(words: &[&str], start: f32, end: f32) -> Vec<(f32, f32)> {
+    let total: usize = words.iter().map(|word| word.chars().count()).sum();
+    let mut position = start;
+    words
+        .iter()
+        .map(|word| {
+            let share = match total {
+                0 => 1.0 / words.len() as f32,
+                _ => word.chars().count() as f32 / total as f32,
+            };
+            let word_start = position;
+            position += (end - start) * share;
+            (word_start, position)
+        })
+        .collect()
+}
+
+/// Start and end in seconds of the audio between leading and trailing silence
+fn voiced_range(sample: &TtsSample) -> (f32, f32) {
+    let peak = sample
+        .samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let threshold = peak * SILENCE_THRESHOLD;
+    let first = sample
+        .samples
+        .iter()
+        .position(|sample| sample.abs() > threshold);
+    let last = sample
+        .samples
+        .iter()
+        .rposition(|sample| sample.abs() > threshold);
+    let rate = sample.sample_rate as f32;
+    match (first, last) {
+        (Some(first), Some(last)) => (first as f32 / rate, (last + 1) as f32 / rate),
+        _ => (0.0, sample.duration),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(samples: Vec<f32>, sample_rate: i32) -> TtsSample {
+        let duration = samples.len() as f32 / sample_rate as f32;
+        TtsSample {
+            samples,
+            sample_rate,
+            duration,
+        }
+    }
+
+    #[test]
+    fn distribute_splits_by_character_count() {
+        let spans = distribute(&["a", "bbb"], 1.0, 3.0);
+        assert_eq!(spans, vec![(1.0, 1.5), (1.5, 3.0)]);
+        assert!(distribute(&[], 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn distribute_splits_empty_words_evenly() {
+        let spans = distribute(&["", ""], 0.0, 1.0);
+        assert_eq!(spans, vec![(0.0, 0.5), (0.5, 1.0)]);
+    }
+
+    #[test]
+    fn voiced_range_skips_leading_and_trailing_silence() {
+        let mut samples = vec![0.0; 10];
+        samples[2] = 0.5;
+        samples[6] = -1.0;
+        samples[8] = 0.01;
+        assert_eq!(voiced_range(&sample(samples, 10)), (0.2, 0.7));
+    }
+
+    #[test]
+    fn voiced_range_of_silence_is_the_whole_sample() {
+        assert_eq!(voiced_range(&sample(vec![0.0; 20], 10)), (0.0, 2.0));
+        assert_eq!(voiced_range(&sample(Vec::new(), 10)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn estimates_timings_over_the_voiced_range() {
+        let mut samples = vec![0.0; 8];
+        samples[2..6].fill(0.5);
+        let timings = estimate_word_timings(&sample(samples, 8), "ab cd");
+        let spans: Vec<_> = timings
+            .iter()
+            .map(|t| (t.word.as_str(), t.start, t.end))
+            .collect();
+        assert_eq!(spans, vec![("ab", 0.25, 0.5), ("cd", 0.5, 0.75)]);
+    }
+
+    #[cfg(feature = "asr")]
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn recognized_words_join_pieces() {
+        let tokens = strings(&["▁hel", "lo", " wor", "ld", "▁!"]);
+        let words = recognized_words(&tokens, &[0.1, 0.2, 0.5, 0.6, 0.9], 1.2);
+        assert_eq!(
+            words,
+            vec![
+                ("hello".to_string(), 0.1, 0.5),
+                ("world".to_string(), 0.5, 0.9),
+                ("!".to_string(), 0.9, 1.2),
+            ]
+        );
+        assert!(recognized_words(&[], &[], 1.0).is_empty());
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn recognized_words_end_no_earlier_than_they_start() {
+        let words = recognized_words(&strings(&["hi"]), &[0.8], 0.5);
+        assert_eq!(words, vec![("hi".to_string(), 0.8, 0.8)]);
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn align_matches_substitutes_and_skips() {
+        let reference = strings(&["the", "quick", "brown", "fox"]);
+        assert_eq!(
+            align(&reference, &strings(&["the", "quick", "brown", "fox"])),
+            vec![Some(0), Some(1), Some(2), Some(3)]
+        );
+        // `brown` was missed, `fox` was misheard and `um` inserted
+        assert_eq!(
+            align(&reference, &strings(&["um", "the", "quick", "box"])),
+            vec![Some(1), Some(2), None, Some(3)]
+        );
+        assert_eq!(align(&reference, &[]), vec![None; 4]);
+        assert!(align(&[], &reference).is_empty());
+    }
+
+    #[cfg(feature = "asr")]
+    #[test]
+    fn normalize_ignores_case_and_punctuation() {
+        assert_eq!(normalize("Don't!"), "dont");
+        assert_eq!(normalize("..."), "");
+    }
+}
@@ -0,0 +1,294 @@
+//! Re-synthesize a timed transcript into a single track.
+//!
+//! Each segment is synthesized with `OfflineTts` and fitted to its original slot:
+//! speech that runs long is first regenerated faster, up to `max_speed`, and then
+//! time-stretched, speech that runs short is padded with silence. The fitted
+//! segments are mixed at their original start times, so the result lines up with
+//! the source video or recording.
+
+use super::{OfflineTts, TtsSample};
+use crate::{audio::time_stretch, bail, Result};
+
+/// A transcript segment, for example a `Vad` segment with its `WhisperRecognizer` text
+#[derive(Debug, Clone)]
+pub struct DubSegment {
+    /// Start time in seconds
+    pub start: f32,
+    /// End time in seconds
+    pub end: f32,
+    pub text: String,
+    pub sid: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct DubbingOptions {
+    /// Speed every segment is first synthesized at
+    pub speed: f32,
+    /// Fastest speed used to regenerate a segment before time-stretching it
+    pub max_speed: f32,
+    /// Length of the output in seconds, defaults to the end of the last segment
+    pub duration: Option<f32>,
+}
+
+impl Default for DubbingOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            max_speed: 1.5,
+            duration: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DubbedSegment {
+    pub text: String,
+    pub sid: i32,
+    /// First sample of the segment in the output
+    pub start: usize,
+    /// One past the last sample of the slot in the output
+    pub end: usize,
+    /// Speed the segment was synthesized at
+    pub speed: f32,
+    /// Synthesized length divided by the slot length, 1.0 when it wasn't stretched
+    pub stretch: f32,
+}
+
+#[derive(Debug)]
+pub struct DubbingSample {
+    pub sample: TtsSample,
+    pub segments: Vec<DubbedSegment>,
+}
+
+impl OfflineTts {
+    /// Synthesize `segments` and mix them into one track at their original timings.
+    /// Overlapping segments are summed.
+    pub fn dub(
+        &mut self,
+        segments: &[DubSegment],
+        options: &DubbingOptions,
+    ) -> Result<DubbingSample> {
+        let speeds_valid = options.speed > 0.0 && options.max_speed >= options.speed;
+        if !speeds_valid || !options.max_speed.is_finite() {
+            bail!(
+                InvalidConfig,
+                "invalid speeds {} and {}, expected 0 < speed <= max_speed",
+                options.speed,
+                options.max_speed
+            )
+        }
+        let sample_rate = self.sample_rate();
+        let slots = segments
+            .iter()
+            .map(|segment| slot(segment, sample_rate))
+            .collect::<Result<Vec<_>>>()?;
+        let duration = options.duration.unwrap_or_else(|| {
+            segments
+                .iter()
+                .map(|segment| segment.end)
+                .fold(0.0, f32::max)
+        });
+        if !(duration >= 0.0 && duration.is_finite()) {
+            bail!(InvalidConfig, "invalid duration {}", duration)
+        }
+        let mut samples = vec![0.0f32; to_sample(duration, sample_rate)];
+
+        let mut dubbed = Vec::new();
+        for (segment, (start, end)) in segments.iter().zip(slots) {
+            let fitted = fit(end - start, sample_rate, options, |speed| {
+                Ok(self
+                    .generate(segment.text.clone(), segment.sid, speed)?
+                    .samples)
+            })?;
+
+            if start < samples.len() {
+                for (mixed, sample) in samples[start..].iter_mut().zip(&fitted.samples) {
+                    *mixed += sample;
+                }
+            }
+            dubbed.push(DubbedSegment {
+                text: segment.text.clone(),
+                sid: segment.sid,
+                start,
+                end,
+                speed: fitted.speed,
+                stretch: fitted.stretch,
+            });
+        }
+
+        for sample in &mut samples {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        let duration = samples.len() as f32 / sample_rate as f32;
+        Ok(DubbingSample {
+            sample: TtsSample {
+                samples,
+                sample_rate,
+                duration,
+            },
+            segments: dubbed,
+        })
+    }
+}
+
+/// Synthesized segment, no longer than its slot
+struct Fitted {
+    samples: Vec<f32>,
+    speed: f32,
+    stretch: f32,
+}
+
+fn to_sample(seconds: f32, sample_rate: i32) -> usize {
+    (seconds * sample_rate as f32).round() as usize
+}
+
+/// First and one past the last sample of a segment, which must span at least one
+fn slot(segment: &DubSegment, sample_rate: i32) -> Result<(usize, usize)> {
+    if !(segment.start >= 0.0 && segment.end > segment.start && segment.end.is_finite()) {
+        bail!(
+            InvalidConfig,
+            "segment `{}` has invalid timings {}..{}",
+            segment.text,
+            segment.start,
+            segment.end
+        )
+    }
+    let start = to_sample(segment.start, sample_rate);
+    let end = to_sample(segment.end, sample_rate);
+    if end <= start {
+        bail!(
+            InvalidConfig,
+            "segment `{}` at {}..{} is shorter than one sample",
+            segment.text,
+            segment.start,
+            segment.end
+        )
+    }
+    Ok((start, end))
+}
+
+/// Synthesize with `generate` at the given speed, then regenerate faster and
+/// time-stretch until the audio fits in `slot` samples
+fn fit(
+    slot: usize,
+    sample_rate: i32,
+    options: &DubbingOptions,
+    mut generate: impl FnMut(f32) -> Result<Vec<f32>>,
+) -> Result<Fitted> {
+    let mut speed = options.speed;
+    let mut samples = generate(speed)?;
+    if samples.len() > slot && speed < options.max_speed {
+        // Speed scales the duration of the speech roughly linearly
+        let ratio = samples.len() as f32 / slot as f32;
+        speed = (speed * ratio).min(options.max_speed);
+        samples = generate(speed)?;
+    }
+
+    let mut stretch = 1.0;
+    if samples.len() > slot {
+        stretch = samples.len() as f32 / slot as f32;
+        samples = time_stretch(&samples, sample_rate as u32, slot);
+    }
+    Ok(Fitted {
+        samples,
+        speed,
+        stretch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: i32 = 16000;
+
+    fn segment(start: f32, end: f32) -> DubSegment {
+        DubSegment {
+            start,
+            end,
+            text: "hello".into(),
+            sid: 0,
+        }
+    }
+
+    /// Speech of `length` samples at speed 1, shorter when faster
+    fn speech(length: usize) -> impl FnMut(f32) -> Result<Vec<f32>> {
+        move |speed| Ok(vec![0.1; (length as f32 / speed).round() as usize])
+    }
+
+    #[test]
+    fn short_speech_is_kept() {
+        let fitted = fit(1000, SAMPLE_RATE, &DubbingOptions::default(), speech(800)).unwrap();
+        assert_eq!(fitted.samples.len(), 800);
+        assert_eq!(fitted.speed, 1.0);
+        assert_eq!(fitted.stretch, 1.0);
+    }
+
+    #[test]
+    fn long_speech_is_regenerated_faster() {
+        let fitted = fit(1000, SAMPLE_RATE, &DubbingOptions::default(), speech(1200)).unwrap();
+        assert!((fitted.speed - 1.2).abs() < 1e-6);
+        assert_eq!(fitted.samples.len(), 1000);
+        assert_eq!(fitted.stretch, 1.0);
+    }
+
+    #[test]
+    fn speech_beyond_max_speed_is_stretched() {
+        let fitted = fit(1000, SAMPLE_RATE, &DubbingOptions::default(), speech(3000)).unwrap();
+        assert_eq!(fitted.speed, 1.5);
+        assert!((fitted.stretch - 2.0).abs() < 1e-6);
+        assert_eq!(fitted.samples.len(), 1000);
+    }
+
+    #[test]
+    fn stretches_without_regenerating_at_max_speed() {
+        let options = DubbingOptions {
+            speed: 1.5,
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let fitted = fit(1000, SAMPLE_RATE, &options, |speed| {
+            calls += 1;
+            speech(1500)(speed)
+        })
+        .unwrap();
+        assert_eq!(calls, 1);
+        assert_eq!(fitted.samples.len(), 1000);
+    }
+
+    #[test]
+    fn slots_round_to_samples() {
+        assert_eq!(
+            slot(&segment(0.5, 1.0), SAMPLE_RATE).unwrap(),
+            (8000, 16000)
+        );
+    }
+
+    #[test]
+    fn rejects_slots_shorter_than_a_sample() {
+        let error = slot(&segment(1.0, 1.00001), SAMPLE_RATE).unwrap_err();
+        assert!(
+            error.to_string().contains("shorter than one sample"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_timings() {
+        for (start, end) in [
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (-1.0, 1.0),
+            (0.0, f32::INFINITY),
+            (f32::NAN, 1.0),
+        ] {
+            assert!(
+                slot(&segment(start, end), SAMPLE_RATE).is_err(),
+                "{}..{}",
+                start,
+                end
+            );
+        }
+    }
+}
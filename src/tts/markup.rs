@@ -0,0 +1,588 @@
+//! SSML-lite markup for `OfflineTts`.
+//!
+//! Supported tags:
+//! - `<speak>`, `<p>`, `<s>`: accepted and ignored
+//! - `<break time="500ms"/>` or `<break strength="strong"/>`: insert silence,
+//!   `<break time="1s"></break>` works too
+//! - `<prosody rate="1.2">` / `rate="120%"` / `rate="slow"`: multiply the speed
+//! - `<voice sid="3">`: speaker id for the enclosed text
+//! - `<say-as interpret-as="digits">123</say-as>`: read digits (or `characters`) one by one
+
+use super::{OfflineTts, TtsSample};
+use crate::{bail, Result};
+
+#[derive(Debug, Clone)]
+pub struct MarkupSegment {
+    pub text: String,
+    pub sid: i32,
+    pub speed: f32,
+    /// First sample of the segment in the combined output
+    pub start: usize,
+    /// One past the last sample of the segment in the combined output
+    pub end: usize,
+}
+
+#[derive(Debug)]
+pub struct MarkupSample {
+    pub sample: TtsSample,
+    pub segments: Vec<MarkupSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkupItem {
+    Text { text: String, sid: i32, speed: f32 },
+    Break { seconds: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    sid: i32,
+    speed: f32,
+    say_as: SayAs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SayAs {
+    Normal,
+    Digits,
+    Characters,
+}
+
+/// Split markup into text runs and breaks. Adjacent runs with the same
+/// speaker and speed are merged.
+pub fn parse_markup(markup: &str, sid: i32, speed: f32) -> Result<Vec<MarkupItem>> {
+    let mut items: Vec<MarkupItem> = Vec::new();
+    let mut stack: Vec<(String, Scope)> = Vec::new();
+    let mut scope = Scope {
+        sid,
+        speed,
+        say_as: SayAs::Normal,
+    };
+    let mut rest = markup;
+    let mut offset = 0;
+
+    while !rest.is_empty() {
+        // `<break>` may be written with a closing tag, but is always empty
+        let in_break = stack.last().is_some_and(|(open, _)| open == "break");
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        if in_break && !rest[..text_end].trim().is_empty() {
+            bail!(Parse, "<break> can't contain text, at byte {}", offset)
+        }
+        let Some(tag_start) = rest.find('<') else {
+            push_text(&mut items, rest, scope)?;
+            break;
+        };
+        push_text(&mut items, &rest[..tag_start], scope)?;
+        let Some(tag_len) = tag_end(&rest[tag_start..]) else {
+            bail!(Parse, "unclosed tag at byte {}", offset + tag_start)
+        };
+        let tag = rest[tag_start + 1..tag_start + tag_len].trim();
+        let position = offset + tag_start;
+        offset += tag_start + tag_len + 1;
+        rest = &rest[tag_start + tag_len + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            match stack.pop() {
+                Some((open, outer)) if open == name => scope = outer,
+                Some((open, _)) => {
+                    bail!(
+                        Parse,
+                        "expected </{}> but found </{}> at byte {}",
+                        open,
+                        name,
+                        position
+                    )
+                }
+                None => bail!(Parse, "unexpected </{}> at byte {}", name, position),
+            }
+            continue;
+        }
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        if in_break {
+            bail!(
+                Parse,
+                "<break> can't contain <{}>, at byte {}",
+                name,
+                position
+            )
+        }
+        let attributes = parse_attributes(attributes, position)?;
+        let attribute = |key: &str| {
+            attributes
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+
+        let mut inner = scope;
+        match name {
+            "speak" | "p" | "s" => {}
+            "break" => {
+                let seconds = match (attribute("time"), attribute("strength")) {
+                    (Some(time), _) => parse_time(time)?,
+                    (None, Some(strength)) => parse_strength(strength)?,
+                    (None, None) => parse_strength("medium")?,
+                };
+                items.push(MarkupItem::Break { seconds });
+            }
+            "prosody" => {
+                if let Some(rate) = attribute("rate") {
+                    inner.speed = scope.speed * parse_rate(rate)?;
+                }
+            }
+            "voice" => {
+                if let Some(sid) = attribute("sid") {
+                    inner.sid = match sid.parse() {
+                        Ok(sid) => sid,
+                        Err(_) => bail!(Parse, "invalid sid `{}` at byte {}", sid, position),
+                    };
+                }
+            }
+            "say-as" => {
+                inner.say_as = match attribute("interpret-as") {
+                    Some("digits") | Some("number_digits") => SayAs::Digits,
+                    Some("characters") | Some("spell-out") => SayAs::Characters,
+                    _ => SayAs::Normal,
+                };
+            }
+            _ => bail!(Parse, "unsupported tag <{}> at byte {}", name, position),
+        }
+        if !self_closing {
+            stack.push((name.to_string(), scope));
+            scope = inner;
+        }
+    }
+
+    if let Some((open, _)) = stack.pop() {
+        bail!(Parse, "missing </{}>", open)
+    }
+    Ok(items)
+}
+
+/// Offset of the `>` closing the tag at the start of `rest`, skipping quoted
+/// attribute values
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+fn push_text(items: &mut Vec<MarkupItem>, raw: &str, scope: Scope) -> Result<()> {
+    let text = unescape(raw)?;
+    let text = match scope.say_as {
+        SayAs::Normal => text,
+        SayAs::Digits => spell(&text, |c| c.is_ascii_digit()),
+        SayAs::Characters => spell(&text, |c| !c.is_whitespace()),
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return Ok(());
+    }
+    if let Some(MarkupItem::Text {
+        text: previous,
+        sid,
+        speed,
+    }) = items.last_mut()
+    {
+        if *sid == scope.sid && *speed == scope.speed {
+            previous.push(' ');
+            previous.push_str(&text);
+            return Ok(());
+        }
+    }
+    items.push(MarkupItem::Text {
+        text,
+        sid: scope.sid,
+        speed: scope.speed,
+    });
+    Ok(())
+}
+
+/// Separate the matching characters with spaces so they're read one by one
+fn spell(text: &str, matches: impl Fn(char) -> bool) -> String {
+    let mut spelled = String::new();
+    for c in text.chars() {
+        if matches(c) {
+            spelled.push(' ');
+            spelled.push(c);
+            spelled.push(' ');
+        } else {
+            spelled.push(c);
+        }
+    }
+    spelled
+}
+
+/// Replace the XML entities. An `&` that doesn't start an entity is kept as is.
+fn unescape(text: &str) -> Result<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let name_len = after
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(after.len());
+        if name_len == 0 || !after[name_len..].starts_with(';') {
+            unescaped.push('&');
+            rest = after;
+            continue;
+        }
+        let entity = &after[..name_len];
+        unescaped.push(match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => bail!(Parse, "unknown entity &{};", entity),
+        });
+        rest = &after[name_len + 1..];
+    }
+    unescaped.push_str(rest);
+    Ok(unescaped)
+}
+
+fn parse_attributes(mut raw: &str, position: usize) -> Result<Vec<(String, String)>> {
+    let mut attributes = Vec::new();
+    loop {
+        raw = raw.trim_start();
+        if raw.is_empty() {
+            return Ok(attributes);
+        }
+        let Some((key, value)) = raw.split_once('=') else {
+            bail!(Parse, "invalid attribute `{}` at byte {}", raw, position)
+        };
+        let value = value.trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            bail!(
+                Parse,
+                "unquoted attribute `{}` at byte {}",
+                key.trim(),
+                position
+            )
+        };
+        let Some(end) = value[1..].find(quote) else {
+            bail!(
+                Parse,
+                "unterminated attribute `{}` at byte {}",
+                key.trim(),
+                position
+            )
+        };
+        attributes.push((key.trim().to_string(), unescape(&value[1..end + 1])?));
+        raw = &value[end + 2..];
+    }
+}
+
+fn parse_time(time: &str) -> Result<f32> {
+    let time = time.trim();
+    let (number, scale) = if let Some(ms) = time.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = time.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        bail!(Parse, "break time `{}` must end with ms or s", time)
+    };
+    match number.trim().parse::<f32>() {
+        Ok(value) if value >= 0.0 && value.is_finite() => Ok(value * scale),
+        _ => bail!(Parse, "invalid break time `{}`", time),
+    }
+}
+
+fn parse_strength(strength: &str) -> Result<f32> {
+    Ok(match strength {
+        "none" => 0.0,
+        "x-weak" => 0.1,
+        "weak" => 0.25,
+        "medium" => 0.4,
+        "strong" => 0.75,
+        "x-strong" => 1.2,
+        _ => bail!(Parse, "invalid break strength `{}`", strength),
+    })
+}
+
+fn parse_rate(rate: &str) -> Result<f32> {
+    let rate = rate.trim();
+    let value = match rate {
+        "x-slow" => 0.5,
+        "slow" => 0.75,
+        "medium" | "default" => 1.0,
+        "fast" => 1.25,
+        "x-fast" => 1.5,
+        _ => {
+            let parsed = match rate.strip_suffix('%') {
+                Some(percent) => percent.trim().parse::<f32>().map(|p| p / 100.0),
+                None => rate.parse::<f32>(),
+            };
+            match parsed {
+                Ok(value) => value,
+                Err(_) => bail!(Parse, "invalid prosody rate `{}`", rate),
+            }
+        }
+    };
+    if value <= 0.0 || !value.is_finite() {
+        bail!(Parse, "prosody rate must be positive, got `{}`", rate)
+    }
+    Ok(value)
+}
+
+impl OfflineTts {
+    /// Synthesize SSML-lite markup (see module docs) into a single sample.
+    /// `sid` and `speed` are the defaults for text outside `<voice>` / `<prosody>`.
+    pub fn generate_markup(&mut self, markup: &str, sid: i32, speed: f32) -> Result<MarkupSample> {
+        let items = parse_markup(markup, sid, speed)?;
+        let sample_rate = self.sample_rate();
+        render(items, sample_rate, |text, sid, speed| {
+            self.generate(text.to_string(), sid, speed)
+        })
+    }
+}
+
+/// Join the synthesized text runs and the breaks, in order
+fn render(
+    items: Vec<MarkupItem>,
+    sample_rate: i32,
+    mut generate: impl FnMut(&str, i32, f32) -> Result<TtsSample>,
+) -> Result<MarkupSample> {
+    let mut samples = Vec::new();
+    let mut segments = Vec::new();
+    for item in items {
+        match item {
+            MarkupItem::Text { text, sid, speed } => {
+                let audio = generate(&text, sid, speed)?;
+                if audio.sample_rate != sample_rate {
+                    bail!(
+                        Native,
+                        "model generated {} Hz audio but reports {} Hz",
+                        audio.sample_rate,
+                        sample_rate
+                    )
+                }
+                let start = samples.len();
+                samples.extend_from_slice(&audio.samples);
+                segments.push(MarkupSegment {
+                    text,
+                    sid,
+                    speed,
+                    start,
+                    end: samples.len(),
+                });
+            }
+            MarkupItem::Break { seconds } => {
+                let silence = (seconds * sample_rate as f32).round() as usize;
+                samples.resize(samples.len() + silence, 0.0);
+            }
+        }
+    }
+
+    let duration = samples.len() as f32 / sample_rate as f32;
+    Ok(MarkupSample {
+        sample: TtsSample {
+            samples,
+            sample_rate,
+            duration,
+        },
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, sid: i32, speed: f32) -> MarkupItem {
+        MarkupItem::Text {
+            text: text.into(),
+            sid,
+            speed,
+        }
+    }
+
+    #[test]
+    fn nested_scopes_apply_and_restore() {
+        let items = parse_markup(
+            r#"<speak>a <voice sid="3">b <prosody rate="200%">c</prosody> d</voice> e</speak>"#,
+            0,
+            0.5,
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                text("a", 0, 0.5),
+                text("b", 3, 0.5),
+                text("c", 3, 1.0),
+                text("d", 3, 0.5),
+                text("e", 0, 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn adjacent_runs_merge() {
+        let items = parse_markup(
+            r#"<p>one</p> <s>two</s> <voice sid="0">three</voice> <voice sid="1">four</voice>"#,
+            0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![text("one two three", 0, 1.0), text("four", 1, 1.0)]
+        );
+    }
+
+    #[test]
+    fn breaks_split_runs() {
+        let items = parse_markup(
+            r#"a<break time="250ms"/>b<break time="1s"></break>c<break strength="strong" /><break/>"#,
+            0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                text("a", 0, 1.0),
+                MarkupItem::Break { seconds: 0.25 },
+                text("b", 0, 1.0),
+                MarkupItem::Break { seconds: 1.0 },
+                text("c", 0, 1.0),
+                MarkupItem::Break { seconds: 0.75 },
+                MarkupItem::Break { seconds: 0.4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn break_must_be_empty() {
+        assert!(parse_markup(r#"<break time="1s">a</break>"#, 0, 1.0).is_err());
+        assert!(parse_markup(r#"<break time="1s"><p></p></break>"#, 0, 1.0).is_err());
+        assert!(parse_markup(r#"<break time="1s"> </break>"#, 0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for time in ["1", "-1s", "abc ms", "infs", "NaNms", ""] {
+            let markup = format!(r#"<break time="{}"/>"#, time);
+            assert!(parse_markup(&markup, 0, 1.0).is_err(), "{}", time);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_strengths() {
+        for strength in ["loud", "Strong", ""] {
+            let markup = format!(r#"<break strength="{}"/>"#, strength);
+            assert!(parse_markup(&markup, 0, 1.0).is_err(), "{}", strength);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        for rate in ["0", "-50%", "inf", "quick"] {
+            let markup = format!(r#"<prosody rate="{}">a</prosody>"#, rate);
+            assert!(parse_markup(&markup, 0, 1.0).is_err(), "{}", rate);
+        }
+        let items = parse_markup(r#"<prosody rate="slow">a</prosody>"#, 0, 1.0).unwrap();
+        assert_eq!(items, vec![text("a", 0, 0.75)]);
+    }
+
+    #[test]
+    fn unescapes_entities() {
+        let items = parse_markup("&lt;a&gt; &amp; &quot;b&quot; &apos;c&apos;", 0, 1.0).unwrap();
+        assert_eq!(items, vec![text(r#"<a> & "b" 'c'"#, 0, 1.0)]);
+        assert!(parse_markup("&nbsp;", 0, 1.0).is_err());
+    }
+
+    #[test]
+    fn keeps_bare_ampersands() {
+        let items = parse_markup("salt & pepper &amp; R&D &", 0, 1.0).unwrap();
+        assert_eq!(items, vec![text("salt & pepper & R&D &", 0, 1.0)]);
+        let items = parse_markup("fish &chips; too", 0, 1.0);
+        assert!(items.is_err());
+    }
+
+    #[test]
+    fn quoted_values_can_contain_angle_brackets() {
+        let items = parse_markup(r#"<voice sid="2" name="a>b">x</voice> y"#, 0, 1.0).unwrap();
+        assert_eq!(items, vec![text("x", 2, 1.0), text("y", 0, 1.0)]);
+        let items = parse_markup(r#"<voice name='1 > 0' sid='4'>x</voice>"#, 0, 1.0).unwrap();
+        assert_eq!(items, vec![text("x", 4, 1.0)]);
+    }
+
+    #[test]
+    fn renders_breaks_without_text() {
+        let items = parse_markup(r#"<break time="250ms"/><break time="0.5s"/>"#, 0, 1.0).unwrap();
+        let rendered = render(items, 16000, |_, _, _| unreachable!()).unwrap();
+        assert_eq!(rendered.sample.samples.len(), 12000);
+        assert_eq!(rendered.sample.sample_rate, 16000);
+        assert!(rendered.segments.is_empty());
+    }
+
+    #[test]
+    fn renders_segments_in_order() {
+        let items =
+            parse_markup(r#"a <break time="1s"/> <voice sid="1">b</voice>"#, 0, 1.0).unwrap();
+        let rendered = render(items, 10, |text, sid, _| {
+            Ok(TtsSample {
+                samples: vec![sid as f32; text.len() * 5],
+                sample_rate: 10,
+                duration: text.len() as f32 * 0.5,
+            })
+        })
+        .unwrap();
+        assert_eq!(rendered.sample.samples.len(), 20);
+        assert_eq!(rendered.sample.duration, 2.0);
+        let ranges: Vec<_> = rendered.segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(ranges, vec![(0, 5), (15, 20)]);
+        assert_eq!(rendered.sample.samples[15], 1.0);
+    }
+
+    #[test]
+    fn render_stops_on_errors() {
+        let items = vec![text("a", 0, 1.0), text("b", 1, 1.0)];
+        let mut calls = 0;
+        let result = render(items, 10, |_, _, _| {
+            calls += 1;
+            Err(crate::Error::Native("failed".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn say_as_spells() {
+        let items = parse_markup(
+            r#"room <say-as interpret-as="digits">42</say-as> <say-as interpret-as="characters">ab</say-as>"#,
+            0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(items, vec![text("room 4 2 a b", 0, 1.0)]);
+    }
+
+    #[test]
+    fn rejects_unbalanced_tags() {
+        assert!(parse_markup("<voice>a", 0, 1.0).is_err());
+        assert!(parse_markup("a</voice>", 0, 1.0).is_err());
+        assert!(parse_markup("<voice>a</prosody>", 0, 1.0).is_err());
+        assert!(parse_markup("<voice a", 0, 1.0).is_err());
+        assert!(parse_markup("<emphasis>a</emphasis>", 0, 1.0).is_err());
+    }
+}
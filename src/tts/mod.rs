@@ -0,0 +1,1091 @@
+pub mod alignment;
+pub mod dubbing;
+pub mod markup;
+pub mod speakers;
+
+use crate::{
+    audio::{self, AudioFormat},
+    bail, check_file, cstr, logging,
+    model_dir::ModelDir,
+    path_to_cstring,
+    provider::{self, parse_provider, Provider},
+    Result,
+};
+use hound::{WavSpec, WavWriter};
+use speakers::SpeakerMap;
+use std::{
+    ffi::{CStr, CString},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub struct TtsVitsModelConfig {
+    pub(crate) cfg: sherpa_rs_sys::SherpaOnnxOfflineTtsVitsModelConfig,
+}
+
+#[derive(Debug)]
+pub struct TtsMatchaModelConfig {
+    pub(crate) cfg: sherpa_rs_sys::SherpaOnnxOfflineTtsMatchaModelConfig,
+}
+
+#[derive(Debug)]
+pub struct TtsKokoroModelConfig {
+    pub(crate) cfg: sherpa_rs_sys::SherpaOnnxOfflineTtsKokoroModelConfig,
+}
+
+/// Model family used by `OfflineTts`. Only one of them is passed to sherpa-onnx,
+/// the others are left empty.
+#[derive(Debug)]
+pub enum TtsModelConfig {
+    Vits(TtsVitsModelConfig),
+    Matcha(TtsMatchaModelConfig),
+    Kokoro(TtsKokoroModelConfig),
+}
+
+#[derive(Debug)]
+pub struct OfflineTtsModelConfig {
+    pub(crate) cfg: sherpa_rs_sys::SherpaOnnxOfflineTtsModelConfig,
+    providers: Vec<Provider>,
+}
+
+#[derive(Debug)]
+pub struct OfflineTtsConfig {
+    pub(crate) cfg: sherpa_rs_sys::SherpaOnnxOfflineTtsConfig,
+    providers: Vec<Provider>,
+}
+
+#[derive(Debug)]
+pub struct OfflineTts {
+    pub(crate) tts: *mut sherpa_rs_sys::SherpaOnnxOfflineTts,
+    speakers: SpeakerMap,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TtsVitsModelConfigBuilder {
+    model: PathBuf,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    lexicon: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    data_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    dict_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default = "default_noise_scale"))]
+    noise_scale: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_noise_scale_w"))]
+    noise_scale_w: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_length_scale"))]
+    length_scale: f32,
+}
+
+impl TtsVitsModelConfigBuilder {
+    pub fn new<P: Into<PathBuf>>(model: P, tokens: P) -> Self {
+        Self {
+            model: model.into(),
+            tokens: tokens.into(),
+            lexicon: None,
+            data_dir: None,
+            dict_dir: None,
+            noise_scale: default_noise_scale(),
+            noise_scale_w: default_noise_scale_w(),
+            length_scale: default_length_scale(),
+        }
+    }
+
+    pub fn lexicon<P: Into<PathBuf>>(mut self, lexicon: P) -> Self {
+        self.lexicon = Some(lexicon.into());
+        self
+    }
+
+    /// espeak-ng-data directory, required by Piper voices
+    pub fn data_dir<P: Into<PathBuf>>(mut self, data_dir: P) -> Self {
+        self.data_dir = Some(data_dir.into());
+        self
+    }
+
+    /// jieba dictionary directory, used by Chinese voices
+    pub fn dict_dir<P: Into<PathBuf>>(mut self, dict_dir: P) -> Self {
+        self.dict_dir = Some(dict_dir.into());
+        self
+    }
+
+    pub fn noise_scale(mut self, noise_scale: f32) -> Self {
+        self.noise_scale = noise_scale;
+        self
+    }
+
+    pub fn noise_scale_w(mut self, noise_scale_w: f32) -> Self {
+        self.noise_scale_w = noise_scale_w;
+        self
+    }
+
+    /// Larger is slower speech
+    pub fn length_scale(mut self, length_scale: f32) -> Self {
+        self.length_scale = length_scale;
+        self
+    }
+
+    pub fn build(self) -> Result<TtsVitsModelConfig> {
+        check_file(&self.model)?;
+        check_file(&self.tokens)?;
+        check_optional(&[&self.lexicon, &self.data_dir, &self.dict_dir])?;
+        check_scales(&[self.noise_scale, self.noise_scale_w])?;
+        check_length_scale(self.length_scale)?;
+        TtsVitsModelConfig::from_builder(self)
+    }
+}
+
+impl TtsVitsModelConfig {
+    pub fn builder<P: Into<PathBuf>>(model: P, tokens: P) -> TtsVitsModelConfigBuilder {
+        TtsVitsModelConfigBuilder::new(model, tokens)
+    }
+
+    #[deprecated(note = "use `TtsVitsModelConfig::builder` instead")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        model: String,
+        lexicon: String,
+        tokens: String,
+        data_dir: String,
+        noise_scale: f32,
+        noise_scale_w: f32,
+        dict_dir: String,
+        length_scale: f32,
+    ) -> Result<Self> {
+        let mut builder = Self::builder(model, tokens)
+            .noise_scale(noise_scale)
+            .noise_scale_w(noise_scale_w)
+            .length_scale(length_scale);
+        builder.lexicon = optional_path(lexicon);
+        builder.data_dir = optional_path(data_dir);
+        builder.dict_dir = optional_path(dict_dir);
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: TtsVitsModelConfigBuilder) -> Result<Self> {
+        let cfg = sherpa_rs_sys::SherpaOnnxOfflineTtsVitsModelConfig {
+            model: path_to_cstring(&builder.model)?.into_raw(),
+            lexicon: optional_cstring(&builder.lexicon)?.into_raw(),
+            tokens: path_to_cstring(&builder.tokens)?.into_raw(),
+            data_dir: optional_cstring(&builder.data_dir)?.into_raw(),
+            noise_scale: builder.noise_scale,
+            noise_scale_w: builder.noise_scale_w,
+            dict_dir: optional_cstring(&builder.dict_dir)?.into_raw(),
+            length_scale: builder.length_scale,
+        };
+        Ok(Self { cfg })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TtsMatchaModelConfigBuilder {
+    acoustic_model: PathBuf,
+    vocoder: PathBuf,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    lexicon: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    data_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    dict_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default = "default_noise_scale"))]
+    noise_scale: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_length_scale"))]
+    length_scale: f32,
+}
+
+impl TtsMatchaModelConfigBuilder {
+    pub fn new<P: Into<PathBuf>>(acoustic_model: P, vocoder: P, tokens: P) -> Self {
+        Self {
+            acoustic_model: acoustic_model.into(),
+            vocoder: vocoder.into(),
+            tokens: tokens.into(),
+            lexicon: None,
+            data_dir: None,
+            dict_dir: None,
+            noise_scale: default_noise_scale(),
+            length_scale: default_length_scale(),
+        }
+    }
+
+    pub fn lexicon<P: Into<PathBuf>>(mut self, lexicon: P) -> Self {
+        self.lexicon = Some(lexicon.into());
+        self
+    }
+
+    pub fn data_dir<P: Into<PathBuf>>(mut self, data_dir: P) -> Self {
+        self.data_dir = Some(data_dir.into());
+        self
+    }
+
+    pub fn dict_dir<P: Into<PathBuf>>(mut self, dict_dir: P) -> Self {
+        self.dict_dir = Some(dict_dir.into());
+        self
+    }
+
+    pub fn noise_scale(mut self, noise_scale: f32) -> Self {
+        self.noise_scale = noise_scale;
+        self
+    }
+
+    pub fn length_scale(mut self, length_scale: f32) -> Self {
+        self.length_scale = length_scale;
+        self
+    }
+
+    pub fn build(self) -> Result<TtsMatchaModelConfig> {
+        check_file(&self.acoustic_model)?;
+        check_file(&self.vocoder)?;
+        check_file(&self.tokens)?;
+        check_optional(&[&self.lexicon, &self.data_dir, &self.dict_dir])?;
+        check_scales(&[self.noise_scale])?;
+        check_length_scale(self.length_scale)?;
+        TtsMatchaModelConfig::from_builder(self)
+    }
+}
+
+impl TtsMatchaModelConfig {
+    pub fn builder<P: Into<PathBuf>>(
+        acoustic_model: P,
+        vocoder: P,
+        tokens: P,
+    ) -> TtsMatchaModelConfigBuilder {
+        TtsMatchaModelConfigBuilder::new(acoustic_model, vocoder, tokens)
+    }
+
+    #[deprecated(note = "use `TtsMatchaModelConfig::builder` instead")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        acoustic_model: String,
+        vocoder: String,
+        lexicon: String,
+        tokens: String,
+        data_dir: String,
+        noise_scale: f32,
+        dict_dir: String,
+        length_scale: f32,
+    ) -> Result<Self> {
+        let mut builder = Self::builder(acoustic_model, vocoder, tokens)
+            .noise_scale(noise_scale)
+            .length_scale(length_scale);
+        builder.lexicon = optional_path(lexicon);
+        builder.data_dir = optional_path(data_dir);
+        builder.dict_dir = optional_path(dict_dir);
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: TtsMatchaModelConfigBuilder) -> Result<Self> {
+        let cfg = sherpa_rs_sys::SherpaOnnxOfflineTtsMatchaModelConfig {
+            acoustic_model: path_to_cstring(&builder.acoustic_model)?.into_raw(),
+            vocoder: path_to_cstring(&builder.vocoder)?.into_raw(),
+            lexicon: optional_cstring(&builder.lexicon)?.into_raw(),
+            tokens: path_to_cstring(&builder.tokens)?.into_raw(),
+            data_dir: optional_cstring(&builder.data_dir)?.into_raw(),
+            noise_scale: builder.noise_scale,
+            dict_dir: optional_cstring(&builder.dict_dir)?.into_raw(),
+            length_scale: builder.length_scale,
+        };
+        Ok(Self { cfg })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TtsKokoroModelConfigBuilder {
+    model: PathBuf,
+    voices: PathBuf,
+    tokens: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    data_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    dict_dir: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    lexicon: Option<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default = "default_length_scale"))]
+    length_scale: f32,
+}
+
+impl TtsKokoroModelConfigBuilder {
+    pub fn new<P: Into<PathBuf>>(model: P, voices: P, tokens: P) -> Self {
+        Self {
+            model: model.into(),
+            voices: voices.into(),
+            tokens: tokens.into(),
+            data_dir: None,
+            dict_dir: None,
+            lexicon: None,
+            length_scale: default_length_scale(),
+        }
+    }
+
+    pub fn data_dir<P: Into<PathBuf>>(mut self, data_dir: P) -> Self {
+        self.data_dir = Some(data_dir.into());
+        self
+    }
+
+    pub fn dict_dir<P: Into<PathBuf>>(mut self, dict_dir: P) -> Self {
+        self.dict_dir = Some(dict_dir.into());
+        self
+    }
+
+    /// Comma separated lexicons are passed as is
+    pub fn lexicon<P: Into<PathBuf>>(mut self, lexicon: P) -> Self {
+        self.lexicon = Some(lexicon.into());
+        self
+    }
+
+    pub fn length_scale(mut self, length_scale: f32) -> Self {
+        self.length_scale = length_scale;
+        self
+    }
+
+    pub fn build(self) -> Result<TtsKokoroModelConfig> {
+        check_file(&self.model)?;
+        check_file(&self.voices)?;
+        check_file(&self.tokens)?;
+        check_optional(&[&self.data_dir, &self.dict_dir])?;
+        check_length_scale(self.length_scale)?;
+        TtsKokoroModelConfig::from_builder(self)
+    }
+}
+
+impl TtsKokoroModelConfig {
+    pub fn builder<P: Into<PathBuf>>(
+        model: P,
+        voices: P,
+        tokens: P,
+    ) -> TtsKokoroModelConfigBuilder {
+        TtsKokoroModelConfigBuilder::new(model, voices, tokens)
+    }
+
+    #[deprecated(note = "use `TtsKokoroModelConfig::builder` instead")]
+    pub fn new(
+        model: String,
+        voices: String,
+        tokens: String,
+        data_dir: String,
+        dict_dir: String,
+        lexicon: String,
+        length_scale: f32,
+    ) -> Result<Self> {
+        let mut builder = Self::builder(model, voices, tokens).length_scale(length_scale);
+        builder.data_dir = optional_path(data_dir);
+        builder.dict_dir = optional_path(dict_dir);
+        builder.lexicon = optional_path(lexicon);
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: TtsKokoroModelConfigBuilder) -> Result<Self> {
+        let cfg = sherpa_rs_sys::SherpaOnnxOfflineTtsKokoroModelConfig {
+            model: path_to_cstring(&builder.model)?.into_raw(),
+            voices: path_to_cstring(&builder.voices)?.into_raw(),
+            tokens: path_to_cstring(&builder.tokens)?.into_raw(),
+            data_dir: optional_cstring(&builder.data_dir)?.into_raw(),
+            dict_dir: optional_cstring(&builder.dict_dir)?.into_raw(),
+            lexicon: optional_cstring(&builder.lexicon)?.into_raw(),
+            length_scale: builder.length_scale,
+        };
+        Ok(Self { cfg })
+    }
+}
+
+/// Unbuilt `TtsModelConfig`, validated when the `OfflineTtsConfig` is built
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "lowercase")
+)]
+pub enum TtsModelConfigBuilder {
+    Vits(TtsVitsModelConfigBuilder),
+    Matcha(TtsMatchaModelConfigBuilder),
+    Kokoro(TtsKokoroModelConfigBuilder),
+}
+
+impl TtsModelConfigBuilder {
+    pub fn build(self) -> Result<TtsModelConfig> {
+        Ok(match self {
+            Self::Vits(builder) => builder.build()?.into(),
+            Self::Matcha(builder) => builder.build()?.into(),
+            Self::Kokoro(builder) => builder.build()?.into(),
+        })
+    }
+}
+
+impl From<TtsVitsModelConfigBuilder> for TtsModelConfigBuilder {
+    fn from(builder: TtsVitsModelConfigBuilder) -> Self {
+        Self::Vits(builder)
+    }
+}
+
+impl From<TtsMatchaModelConfigBuilder> for TtsModelConfigBuilder {
+    fn from(builder: TtsMatchaModelConfigBuilder) -> Self {
+        Self::Matcha(builder)
+    }
+}
+
+impl From<TtsKokoroModelConfigBuilder> for TtsModelConfigBuilder {
+    fn from(builder: TtsKokoroModelConfigBuilder) -> Self {
+        Self::Kokoro(builder)
+    }
+}
+
+impl From<TtsVitsModelConfig> for TtsModelConfig {
+    fn from(config: TtsVitsModelConfig) -> Self {
+        Self::Vits(config)
+    }
+}
+
+impl From<TtsMatchaModelConfig> for TtsModelConfig {
+    fn from(config: TtsMatchaModelConfig) -> Self {
+        Self::Matcha(config)
+    }
+}
+
+impl From<TtsKokoroModelConfig> for TtsModelConfig {
+    fn from(config: TtsKokoroModelConfig) -> Self {
+        Self::Kokoro(config)
+    }
+}
+
+impl OfflineTtsModelConfig {
+    #[deprecated(note = "use `OfflineTtsConfig::builder` instead")]
+    pub fn new(
+        debug: bool,
+        model_config: impl Into<TtsModelConfig>,
+        provider: Option<String>,
+        num_threads: i32,
+    ) -> Result<Self> {
+        Self::from_parts(
+            model_config.into(),
+            debug,
+            parse_provider(provider).into_iter().collect(),
+            num_threads,
+        )
+    }
+
+    fn from_parts(
+        model_config: TtsModelConfig,
+        debug: bool,
+        providers: Vec<Provider>,
+        num_threads: i32,
+    ) -> Result<Self> {
+        let debug = if debug { 1 } else { 0 };
+
+        let provider = Provider::candidates(&providers)[0];
+        let provider_c = cstr!(provider.as_str());
+
+        let mut cfg = sherpa_rs_sys::SherpaOnnxOfflineTtsModelConfig {
+            debug,
+            num_threads,
+            provider: provider_c.into_raw(),
+            // Models this crate doesn't set stay at their C API defaults
+            ..unsafe { std::mem::zeroed() }
+        };
+        match model_config {
+            TtsModelConfig::Vits(config) => cfg.vits = config.cfg,
+            TtsModelConfig::Matcha(config) => cfg.matcha = config.cfg,
+            TtsModelConfig::Kokoro(config) => cfg.kokoro = config.cfg,
+        }
+        Ok(Self { cfg, providers })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfflineTtsConfigBuilder {
+    model: TtsModelConfigBuilder,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+    #[cfg_attr(feature = "serde", serde(default = "default_max_num_sentences"))]
+    max_num_sentences: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rule_fsts: Vec<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rule_fars: Vec<PathBuf>,
+    #[cfg_attr(feature = "serde", serde(default = "default_silence_scale"))]
+    silence_scale: f32,
+}
+
+impl OfflineTtsConfigBuilder {
+    pub fn new(model: impl Into<TtsModelConfigBuilder>) -> Self {
+        Self {
+            model: model.into(),
+            debug: false,
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+            max_num_sentences: default_max_num_sentences(),
+            rule_fsts: Vec::new(),
+            rule_fars: Vec::new(),
+            silence_scale: default_silence_scale(),
+        }
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Sentences synthesized per batch
+    pub fn max_num_sentences(mut self, max_num_sentences: i32) -> Self {
+        self.max_num_sentences = max_num_sentences;
+        self
+    }
+
+    /// Text normalization FST, may be called more than once
+    pub fn rule_fst<P: Into<PathBuf>>(mut self, rule_fst: P) -> Self {
+        self.rule_fsts.push(rule_fst.into());
+        self
+    }
+
+    /// Text normalization FST archive, may be called more than once
+    pub fn rule_far<P: Into<PathBuf>>(mut self, rule_far: P) -> Self {
+        self.rule_fars.push(rule_far.into());
+        self
+    }
+
+    /// Scale of the silence between sentences
+    pub fn silence_scale(mut self, silence_scale: f32) -> Self {
+        self.silence_scale = silence_scale;
+        self
+    }
+
+    pub fn build(self) -> Result<OfflineTtsConfig> {
+        for rule in self.rule_fsts.iter().chain(&self.rule_fars) {
+            check_file(rule)?;
+        }
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        if self.max_num_sentences < 1 {
+            bail!(
+                InvalidConfig,
+                "max_num_sentences must be at least 1, got {}",
+                self.max_num_sentences
+            )
+        }
+        if self.silence_scale < 0.0 {
+            bail!(
+                InvalidConfig,
+                "silence_scale can't be negative, got {}",
+                self.silence_scale
+            )
+        }
+
+        let model = OfflineTtsModelConfig::from_parts(
+            self.model.build()?,
+            self.debug,
+            self.providers,
+            self.num_threads,
+        )?;
+        let mut config = OfflineTtsConfig::from_parts(
+            model,
+            self.max_num_sentences,
+            join_paths(&self.rule_fars),
+            join_paths(&self.rule_fsts),
+        )?;
+        config.cfg.silence_scale = self.silence_scale;
+        Ok(config)
+    }
+}
+
+impl OfflineTtsConfig {
+    pub fn builder(model: impl Into<TtsModelConfigBuilder>) -> OfflineTtsConfigBuilder {
+        OfflineTtsConfigBuilder::new(model)
+    }
+
+    #[deprecated(note = "use `OfflineTtsConfig::builder` instead")]
+    pub fn new(
+        model: OfflineTtsModelConfig,
+        max_num_sentences: i32,
+        rule_fars: String,
+        rule_fsts: String,
+    ) -> Result<Self> {
+        Self::from_parts(model, max_num_sentences, rule_fars, rule_fsts)
+    }
+
+    fn from_parts(
+        model: OfflineTtsModelConfig,
+        max_num_sentences: i32,
+        rule_fars: String,
+        rule_fsts: String,
+    ) -> Result<Self> {
+        let rule_fars_c = cstr!(rule_fars);
+        let rule_fsts_c = cstr!(rule_fsts);
+
+        let cfg = sherpa_rs_sys::SherpaOnnxOfflineTtsConfig {
+            max_num_sentences,
+            model: model.cfg,
+            rule_fars: rule_fars_c.into_raw(),
+            rule_fsts: rule_fsts_c.into_raw(),
+            silence_scale: default_silence_scale(),
+        };
+        Ok(OfflineTtsConfig {
+            cfg,
+            providers: model.providers,
+        })
+    }
+}
+
+fn default_noise_scale() -> f32 {
+    0.667
+}
+
+fn default_noise_scale_w() -> f32 {
+    0.8
+}
+
+fn default_length_scale() -> f32 {
+    1.0
+}
+
+fn default_num_threads() -> i32 {
+    1
+}
+
+fn default_max_num_sentences() -> i32 {
+    1
+}
+
+/// sherpa-onnx default
+fn default_silence_scale() -> f32 {
+    0.2
+}
+
+fn optional_path(path: String) -> Option<PathBuf> {
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+fn optional_cstring(path: &Option<PathBuf>) -> Result<CString> {
+    match path {
+        Some(path) => path_to_cstring(path),
+        None => Ok(CString::default()),
+    }
+}
+
+fn join_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// VITS and Matcha take one lexicon, `lexicon.txt` if there are several
+fn single_lexicon(dir: &Path, mut lexicons: Vec<PathBuf>) -> Result<Option<PathBuf>> {
+    if lexicons.len() > 1 {
+        let Some(default) = lexicons.iter().position(|lexicon| {
+            lexicon
+                .file_name()
+                .is_some_and(|name| name == "lexicon.txt")
+        }) else {
+            bail!(
+                ModelLoad,
+                "found several lexicons in {}: {}. Pass the files explicitly instead",
+                dir.display(),
+                join_paths(&lexicons)
+            )
+        };
+        return Ok(Some(lexicons.swap_remove(default)));
+    }
+    Ok(lexicons.pop())
+}
+
+fn check_optional(paths: &[&Option<PathBuf>]) -> Result<()> {
+    for path in paths.iter().copied().flatten() {
+        check_file(path)?;
+    }
+    Ok(())
+}
+
+fn check_scales(scales: &[f32]) -> Result<()> {
+    if let Some(scale) = scales.iter().find(|scale| **scale < 0.0) {
+        bail!(
+            InvalidConfig,
+            "noise scales can't be negative, got {}",
+            scale
+        )
+    }
+    Ok(())
+}
+
+fn check_length_scale(length_scale: f32) -> Result<()> {
+    if length_scale <= 0.0 {
+        bail!(
+            InvalidConfig,
+            "length_scale must be positive, got {}",
+            length_scale
+        )
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct TtsSample {
+    pub samples: Vec<f32>,
+    pub sample_rate: i32,
+    /// Duration in seconds
+    pub duration: f32,
+}
+
+impl TtsSample {
+    pub fn write_to_wav(&self, filename: &str) -> Result<()> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+
+        let mut writer = WavWriter::create(filename, spec)?;
+
+        for &sample in &self.samples {
+            writer.write_sample(sample)?;
+        }
+
+        writer.finalize()?;
+
+        Ok(())
+    }
+
+    /// Encode the samples in the given format into any writer (file, socket, buffer)
+    pub fn write_to<W: Write>(&self, writer: W, format: AudioFormat) -> Result<()> {
+        audio::write_audio(writer, &self.samples, self.sample_rate as u32, format)
+    }
+
+    /// Encode the samples in the given format in memory
+    pub fn to_bytes(&self, format: AudioFormat) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.write_to(&mut buffer, format)?;
+        Ok(buffer)
+    }
+
+    /// Resample to another rate, eg. 8000 for telephony
+    pub fn resample(&self, sample_rate: i32) -> Result<TtsSample> {
+        if sample_rate <= 0 {
+            bail!(
+                InvalidConfig,
+                "sample rate must be positive, got {}",
+                sample_rate
+            )
+        }
+        if self.sample_rate <= 0 {
+            bail!(
+                InvalidAudio,
+                "sample rate must be positive, got {}",
+                self.sample_rate
+            )
+        }
+        let samples = audio::resample(&self.samples, self.sample_rate as u32, sample_rate as u32);
+        let duration = samples.len() as f32 / sample_rate as f32;
+        Ok(TtsSample {
+            samples,
+            sample_rate,
+            duration,
+        })
+    }
+
+    /// Play on a speaker (or any output) and wait until it's done
+    #[cfg(feature = "audio-io")]
+    pub fn play(&self, output: &mut impl crate::audio_io::AudioOutput) -> Result<()> {
+        output.play(&self.samples, self.sample_rate)?;
+        output.wait()
+    }
+}
+
+impl OfflineTts {
+    pub fn new(mut config: OfflineTtsConfig) -> Result<Self> {
+        let tts = provider::create(&config.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.cfg.model.provider = provider_c.as_ptr();
+            Ok(logging::load("text to speech", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateOfflineTts(&config.cfg)
+            }))
+        })?;
+        if tts.is_null() {
+            bail!(ModelLoad, "failed to create text to speech engine")
+        }
+        let speakers = Self::bundled_speakers(&config).unwrap_or_default();
+        Ok(Self { tts, speakers })
+    }
+
+    /// Detect the model family and files of a sherpa-onnx TTS release directory.
+    ///
+    /// A `voices.bin` means Kokoro, a hifigan or vocos vocoder means Matcha and
+    /// a single model means VITS. `espeak-ng-data`, `dict`, lexicons and rule
+    /// FSTs/FARs are passed along when present. Kokoro gets every `lexicon*.txt`,
+    /// VITS and Matcha only one.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref();
+        let dir = ModelDir::open(path)?;
+        let tokens = dir.tokens()?;
+        let data_dir = dir.get("espeak-ng-data");
+        let dict_dir = dir.get("dict");
+        let lexicons = dir.find("lexicon", "txt");
+
+        let vocoder = match dir.onnx("hifigan")? {
+            Some(vocoder) => Some(vocoder),
+            None => dir.onnx("vocos")?,
+        };
+        let Some(model) = dir.single_onnx(vocoder.as_deref())? else {
+            bail!(
+                ModelLoad,
+                "no text to speech model in {}. {}",
+                path.display(),
+                dir.found()
+            )
+        };
+
+        let model: TtsModelConfigBuilder = if let Some(voices) = dir.get("voices.bin") {
+            let mut builder = TtsKokoroModelConfig::builder(model, voices, tokens);
+            builder.data_dir = data_dir;
+            builder.dict_dir = dict_dir;
+            // Multi-lingual models take several lexicons separated by commas
+            builder.lexicon = (!lexicons.is_empty()).then(|| PathBuf::from(join_paths(&lexicons)));
+            builder.into()
+        } else if let Some(vocoder) = vocoder {
+            let mut builder = TtsMatchaModelConfig::builder(model, vocoder, tokens);
+            builder.data_dir = data_dir;
+            builder.dict_dir = dict_dir;
+            builder.lexicon = single_lexicon(path, lexicons)?;
+            builder.into()
+        } else {
+            let mut builder = TtsVitsModelConfig::builder(model, tokens);
+            builder.data_dir = data_dir;
+            builder.dict_dir = dict_dir;
+            builder.lexicon = single_lexicon(path, lexicons)?;
+            builder.into()
+        };
+
+        let mut config = OfflineTtsConfig::builder(model);
+        for rule_fst in dir.find("", "fst") {
+            config = config.rule_fst(rule_fst);
+        }
+        for rule_far in dir.find("", "far") {
+            config = config.rule_far(rule_far);
+        }
+        Self::new(config.build()?)
+    }
+
+    /// Piper voices ship `<model>.onnx.json` with a `speaker_id_map`
+    fn bundled_speakers(config: &OfflineTtsConfig) -> Option<SpeakerMap> {
+        let model = config.cfg.model.vits.model;
+        if model.is_null() {
+            return None;
+        }
+        let model = unsafe { CStr::from_ptr(model) }.to_str().ok()?;
+        let path = format!("{}.json", model);
+        if model.is_empty() || !Path::new(&path).exists() {
+            return None;
+        }
+        match SpeakerMap::from_file(&path) {
+            Ok(speakers) => Some(speakers),
+            Err(error) => {
+                tracing::debug!("ignoring speakers from {}: {}", path, error);
+                None
+            }
+        }
+    }
+
+    /// Sample rate of the generated audio
+    pub fn sample_rate(&self) -> i32 {
+        unsafe { sherpa_rs_sys::SherpaOnnxOfflineTtsSampleRate(self.tts) }
+    }
+
+    /// Number of speakers supported by the model, valid sids are `0..num_speakers`
+    pub fn num_speakers(&self) -> i32 {
+        unsafe { sherpa_rs_sys::SherpaOnnxOfflineTtsNumSpeakers(self.tts) }
+    }
+
+    /// Replace the speaker name table. Every sid must exist in the model.
+    pub fn set_speakers(&mut self, speakers: SpeakerMap) -> Result<()> {
+        for (name, sid) in speakers.voices() {
+            if let Err(error) = self.check_sid(sid) {
+                bail!(InvalidConfig, "speaker `{}`: {}", name, error)
+            }
+        }
+        self.speakers = speakers;
+        Ok(())
+    }
+
+    /// Load the speaker name table from a file, see `SpeakerMap::from_file`
+    pub fn load_speakers<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.set_speakers(SpeakerMap::from_file(path)?)
+    }
+
+    pub fn speakers(&self) -> &SpeakerMap {
+        &self.speakers
+    }
+
+    pub fn speaker_id(&self, name: &str) -> Option<i32> {
+        self.speakers.get(name)
+    }
+
+    pub fn generate_with_speaker(
+        &mut self,
+        text: String,
+        speaker: &str,
+        speed: f32,
+    ) -> Result<TtsSample> {
+        let Some(sid) = self.speaker_id(speaker) else {
+            bail!(InvalidConfig, "unknown speaker `{}`", speaker)
+        };
+        self.generate(text, sid, speed)
+    }
+
+    fn check_sid(&self, sid: i32) -> Result<()> {
+        let num_speakers = self.num_speakers();
+        if sid < 0 || (num_speakers > 0 && sid >= num_speakers) {
+            bail!(
+                InvalidConfig,
+                "sid {} is out of range, the model has {} speakers",
+                sid,
+                num_speakers
+            )
+        }
+        Ok(())
+    }
+
+    pub fn generate(&mut self, text: String, sid: i32, speed: f32) -> Result<TtsSample> {
+        self.check_sid(sid)?;
+        let text_c = CString::new(text)?;
+        unsafe {
+            let audio_ptr = logging::infer("text to speech", || {
+                sherpa_rs_sys::SherpaOnnxOfflineTtsGenerate(self.tts, text_c.as_ptr(), sid, speed)
+            });
+            if audio_ptr.is_null() {
+                bail!(Native, "audio is null")
+            }
+            let sample = read_audio(audio_ptr.read());
+            sherpa_rs_sys::SherpaOnnxDestroyOfflineTtsGeneratedAudio(audio_ptr);
+            sample
+        }
+    }
+}
+
+/// Copy the generated audio, which stays owned by sherpa-onnx
+///
+/// # Safety
+///
+/// `audio` must come from a generated audio that isn't destroyed yet
+unsafe fn read_audio(audio: sherpa_rs_sys::SherpaOnnxGeneratedAudio) -> Result<TtsSample> {
+    if audio.n.is_negative() {
+        bail!(Native, "no samples found")
+    }
+    if audio.samples.is_null() {
+        bail!(Native, "audio samples are null")
+    }
+    let samples = std::slice::from_raw_parts(audio.samples, audio.n as usize).to_vec();
+    let sample_rate = audio.sample_rate;
+    let duration = samples.len() as f32 / sample_rate as f32;
+    Ok(TtsSample {
+        samples,
+        sample_rate,
+        duration,
+    })
+}
+
+unsafe impl Send for OfflineTts {}
+unsafe impl Sync for OfflineTts {}
+
+impl Drop for OfflineTts {
+    fn drop(&mut self) {
+        unsafe {
+            sherpa_rs_sys::SherpaOnnxDestroyOfflineTts(self.tts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_a_single_lexicon() {
+        let dir = Path::new("model");
+        assert_eq!(single_lexicon(dir, Vec::new()).unwrap(), None);
+        assert_eq!(
+            single_lexicon(dir, vec!["model/lexicon-zh.txt".into()]).unwrap(),
+            Some(PathBuf::from("model/lexicon-zh.txt"))
+        );
+        assert_eq!(
+            single_lexicon(
+                dir,
+                vec!["model/lexicon-us-en.txt".into(), "model/lexicon.txt".into()]
+            )
+            .unwrap(),
+            Some(PathBuf::from("model/lexicon.txt"))
+        );
+        let error = single_lexicon(
+            dir,
+            vec![
+                "model/lexicon-us-en.txt".into(),
+                "model/lexicon-zh.txt".into(),
+            ],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("several lexicons"), "{}", error);
+    }
+
+    fn sample(sample_rate: i32) -> TtsSample {
+        TtsSample {
+            samples: vec![0.0; 22050],
+            sample_rate,
+            duration: 1.0,
+        }
+    }
+
+    #[test]
+    fn resample_changes_rate_and_keeps_duration() {
+        let resampled = sample(22050).resample(8000).unwrap();
+        assert_eq!(resampled.sample_rate, 8000);
+        assert_eq!(resampled.samples.len(), 8000);
+        assert_eq!(resampled.duration, 1.0);
+    }
+
+    #[test]
+    fn resample_rejects_non_positive_rates() {
+        for rate in [0, -8000] {
+            let error = sample(22050).resample(rate).unwrap_err();
+            assert!(matches!(error, crate::Error::InvalidConfig(_)), "{}", error);
+        }
+        let error = sample(0).resample(8000).unwrap_err();
+        assert!(matches!(error, crate::Error::InvalidAudio(_)), "{}", error);
+    }
+}
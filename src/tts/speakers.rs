@@ -0,0 +1,172 @@
+use crate::{bail, error::ResultExt, Result};
+use std::{collections::HashMap, path::Path};
+
+/// Maps speaker names to the `sid` used by multi speaker models
+#[derive(Debug, Clone, Default)]
+pub struct SpeakerMap {
+    names: HashMap<String, i32>,
+}
+
+impl SpeakerMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a speaker table from a file.
+    ///
+    /// `.json` files are read as Piper model configs (`speaker_id_map`).
+    /// Anything else is read as text with one speaker per line, either
+    /// `name sid` or just `name` where the sid is the line index. Lines starting with `#` are skipped.
+    /// A name listed twice or a negative sid is an `Error::Parse`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read speakers from {}", path.display()))?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_piper_json(&content)
+        } else {
+            Self::from_text(&content)
+        }
+    }
+
+    pub fn from_piper_json(content: &str) -> Result<Self> {
+        let config: serde_json::Value = serde_json::from_str(content)?;
+        let mut speakers = Self::new();
+        let Some(map) = config.get("speaker_id_map") else {
+            return Ok(speakers);
+        };
+        let Some(map) = map.as_object() else {
+            bail!(Parse, "speaker_id_map must be an object")
+        };
+        for (name, sid) in map {
+            let Some(sid) = sid.as_i64().and_then(|sid| i32::try_from(sid).ok()) else {
+                bail!(Parse, "speaker `{}` has an invalid id {}", name, sid)
+            };
+            speakers.insert_new(name.clone(), sid)?;
+        }
+        Ok(speakers)
+    }
+
+    pub fn from_text(content: &str) -> Result<Self> {
+        let mut speakers = Self::new();
+        let lines = content
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        for (index, (number, line)) in lines.enumerate() {
+            let (name, sid) = match line.rsplit_once(char::is_whitespace) {
+                Some((name, sid)) if sid.parse::<i64>().is_ok() => match sid.parse::<i32>() {
+                    Ok(sid) if sid >= 0 => (name.trim(), sid),
+                    _ => bail!(Parse, "invalid speaker id `{}` on line {}", sid, number),
+                },
+                _ => (line, index as i32),
+            };
+            speakers.insert_new(name.to_string(), sid)?;
+        }
+        Ok(speakers)
+    }
+
+    /// Like `insert`, but a name can only be listed once in a file
+    fn insert_new(&mut self, name: String, sid: i32) -> Result<()> {
+        if sid < 0 {
+            bail!(Parse, "speaker `{}` has a negative id {}", name, sid)
+        }
+        if self.names.contains_key(&name) {
+            bail!(Parse, "speaker `{}` is listed twice", name)
+        }
+        self.names.insert(name, sid);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, name: String, sid: i32) {
+        self.names.insert(name, sid);
+    }
+
+    pub fn get(&self, name: &str) -> Option<i32> {
+        self.names.get(name).copied()
+    }
+
+    /// Speakers sorted by sid
+    pub fn voices(&self) -> Vec<(&str, i32)> {
+        let mut voices: Vec<_> = self
+            .names
+            .iter()
+            .map(|(name, sid)| (name.as_str(), *sid))
+            .collect();
+        voices.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+        voices
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl FromIterator<(String, i32)> for SpeakerMap {
+    fn from_iter<T: IntoIterator<Item = (String, i32)>>(iter: T) -> Self {
+        Self {
+            names: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_piper_speaker_id_map() {
+        let speakers = SpeakerMap::from_piper_json(
+            r#"{"audio": {"sample_rate": 22050}, "speaker_id_map": {"p239": 1, "p236": 0}}"#,
+        )
+        .unwrap();
+        assert_eq!(speakers.voices(), vec![("p236", 0), ("p239", 1)]);
+        assert_eq!(speakers.get("p239"), Some(1));
+        assert_eq!(speakers.get("p240"), None);
+    }
+
+    #[test]
+    fn piper_config_without_speakers_is_empty() {
+        let speakers = SpeakerMap::from_piper_json(r#"{"num_speakers": 1}"#).unwrap();
+        assert!(speakers.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_piper_ids() {
+        assert!(SpeakerMap::from_piper_json(r#"{"speaker_id_map": {"a": "1"}}"#).is_err());
+        assert!(SpeakerMap::from_piper_json(r#"{"speaker_id_map": {"a": -1}}"#).is_err());
+        assert!(SpeakerMap::from_piper_json(r#"{"speaker_id_map": {"a": 1.5}}"#).is_err());
+        assert!(SpeakerMap::from_piper_json(r#"{"speaker_id_map": [1]}"#).is_err());
+        assert!(SpeakerMap::from_piper_json("{").is_err());
+    }
+
+    #[test]
+    fn reads_text_with_blank_lines_and_comments() {
+        let speakers =
+            SpeakerMap::from_text("# name sid\n\nalice\n  bob 7  \n\n# more\ncarol\nmary ann 3\n")
+                .unwrap();
+        assert_eq!(
+            speakers.voices(),
+            vec![("alice", 0), ("carol", 2), ("mary ann", 3), ("bob", 7)]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let error = SpeakerMap::from_text("alice 0\nbob 1\nalice 2").unwrap_err();
+        assert!(error.to_string().contains("alice"), "{}", error);
+        assert!(SpeakerMap::from_text("alice\nalice").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let error = SpeakerMap::from_text("alice 0\n\nbob -1").unwrap_err();
+        assert!(error.to_string().contains("line 3"), "{}", error);
+        assert!(SpeakerMap::from_text("bob 99999999999").is_err());
+    }
+}
@@ -1,10 +1,14 @@
-use crate::get_default_provider;
-use eyre::Result;
-use std::ffi::CString;
+use crate::{
+    bail, check_file, cstr, logging, path_to_cstring,
+    provider::{self, parse_provider, Provider},
+    Result,
+};
+use std::{ffi::CString, path::PathBuf};
 
 #[derive(Debug)]
 pub struct VadConfig {
     pub(crate) cfg: sherpa_rs_sys::SherpaOnnxVadModelConfig,
+    providers: Vec<Provider>,
 }
 
 #[derive(Debug)]
@@ -12,7 +16,175 @@ pub struct Vad {
     pub(crate) vad: *mut sherpa_rs_sys::SherpaOnnxVoiceActivityDetector,
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VadConfigBuilder {
+    model: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default = "default_min_silence_duration"))]
+    min_silence_duration: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_min_speech_duration"))]
+    min_speech_duration: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_threshold"))]
+    threshold: f32,
+    #[cfg_attr(feature = "serde", serde(default = "default_sample_rate"))]
+    sample_rate: i32,
+    #[cfg_attr(feature = "serde", serde(default = "default_window_size"))]
+    window_size: i32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            alias = "provider",
+            deserialize_with = "crate::provider::one_or_many"
+        )
+    )]
+    providers: Vec<Provider>,
+    #[cfg_attr(feature = "serde", serde(default = "default_num_threads"))]
+    num_threads: i32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    debug: bool,
+}
+
+fn default_min_silence_duration() -> f32 {
+    0.5
+}
+
+fn default_min_speech_duration() -> f32 {
+    0.25
+}
+
+fn default_threshold() -> f32 {
+    0.5
+}
+
+fn default_sample_rate() -> i32 {
+    16000
+}
+
+fn default_window_size() -> i32 {
+    512
+}
+
+fn default_num_threads() -> i32 {
+    1
+}
+
+impl VadConfigBuilder {
+    pub fn new<P: Into<PathBuf>>(model: P) -> Self {
+        Self {
+            model: model.into(),
+            min_silence_duration: default_min_silence_duration(),
+            min_speech_duration: default_min_speech_duration(),
+            threshold: default_threshold(),
+            sample_rate: default_sample_rate(),
+            window_size: default_window_size(),
+            providers: Vec::new(),
+            num_threads: default_num_threads(),
+            debug: false,
+        }
+    }
+
+    /// Silence in seconds that ends a speech segment
+    pub fn min_silence_duration(mut self, seconds: f32) -> Self {
+        self.min_silence_duration = seconds;
+        self
+    }
+
+    /// Speech shorter than this many seconds is dropped
+    pub fn min_speech_duration(mut self, seconds: f32) -> Self {
+        self.min_speech_duration = seconds;
+        self
+    }
+
+    /// Speech probability threshold, between 0 and 1
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: i32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Samples per call to the model, 512 for 16kHz and 256 for 8kHz
+    pub fn window_size(mut self, window_size: i32) -> Self {
+        self.window_size = window_size;
+        self
+    }
+
+    /// Same as `providers([provider])`
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers = vec![provider];
+        self
+    }
+
+    /// Providers to load the model with, tried in order until one works. `Cpu`
+    /// is tried last if it's not in the list, see `Provider::candidates`
+    pub fn providers(mut self, providers: impl IntoIterator<Item = Provider>) -> Self {
+        self.providers = providers.into_iter().collect();
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: i32) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn build(self) -> Result<VadConfig> {
+        check_file(&self.model)?;
+        if !(0.0..=1.0).contains(&self.threshold) {
+            bail!(
+                InvalidConfig,
+                "threshold must be between 0 and 1, got {}",
+                self.threshold
+            )
+        }
+        if self.min_silence_duration < 0.0 || self.min_speech_duration < 0.0 {
+            bail!(
+                InvalidConfig,
+                "durations can't be negative, got min_silence_duration={} min_speech_duration={}",
+                self.min_silence_duration,
+                self.min_speech_duration
+            )
+        }
+        if self.sample_rate != 8000 && self.sample_rate != 16000 {
+            bail!(
+                InvalidConfig,
+                "sample_rate must be 8000 or 16000, got {}",
+                self.sample_rate
+            )
+        }
+        if self.window_size < 1 {
+            bail!(
+                InvalidConfig,
+                "window_size must be positive, got {}",
+                self.window_size
+            )
+        }
+        if self.num_threads < 1 {
+            bail!(
+                InvalidConfig,
+                "num_threads must be at least 1, got {}",
+                self.num_threads
+            )
+        }
+        VadConfig::from_builder(self)
+    }
+}
+
 impl VadConfig {
+    pub fn builder<P: Into<PathBuf>>(model: P) -> VadConfigBuilder {
+        VadConfigBuilder::new(model)
+    }
+
+    #[deprecated(note = "use `VadConfig::builder` instead")]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         model: String,
         min_silence_duration: f32,
@@ -23,28 +195,44 @@ impl VadConfig {
         provider: Option<String>,
         num_threads: Option<i32>,
         debug: Option<bool>,
-    ) -> Self {
-        let provider = provider.unwrap_or(get_default_provider());
-        let provider = CString::new(provider).unwrap();
-        let model = CString::new(model).unwrap();
+    ) -> Result<Self> {
+        let mut builder = Self::builder(model)
+            .min_silence_duration(min_silence_duration)
+            .min_speech_duration(min_speech_duration)
+            .threshold(threshold)
+            .sample_rate(sample_rate)
+            .window_size(window_size)
+            .num_threads(num_threads.unwrap_or(default_num_threads()))
+            .debug(debug.unwrap_or(false));
+        builder.providers = parse_provider(provider).into_iter().collect();
+        Self::from_builder(builder)
+    }
+
+    fn from_builder(builder: VadConfigBuilder) -> Result<Self> {
+        let provider = Provider::candidates(&builder.providers)[0].as_str();
+        let provider = cstr!(provider);
+        let model = path_to_cstring(&builder.model)?;
 
         let silero_vad = sherpa_rs_sys::SherpaOnnxSileroVadModelConfig {
             model: model.into_raw(),
-            min_silence_duration,
-            min_speech_duration,
-            threshold,
-            window_size,
+            min_silence_duration: builder.min_silence_duration,
+            min_speech_duration: builder.min_speech_duration,
+            threshold: builder.threshold,
+            window_size: builder.window_size,
+            ..unsafe { std::mem::zeroed() }
         };
-        let debug = debug.unwrap_or(false);
-        let debug = if debug { 1 } else { 0 };
+        let debug = if builder.debug { 1 } else { 0 };
         let cfg = sherpa_rs_sys::SherpaOnnxVadModelConfig {
             debug,
             provider: provider.into_raw(),
-            num_threads: num_threads.unwrap_or(1),
-            sample_rate,
+            num_threads: builder.num_threads,
+            sample_rate: builder.sample_rate,
             silero_vad,
         };
-        Self { cfg }
+        Ok(Self {
+            cfg,
+            providers: builder.providers,
+        })
     }
 
     pub fn as_ptr(&self) -> *const sherpa_rs_sys::SherpaOnnxVadModelConfig {
@@ -52,33 +240,50 @@ impl VadConfig {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SpeechSegment {
     pub start: i32,
     pub samples: Vec<f32>,
 }
 
 impl Vad {
-    pub fn new_from_config(config: VadConfig, buffer_size_in_seconds: f32) -> Result<Self> {
-        unsafe {
-            let vad = sherpa_rs_sys::SherpaOnnxCreateVoiceActivityDetector(
-                config.as_ptr(),
-                buffer_size_in_seconds,
-            );
-            Ok(Self { vad })
+    pub fn new_from_config(mut config: VadConfig, buffer_size_in_seconds: f32) -> Result<Self> {
+        let vad = provider::create(&config.providers, |provider| {
+            let provider_c = cstr!(provider.as_str());
+            config.cfg.provider = provider_c.as_ptr();
+            Ok(logging::load("voice activity detector", || unsafe {
+                sherpa_rs_sys::SherpaOnnxCreateVoiceActivityDetector(
+                    &config.cfg,
+                    buffer_size_in_seconds,
+                )
+            }))
+        })?;
+        if vad.is_null() {
+            bail!(ModelLoad, "failed to create voice activity detector")
         }
+        Ok(Self { vad })
     }
 
     pub fn is_empty(&mut self) -> bool {
         unsafe { sherpa_rs_sys::SherpaOnnxVoiceActivityDetectorEmpty(self.vad) == 1 }
     }
 
-    pub fn front(&mut self) -> SpeechSegment {
+    /// The oldest detected speech segment, `None` if there's none
+    pub fn front(&mut self) -> Option<SpeechSegment> {
+        if self.is_empty() {
+            return None;
+        }
         unsafe {
             let segment_ptr = sherpa_rs_sys::SherpaOnnxVoiceActivityDetectorFront(self.vad);
+            if segment_ptr.is_null() {
+                return None;
+            }
             let raw_segment = segment_ptr.read();
-            let samples: &[f32] =
-                std::slice::from_raw_parts(raw_segment.samples, raw_segment.n as usize);
+            let samples: &[f32] = if raw_segment.samples.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts(raw_segment.samples, raw_segment.n.max(0) as usize)
+            };
 
             let segment = SpeechSegment {
                 samples: samples.to_vec(),
@@ -88,7 +293,7 @@ impl Vad {
             // Free
             sherpa_rs_sys::SherpaOnnxDestroySpeechSegment(segment_ptr);
 
-            segment
+            Some(segment)
         }
     }
 
@@ -98,16 +303,19 @@ impl Vad {
         }
     }
 
-    pub fn accept_waveform(&mut self, mut samples: Vec<f32>) {
-        let samples_ptr = samples.as_mut_ptr();
-        let samples_length = samples.len();
-        unsafe {
-            sherpa_rs_sys::SherpaOnnxVoiceActivityDetectorAcceptWaveform(
-                self.vad,
-                samples_ptr,
-                samples_length.try_into().unwrap(),
-            );
-        };
+    pub fn accept_waveform(&mut self, samples: Vec<f32>) {
+        logging::infer("voice activity detector", || {
+            // The C API counts samples with an i32, the detector buffers them so they can come in parts
+            for part in samples.chunks(i32::MAX as usize) {
+                unsafe {
+                    sherpa_rs_sys::SherpaOnnxVoiceActivityDetectorAcceptWaveform(
+                        self.vad,
+                        part.as_ptr(),
+                        part.len() as i32,
+                    );
+                }
+            }
+        })
     }
 
     pub fn pop(&mut self) {
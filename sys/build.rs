@@ -3,6 +3,26 @@ use std::env;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// Static libraries of a sherpa-onnx build, in link order
+const STATIC_LIBS: &[&str] = &[
+    "sherpa-onnx-c-api",
+    "sherpa-onnx-core",
+    "kaldi-native-fbank-core",
+    "kaldi-decoder-core",
+    "sherpa-onnx-kaldifst-core",
+    "sherpa-onnx-fstfar",
+    "ssentencepiece_core",
+    "sherpa-onnx-fst",
+    "onnxruntime",
+];
+
+/// Static libraries of a sherpa-onnx build with TTS
+const TTS_STATIC_LIBS: &[&str] = &["espeak-ng", "piper_phonemize", "ucd"];
+
+/// Newest parts of the C API the crate uses: the Kokoro TTS model and the TTS
+/// `silence_scale`, both in sherpa-onnx v1.11.0
+const REQUIRED_C_API: &[&str] = &["SherpaOnnxOfflineTtsKokoroModelConfig", "silence_scale"];
+
 fn copy_folder(src: &Path, dst: &Path) {
     std::fs::create_dir_all(dst).expect("Failed to create dst directory");
     if cfg!(unix) {
@@ -24,52 +44,162 @@ fn copy_folder(src: &Path, dst: &Path) {
     }
 }
 
-fn main() {
-    let target = env::var("TARGET").unwrap();
-    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
-    let sherpa_dst = out_dir.join("sherpa-onnx");
-    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("Failed to get CARGO_MANIFEST_DIR");
-    let sherpa_src = Path::new(&manifest_dir).join("sherpa-onnx");
-    let build_shared_libs = cfg!(feature = "directml") || cfg!(feature = "cuda");
-    let profile = if cfg!(debug_assertions) {
-        "Debug"
+fn shared_lib_suffix() -> &'static str {
+    if cfg!(windows) {
+        ".dll"
+    } else if cfg!(target_os = "macos") {
+        ".dylib"
     } else {
-        "Release"
+        ".so"
+    }
+}
+
+/// Copy the shared libraries of `dir` next to the binaries, so they run as is
+fn copy_shared_libs(dir: &Path, out_dir: &Path) {
+    for entry in glob::glob(&format!(
+        "{}/*{}",
+        dir.to_str().unwrap(),
+        shared_lib_suffix()
+    ))
+    .unwrap()
+    .flatten()
+    {
+        let target_dir = out_dir.parent().unwrap().parent().unwrap().parent().unwrap();
+        let dst = target_dir.join(entry.file_name().unwrap());
+        std::fs::copy(entry, dst).unwrap();
+    }
+}
+
+/// Link a sherpa-onnx install given by `SHERPA_LIB_PATH`, either its prefix
+/// (with `lib` and `include`) or its library directory.
+///
+/// Static libraries are used if the install has them, unless `SHERPA_STATIC=0`.
+/// Returns the include directory, if the install has headers.
+fn link_prebuilt(path: &Path, out_dir: &Path) -> Option<PathBuf> {
+    if !path.exists() {
+        panic!("SHERPA_LIB_PATH {} does not exist", path.display());
+    }
+    let lib_dir = if path.join("lib").is_dir() {
+        path.join("lib")
+    } else {
+        path.to_path_buf()
+    };
+    let static_lib = |name: &str| {
+        if cfg!(windows) {
+            lib_dir.join(format!("{}.lib", name))
+        } else {
+            lib_dir.join(format!("lib{}.a", name))
+        }
+    };
+    let is_static = match env::var("SHERPA_STATIC").as_deref() {
+        Ok("0") => false,
+        Ok(_) => true,
+        // Windows import libraries are .lib too, only static builds have the core library
+        Err(_) => static_lib("sherpa-onnx-core").exists(),
     };
 
-    // Prepare sherpa-onnx source
-    if !sherpa_dst.exists() {
-        copy_folder(&sherpa_src, &sherpa_dst);
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    if is_static {
+        let tts_libs: &[&str] = if cfg!(feature = "tts") {
+            TTS_STATIC_LIBS
+        } else {
+            &[]
+        };
+        // Only some of the libraries exist, depending on the sherpa-onnx version and options
+        for name in STATIC_LIBS.iter().chain(tts_libs) {
+            if static_lib(name).exists() {
+                println!("cargo:rustc-link-lib=static={}", name);
+            }
+        }
+    } else {
+        println!("cargo:rustc-link-lib=dylib=sherpa-onnx-c-api");
+        copy_shared_libs(&lib_dir, out_dir);
+        // Windows installs put DLLs in bin
+        if path.join("bin").is_dir() {
+            copy_shared_libs(&path.join("bin"), out_dir);
+        }
     }
-    // Speed up build
-    env::set_var(
-        "CMAKE_BUILD_PARALLEL_LEVEL",
-        std::thread::available_parallelism()
-            .unwrap()
-            .get()
-            .to_string(),
-    );
 
-    // Bindings
-    let bindings = bindgen::Builder::default()
-        .header("wrapper.h")
-        .clang_arg(format!("-I{}", sherpa_dst.display()))
+    let include_dir = path.join("include");
+    include_dir.is_dir().then_some(include_dir)
+}
+
+/// Link the sherpa-onnx found by pkg-config, returns its include directories
+fn link_pkg_config() -> Option<Vec<PathBuf>> {
+    match pkg_config::Config::new().probe("sherpa-onnx") {
+        Ok(library) => Some(library.include_paths),
+        Err(error) => {
+            println!(
+                "cargo:warning=sherpa-onnx not found by pkg-config: {}",
+                error
+            );
+            None
+        }
+    }
+}
+
+/// Fail early on a sherpa-onnx older than the bindings, rather than on missing fields
+fn check_header(header: &Path) {
+    let Ok(content) = std::fs::read_to_string(header) else {
+        return;
+    };
+    if let Some(missing) = REQUIRED_C_API.iter().find(|api| !content.contains(*api)) {
+        panic!(
+            "{} has no {}, this sherpa-onnx is too old. Update it as described in BUILDING.md",
+            header.display(),
+            missing
+        );
+    }
+}
+
+/// Generate the bindings of the C API with bindgen, from an installed header if
+/// there's one, else from the vendored source
+#[cfg(feature = "bindgen")]
+fn generate_bindings(bindings_path: &Path, include_dirs: &[PathBuf], sherpa_dst: &Path) {
+    let installed_header = include_dirs
+        .iter()
+        .find(|dir| dir.join("sherpa-onnx/c-api/c-api.h").exists());
+    let builder = match installed_header {
+        Some(include_dir) => bindgen::Builder::default()
+            .header_contents("wrapper.h", "#include \"sherpa-onnx/c-api/c-api.h\"")
+            .clang_arg(format!("-I{}", include_dir.display())),
+        None => bindgen::Builder::default()
+            .header("wrapper.h")
+            .clang_arg(format!("-I{}", sherpa_dst.display())),
+    };
+    let bindings = builder
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
         .expect("Failed to generate bindings");
 
     // Write the generated bindings to an output file
-    let bindings_path = out_dir.join("bindings.rs");
     bindings
         .write_to_file(bindings_path)
         .expect("Failed to write bindings");
+}
 
-    println!("cargo:rerun-if-changed=wrapper.h");
-    println!("cargo:rerun-if-changed=./sherpa-onnx");
+/// Pre-generated bindings of the vendored header, layouts depend on the pointer width
+fn pregenerated_bindings(manifest_dir: &Path) -> PathBuf {
+    let pointer_width = env::var("CARGO_CFG_TARGET_POINTER_WIDTH").unwrap();
+    manifest_dir.join(format!("src/bindings/bindings_{}.rs", pointer_width))
+}
+
+/// Build the vendored sherpa-onnx with CMake and link it
+fn build_vendored(out_dir: &Path, sherpa_dst: &Path, profile: &str) {
+    let build_shared_libs = cfg!(feature = "directml") || cfg!(feature = "cuda");
+
+    // Speed up build
+    env::set_var(
+        "CMAKE_BUILD_PARALLEL_LEVEL",
+        std::thread::available_parallelism()
+            .unwrap()
+            .get()
+            .to_string(),
+    );
 
     // Build with Cmake
 
-    let mut config = Config::new(&sherpa_dst);
+    let mut config = Config::new(sherpa_dst);
 
     config
         .define("SHERPA_ONNX_ENABLE_C_API", "ON")
@@ -83,6 +213,16 @@ fn main() {
         if cfg!(feature = "tts") { "ON" } else { "OFF" },
     );
 
+    // Speaker diarization, the C API's pipeline. Speaker embeddings are built either way
+    config.define(
+        "SHERPA_ONNX_ENABLE_SPEAKER_DIARIZATION",
+        if cfg!(feature = "diarization") {
+            "ON"
+        } else {
+            "OFF"
+        },
+    );
+
     // Cuda https://k2-fsa.github.io/k2/installation/cuda-cudnn.html
     if cfg!(feature = "cuda") {
         config.define("SHERPA_ONNX_ENABLE_GPU", "ON");
@@ -95,9 +235,15 @@ fn main() {
         config.define("BUILD_SHARED_LIBS", "ON");
     }
 
-    if cfg!(any(windows, target_os = "linux")) {
-        config.define("SHERPA_ONNX_ENABLE_PORTAUDIO", "ON");
-    }
+    // Only used by the sherpa-onnx binaries, the Rust crate doesn't need it
+    config.define(
+        "SHERPA_ONNX_ENABLE_PORTAUDIO",
+        if cfg!(feature = "portaudio") {
+            "ON"
+        } else {
+            "OFF"
+        },
+    );
 
     // General
     config
@@ -166,6 +312,91 @@ fn main() {
         println!("cargo:rustc-link-lib=static=onnxruntime_providers_tensorrt");
     }
 
+    // copy DLLs to target
+    if build_shared_libs {
+        copy_shared_libs(&out_dir.join("lib"), out_dir);
+    }
+}
+
+fn main() {
+    let target = env::var("TARGET").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let sherpa_dst = out_dir.join("sherpa-onnx");
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("Failed to get CARGO_MANIFEST_DIR");
+    let sherpa_src = Path::new(&manifest_dir).join("sherpa-onnx");
+    let profile = if cfg!(debug_assertions) {
+        "Debug"
+    } else {
+        "Release"
+    };
+
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-changed=./sherpa-onnx");
+    println!("cargo:rerun-if-env-changed=SHERPA_LIB_PATH");
+    println!("cargo:rerun-if-env-changed=SHERPA_STATIC");
+    println!("cargo:rerun-if-env-changed=SHERPA_USE_PKG_CONFIG");
+
+    // Prepare sherpa-onnx source, its header is also the fallback for installs without one
+    let vendored = sherpa_src.join("CMakeLists.txt").exists();
+    if vendored && !sherpa_dst.exists() {
+        copy_folder(&sherpa_src, &sherpa_dst);
+    }
+
+    // An existing install: SHERPA_LIB_PATH, then pkg-config if asked for or
+    // if the submodule isn't checked out, else build from source
+    let use_pkg_config = env::var_os("SHERPA_USE_PKG_CONFIG").is_some() || !vendored;
+    let include_dirs = if let Some(path) = env::var_os("SHERPA_LIB_PATH") {
+        link_prebuilt(Path::new(&path), &out_dir)
+            .into_iter()
+            .collect()
+    } else if let Some(include_dirs) = use_pkg_config.then(link_pkg_config).flatten() {
+        include_dirs
+    } else if vendored {
+        build_vendored(&out_dir, &sherpa_dst, profile);
+        Vec::new()
+    } else {
+        panic!(
+            "sherpa-onnx source not found at {}. Check out the submodule with `git submodule update --init`, \
+             or set SHERPA_LIB_PATH to an existing sherpa-onnx install",
+            sherpa_src.display()
+        );
+    };
+
+    if vendored {
+        check_header(&sherpa_src.join("sherpa-onnx/c-api/c-api.h"));
+    }
+    for include_dir in &include_dirs {
+        check_header(&include_dir.join("sherpa-onnx/c-api/c-api.h"));
+    }
+
+    // Bindings: generated by default, pre-generated ones when the `bindgen` feature is off
+    let bindings_path = out_dir.join("bindings.rs");
+    let pregenerated = pregenerated_bindings(Path::new(&manifest_dir));
+    println!("cargo:rerun-if-changed={}", pregenerated.display());
+    println!("cargo:rerun-if-env-changed=SHERPA_UPDATE_BINDINGS");
+    #[cfg(feature = "bindgen")]
+    {
+        generate_bindings(&bindings_path, &include_dirs, &sherpa_dst);
+        if env::var_os("SHERPA_UPDATE_BINDINGS").is_some() {
+            if !include_dirs.is_empty() {
+                panic!("SHERPA_UPDATE_BINDINGS needs the vendored header, unset SHERPA_LIB_PATH");
+            }
+            std::fs::create_dir_all(pregenerated.parent().unwrap()).unwrap();
+            std::fs::copy(&bindings_path, &pregenerated)
+                .expect("Failed to update pre-generated bindings");
+        }
+    }
+    #[cfg(not(feature = "bindgen"))]
+    {
+        if !pregenerated.exists() {
+            panic!(
+                "no pre-generated bindings at {} for this pointer width, enable the `bindgen` feature to generate them, see BUILDING.md",
+                pregenerated.display()
+            );
+        }
+        std::fs::copy(&pregenerated, &bindings_path).expect("Failed to copy bindings");
+    }
+
     // macOS
     if cfg!(target_os = "macos") {
         println!("cargo:rustc-link-lib=framework=Foundation");
@@ -187,29 +418,6 @@ fn main() {
             println!("cargo:rustc-link-search={}", path);
         }
     }
-
-    // copy DLLs to target
-    if build_shared_libs {
-        let suffix = if cfg!(windows) {
-            ".dll"
-        } else if cfg!(target_os = "macos") {
-            ".dylib"
-        } else {
-            ".so"
-        };
-        for entry in glob::glob(&format!(
-            "{}/*{}",
-            out_dir.join("lib").to_str().unwrap(),
-            suffix
-        ))
-        .unwrap()
-        .flatten()
-        {
-            let target_dir = out_dir.parent().unwrap().parent().unwrap().parent().unwrap();
-            let dst = target_dir.join(entry.file_name().unwrap());
-            std::fs::copy(entry, dst).unwrap();
-        }
-    }
 }
 
 fn macos_link_search_path() -> Option<String> {
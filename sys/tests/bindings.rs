@@ -0,0 +1,24 @@
+//! The pre-generated bindings must match the vendored header.
+//!
+//! Runs in the Lint workflow once the bindings are committed, locally with `cargo test -p sherpa-rs-sys --features bindgen`
+//! after updating the sherpa-onnx submodule. On failure, regenerate the bindings with
+//! `SHERPA_UPDATE_BINDINGS=1 cargo build -p sherpa-rs-sys --features bindgen`
+//! for each pointer width.
+
+#![cfg(feature = "bindgen")]
+
+use std::path::Path;
+
+#[test]
+fn pregenerated_bindings_match_header() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join(format!("src/bindings/bindings_{}.rs", usize::BITS));
+    let pregenerated = std::fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("failed to read {}: {}", path.display(), error));
+    assert!(
+        generated == pregenerated,
+        "{} is out of date with the sherpa-onnx header",
+        path.display()
+    );
+}